@@ -0,0 +1,71 @@
+//! Compares the `DMatrix` products against the plain loops they replaced.
+//! Run with `cargo bench --bench dmatrix`.
+
+use hopfield::dmatrix::DMatrix;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+fn naive_mul_vec(m: &DMatrix<f64>, input: &[f64], output: &mut [f64]) {
+    for r in 0..m.rows() {
+        let mut acc = 0.;
+
+        for c in 0..m.cols() {
+            acc += m[(r, c)] * input[c];
+        }
+
+        output[r] = acc;
+    }
+}
+
+// Walks the matrix column by column, striding over the rows.
+fn naive_trans_mul_vec(m: &DMatrix<f64>, input: &[f64], output: &mut [f64]) {
+    for c in 0..m.cols() {
+        let mut acc = 0.;
+
+        for r in 0..m.rows() {
+            acc += m[(r, c)] * input[r];
+        }
+
+        output[c] = acc;
+    }
+}
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    const ITERATIONS: u32 = 50;
+
+    f();
+
+    let start = Instant::now();
+    for _i in 0..ITERATIONS {
+        f();
+    }
+
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    for n in [64, 256, 1024, 2048] {
+        let mut m = DMatrix::new(n, n, 0.);
+        for r in 0..n {
+            for c in 0..n {
+                m[(r, c)] = ((r * n + c) as f64 * 0.37).sin();
+            }
+        }
+        let x: Vec<f64> = (0..n).map(|i| (i as f64 * 0.1).cos()).collect();
+        let mut y = vec![0.; n];
+
+        let naive = time(|| naive_mul_vec(black_box(&m), black_box(&x), &mut y));
+        let kernel = time(|| m.mul_vec(black_box(&x), black_box(&mut y)));
+        println!(
+            "mul_vec       {:5}x{:<5} naive {:>10.2?}  kernel {:>10.2?}",
+            n, n, naive, kernel
+        );
+
+        let naive = time(|| naive_trans_mul_vec(black_box(&m), black_box(&x), &mut y));
+        let kernel = time(|| m.trans_mul_vec(black_box(&x), black_box(&mut y)));
+        println!(
+            "trans_mul_vec {:5}x{:<5} naive {:>10.2?}  kernel {:>10.2?}",
+            n, n, naive, kernel
+        );
+    }
+}
@@ -1,7 +1,15 @@
+//! Reads integers from stdin and prints the average recall error of the best
+//! of a group of networks every few inputs. The first argument, if given, is
+//! the seed for all random numbers; runs with the same seed and input print
+//! the same errors.
+
 use hopfield::classic::*;
+use hopfield::seed::{Seed, SeedRng};
 use hopfield::smatrix::SMatrix;
 use hopfield::state::State;
 use rand::Rng;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
 const STATE_SIZE: usize = 200;
 const NETWORK_COUNT: usize = 20;
@@ -13,6 +21,29 @@ const LEARN_AMOUNT: f64 = 1. / 200.;
 const LEARNING_NOISE_AMOUNT: f64 = 0.1;
 const ITERATION_PRINT: usize = 100;
 
+struct Network {
+    weights: SMatrix<f64>,
+    state: Vec<f64>,
+    rng: SeedRng,
+    error: f64,
+}
+
+impl Network {
+    fn step(&mut self, current_output: &[f64], pattern: &[f64]) {
+        let zeros: Vec<f64> = vec![0.; STATE_SIZE];
+        let mut temp_state: Vec<f64> = vec![0.; STATE_SIZE];
+
+        self.state.decay(STATE_DECAY);
+        self.state.add_pattern(current_output, PATTERN_AMOUNT);
+        self.state.add_noise(&mut self.rng, NOISE_AMOUNT);
+
+        update_state_sync(&self.weights, &zeros, &self.state, &mut temp_state);
+
+        self.error = temp_state.error_norm(pattern);
+        self.state.copy_from(&temp_state);
+    }
+}
+
 fn read_integer() -> Option<u32> {
     let stdin = std::io::stdin();
     let mut buffer = String::new();
@@ -21,18 +52,27 @@ fn read_integer() -> Option<u32> {
 }
 
 fn main() {
-    let mut weights: Vec<SMatrix<f64>> = Vec::new();
-    let mut states: Vec<Vec<f64>> = Vec::new();
-    let mut rng = rand::rng();
-
-    for _i in 0..NETWORK_COUNT {
+    let seed = Seed(
+        std::env::args()
+            .nth(1)
+            .map(|s| s.parse().expect("the seed must be an integer"))
+            .unwrap_or(0),
+    );
+    let mut networks: Vec<Network> = Vec::new();
+    let mut rng = seed.rng();
+
+    for i in 0..NETWORK_COUNT {
         let mut w = SMatrix::new(STATE_SIZE, 0.);
         initialize_weights(&mut w, &mut rng, INITIAL_NOISE_AMOUNT);
         let mut s = vec![0.; STATE_SIZE];
         s.add_noise(&mut rng, 1.0);
 
-        weights.push(w);
-        states.push(s);
+        networks.push(Network {
+            weights: w,
+            state: s,
+            rng: seed.child(i as u64).rng(),
+            error: 0.,
+        });
     }
 
     let mut current_output: Vec<f64> = vec![0.; STATE_SIZE];
@@ -46,26 +86,25 @@ fn main() {
 
         let mut pattern: Vec<f64> = vec![0.; STATE_SIZE];
         let mut temp_state: Vec<f64> = vec![0.; STATE_SIZE];
-        let zeros: Vec<f64> = vec![0.; STATE_SIZE];
 
         pattern.from_bits(STATE_SIZE, input);
 
-        let mut min_error = f64::MAX;
-        let mut min_net: Option<usize> = None;
+        #[cfg(feature = "parallel")]
+        networks
+            .par_iter_mut()
+            .for_each(|n| n.step(&current_output, &pattern));
 
-        for i in 0..NETWORK_COUNT {
-            states[i].decay(STATE_DECAY);
-            states[i].add_pattern(&current_output, PATTERN_AMOUNT);
-            states[i].add_noise(&mut rng, NOISE_AMOUNT);
+        #[cfg(not(feature = "parallel"))]
+        networks
+            .iter_mut()
+            .for_each(|n| n.step(&current_output, &pattern));
 
-            update_state_sync(&weights[i], &zeros, &states[i], &mut temp_state);
-
-            let err = temp_state.error_norm(&pattern);
-
-            states[i].copy_from(&temp_state);
+        let mut min_error = f64::MAX;
+        let mut min_net: Option<usize> = None;
 
-            if err < min_error {
-                min_error = err;
+        for (i, network) in networks.iter().enumerate() {
+            if network.error < min_error {
+                min_error = network.error;
                 min_net = Some(i);
             }
         }
@@ -73,12 +112,12 @@ fn main() {
         if let Some(winner) = min_net {
             let selected = rng.random_range(0..NETWORK_COUNT);
 
-            temp_state.copy_from(&states[winner]);
+            temp_state.copy_from(&networks[winner].state);
             temp_state.add_noise(&mut rng, LEARNING_NOISE_AMOUNT);
 
-            storkey_learn(&mut weights[selected], &temp_state, LEARN_AMOUNT);
+            storkey_learn(&mut networks[selected].weights, &temp_state, LEARN_AMOUNT);
 
-            current_output.copy_from(&states[winner]);
+            current_output.copy_from(&networks[winner].state);
         }
 
         error_avg += min_error;
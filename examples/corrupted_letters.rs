@@ -0,0 +1,186 @@
+//! The classic "corrupted letter" demo: a few letters are stored in a classic,
+//! a modern and a predictive coding network, each letter is corrupted by
+//! flipping some of its pixels and then recalled by all three networks.
+//!
+//! Run with PBM or PGM files as arguments to use those images (all of the
+//! same size) instead of the built-in letters. With `--out DIR` the recalled
+//! images are also written to DIR as PGM files.
+
+use hopfield::classic;
+use hopfield::dmatrix::DMatrix;
+use hopfield::image::Image;
+use hopfield::modern;
+use hopfield::pcn::PCN;
+use hopfield::seed::Seed;
+use hopfield::smatrix::SMatrix;
+use hopfield::state::State;
+use std::fs::File;
+use std::path::PathBuf;
+
+const LETTERS: [&str; 4] = [
+    "\
+...####...
+..##..##..
+.##....##.
+.##....##.
+.########.
+.##....##.
+.##....##.
+.##....##.
+.##....##.
+..........",
+    "\
+.##....##.
+.##....##.
+.##....##.
+.##....##.
+.########.
+.##....##.
+.##....##.
+.##....##.
+.##....##.
+..........",
+    "\
+.########.
+.########.
+....##....
+....##....
+....##....
+....##....
+....##....
+....##....
+....##....
+..........",
+    "\
+.##....##.
+..##..##..
+...####...
+....##....
+....##....
+...####...
+..##..##..
+.##....##.
+.##....##.
+..........",
+];
+
+const NOISE: f64 = 0.15;
+const STEPS: usize = 10;
+
+fn recall_classic(weights: &SMatrix<f64>, cue: &[f64]) -> Vec<f64> {
+    let bias = vec![0.; cue.len()];
+    let mut state = cue.to_vec();
+    let mut next = vec![0.; cue.len()];
+
+    for _i in 0..STEPS {
+        classic::update_state_sync(weights, &bias, &state, &mut next);
+        std::mem::swap(&mut state, &mut next);
+    }
+
+    state
+}
+
+fn recall_modern(memory: &DMatrix<f64>, cue: &[f64]) -> Vec<f64> {
+    let mut state = cue.to_vec();
+    let mut next = vec![0.; cue.len()];
+
+    for _i in 0..STEPS {
+        modern::update_state_sync(memory, &state, &mut next);
+        std::mem::swap(&mut state, &mut next);
+    }
+
+    state
+}
+
+// The PCN picks the closest memory code for the cue and generates the stored
+// pattern from it.
+fn recall_pcn(pcn: &mut PCN, cue: &[f64]) -> Vec<f64> {
+    let mask = vec![true; cue.len()];
+    let index = pcn.recall(cue, &mask, 0.1, 200).index;
+    let code = pcn.codebook()[index].clone();
+
+    pcn.generate(&code)
+}
+
+fn main() {
+    let mut out = None;
+    let mut images = Vec::new();
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        if arg == "--out" {
+            out = args.next().map(PathBuf::from);
+        } else {
+            let mut file = File::open(&arg).expect("cannot open image");
+            images.push(Image::read_pnm(&mut file).expect("cannot read image"));
+        }
+    }
+
+    if images.is_empty() {
+        images = LETTERS.iter().map(|l| Image::from_ascii(l)).collect();
+    }
+
+    let (width, height) = (images[0].width(), images[0].height());
+    let n = width * height;
+    let patterns: Vec<Vec<f64>> = images.iter().map(|i| i.to_bipolar(0.5)).collect();
+
+    let mut weights = SMatrix::new(n, 0.);
+    let mut memory = DMatrix::new(0, n, 0.);
+    let mut pcn = PCN::new(3, 16, n);
+    let mut rng = Seed(42).rng();
+
+    for p in &patterns {
+        classic::try_storkey_learn(&mut weights, p, 1. / n as f64)
+            .expect("images must have the same size");
+        modern::learn(&mut memory, p);
+    }
+    pcn.memorize(&patterns, &mut rng, 0.1, 100, 0.1, 20);
+
+    for (k, p) in patterns.iter().enumerate() {
+        let mut cue = p.clone();
+        cue.flip_with_probability(&mut rng, NOISE);
+        let recalled = [
+            ("classic", recall_classic(&weights, &cue)),
+            ("modern", recall_modern(&memory, &cue)),
+            ("pcn", recall_pcn(&mut pcn, &cue)),
+        ];
+
+        let mut columns = vec![("original", p.clone()), ("corrupted", cue)];
+        columns.extend(recalled.iter().cloned());
+
+        let rendered: Vec<(&str, String)> = columns
+            .iter()
+            .map(|(name, s)| {
+                (
+                    *name,
+                    Image::from_state(width, height, s).unwrap().to_string(),
+                )
+            })
+            .collect();
+
+        for (name, _) in &rendered {
+            print!("{:<w$}  ", name, w = width);
+        }
+        println!();
+
+        for line in 0..height.div_ceil(2) {
+            for (_, art) in &rendered {
+                print!("{:<w$}  ", art.lines().nth(line).unwrap_or(""), w = width);
+            }
+            println!();
+        }
+        println!();
+
+        if let Some(dir) = &out {
+            for (name, state) in &recalled {
+                let path = dir.join(format!("{}_{}.pgm", k, name));
+                let mut file = File::create(path).expect("cannot create output");
+
+                Image::from_state(width, height, state)
+                    .unwrap()
+                    .write_pgm(&mut file)
+                    .expect("cannot write output");
+            }
+        }
+    }
+}
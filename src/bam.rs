@@ -0,0 +1,273 @@
+//! Kosko's Bidirectional Associative Memory (BAM). Two layers of bipolar
+//! neurons, `x` with `n` and `y` with `p` neurons, are connected by an
+//! `n x p` weight matrix `W` that stores pairs `(x, y)` as sums of outer
+//! products `x y^T`. This makes the BAM a hetero-associative memory: either
+//! half of a pair recalls the other one.
+//!
+//! Recall alternates between the layers, `y = sign(W^T x)` and then
+//! `x = sign(W y)`. A neuron whose input is exactly zero keeps its value.
+//! The energy `E(x, y) = -x^T W y` decreases with every change, so recall
+//! always ends in a stable pair.
+
+use crate::dmatrix::DMatrix;
+use crate::error::{check_vec, HopfieldError};
+use crate::scalar::Scalar;
+
+/// The result of `recall`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Recall {
+    /// The number of passes in both directions.
+    pub steps: usize,
+    /// Whether the pair is stable.
+    pub converged: bool,
+}
+
+// Set `state` to the signs of `field`, keeping the values where the field is
+// zero. Returns the number of changed neurons.
+fn threshold<T: Scalar>(field: &[T], state: &mut [T]) -> usize {
+    let mut changes = 0;
+
+    for i in 0..state.len() {
+        let v = if field[i] > T::ZERO {
+            T::ONE
+        } else if field[i] < T::ZERO {
+            -T::ONE
+        } else {
+            state[i]
+        };
+
+        if v != state[i] {
+            state[i] = v;
+            changes += 1;
+        }
+    }
+
+    changes
+}
+
+/// Store the pair `(x, y)`: `W += x y^T`.
+pub fn learn<T: Scalar>(weights: &mut DMatrix<T>, x: &[T], y: &[T]) {
+    debug_assert_eq!(weights.rows(), x.len());
+    debug_assert_eq!(weights.cols(), y.len());
+
+    for r in 0..x.len() {
+        for c in 0..y.len() {
+            weights[(r, c)] += x[r] * y[c];
+        }
+    }
+}
+
+/// Like `learn`, but checks the sizes of and values in `x` and `y`.
+pub fn try_learn<T: Scalar>(
+    weights: &mut DMatrix<T>,
+    x: &[T],
+    y: &[T],
+) -> Result<(), HopfieldError> {
+    check_vec("x", weights.rows(), x)?;
+    check_vec("y", weights.cols(), y)?;
+
+    learn(weights, x, y);
+    Ok(())
+}
+
+/// Update the `y` layer from `x`. Returns the number of changed neurons.
+pub fn update_forward<T: Scalar>(weights: &DMatrix<T>, x: &[T], y: &mut [T]) -> usize {
+    let mut field = vec![T::ZERO; weights.cols()];
+
+    weights.trans_mul_vec(x, &mut field);
+    threshold(&field, y)
+}
+
+/// Like `update_forward`, but checks the sizes of and values in `x` and `y`.
+pub fn try_update_forward<T: Scalar>(
+    weights: &DMatrix<T>,
+    x: &[T],
+    y: &mut [T],
+) -> Result<usize, HopfieldError> {
+    check_vec("x", weights.rows(), x)?;
+    check_vec("y", weights.cols(), y)?;
+
+    Ok(update_forward(weights, x, y))
+}
+
+/// Update the `x` layer from `y`. Returns the number of changed neurons.
+pub fn update_backward<T: Scalar>(weights: &DMatrix<T>, y: &[T], x: &mut [T]) -> usize {
+    let mut field = vec![T::ZERO; weights.rows()];
+
+    weights.mul_vec(y, &mut field);
+    threshold(&field, x)
+}
+
+/// Like `update_backward`, but checks the sizes of and values in `y` and `x`.
+pub fn try_update_backward<T: Scalar>(
+    weights: &DMatrix<T>,
+    y: &[T],
+    x: &mut [T],
+) -> Result<usize, HopfieldError> {
+    check_vec("y", weights.cols(), y)?;
+    check_vec("x", weights.rows(), x)?;
+
+    Ok(update_backward(weights, y, x))
+}
+
+/// Alternate forward and backward updates, starting with the forward one,
+/// until neither layer changes or `max_steps` passes have been made. To
+/// recall the `y` of a stored pair from a cue for `x`, start with `y` all
+/// zeros; to recall `x` from `y`, start with `x` all zeros, which the first
+/// forward pass leaves unchanged.
+pub fn recall<T: Scalar>(
+    weights: &DMatrix<T>,
+    x: &mut [T],
+    y: &mut [T],
+    max_steps: usize,
+) -> Recall {
+    for step in 0..max_steps {
+        let changes = update_forward(weights, x, y) + update_backward(weights, y, x);
+
+        if changes == 0 {
+            return Recall {
+                steps: step + 1,
+                converged: true,
+            };
+        }
+    }
+
+    Recall {
+        steps: max_steps,
+        converged: false,
+    }
+}
+
+/// Like `recall`, but checks the sizes of and values in `x` and `y`.
+pub fn try_recall<T: Scalar>(
+    weights: &DMatrix<T>,
+    x: &mut [T],
+    y: &mut [T],
+    max_steps: usize,
+) -> Result<Recall, HopfieldError> {
+    check_vec("x", weights.rows(), x)?;
+    check_vec("y", weights.cols(), y)?;
+
+    Ok(recall(weights, x, y, max_steps))
+}
+
+/// The energy `-x^T W y` of a pair.
+pub fn energy<T: Scalar>(weights: &DMatrix<T>, x: &[T], y: &[T]) -> T {
+    let mut field = vec![T::ZERO; weights.rows()];
+    let mut acc = T::ZERO;
+
+    weights.mul_vec(y, &mut field);
+
+    for r in 0..x.len() {
+        acc -= x[r] * field[r];
+    }
+
+    acc
+}
+
+/// Like `energy`, but checks the sizes of and values in `x` and `y`.
+pub fn try_energy<T: Scalar>(weights: &DMatrix<T>, x: &[T], y: &[T]) -> Result<T, HopfieldError> {
+    check_vec("x", weights.rows(), x)?;
+    check_vec("y", weights.cols(), y)?;
+
+    Ok(energy(weights, x, y))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seed::Seed;
+    use crate::state::State;
+    use rand::Rng;
+
+    const N: usize = 64;
+    const P: usize = 12;
+
+    fn pairs() -> Vec<(Vec<f64>, Vec<f64>)> {
+        let mut rng = Seed(9).rng();
+        let mut random = |len: usize| -> Vec<f64> {
+            (0..len)
+                .map(|_| if rng.random_bool(0.5) { 1. } else { -1. })
+                .collect()
+        };
+
+        (0..3).map(|_| (random(N), random(P))).collect()
+    }
+
+    #[test]
+    fn recalls_in_both_directions() {
+        let pairs = pairs();
+        let mut weights = DMatrix::new(N, P, 0.);
+        for (x, y) in &pairs {
+            learn(&mut weights, x, y);
+        }
+
+        let mut rng = Seed(10).rng();
+
+        for (x, y) in &pairs {
+            let mut cue = x.clone();
+            cue.flip_random(&mut rng, 6);
+            let mut label = vec![0.; P];
+            let result = recall(&weights, &mut cue, &mut label, 10);
+
+            assert!(result.converged);
+            assert_eq!(&cue, x);
+            assert_eq!(&label, y);
+
+            let mut pattern = vec![0.; N];
+            let mut label = y.clone();
+            recall(&weights, &mut pattern, &mut label, 10);
+
+            assert_eq!(&pattern, x);
+        }
+    }
+
+    #[test]
+    fn energy_decreases_during_recall() {
+        let pairs = pairs();
+        let mut weights = DMatrix::new(N, P, 0.);
+        for (x, y) in &pairs {
+            learn(&mut weights, x, y);
+        }
+
+        let mut x = pairs[0].0.clone();
+        x.flip_random(&mut Seed(11).rng(), 20);
+        let mut y = pairs[1].1.clone();
+        let mut e = energy(&weights, &x, &y);
+
+        while update_forward(&weights, &x, &mut y) + update_backward(&weights, &y, &mut x) > 0 {
+            let next = energy(&weights, &x, &y);
+
+            assert!(next < e);
+            e = next;
+        }
+
+        assert!(energy(&weights, &pairs[0].0, &pairs[0].1) < 0.);
+    }
+
+    #[test]
+    fn try_variants_check_layers() {
+        let mut weights = DMatrix::new(4, 2, 0.);
+        let (x, y) = (vec![1., -1., 1., -1.], vec![1., -1.]);
+
+        assert_eq!(
+            try_learn(&mut weights, &y, &x),
+            Err(HopfieldError::DimensionMismatch {
+                what: "x",
+                expected: 4,
+                found: 2
+            })
+        );
+        assert!(try_learn(&mut weights, &x, &[f64::NAN, 1.]).is_err());
+        assert_eq!(weights, DMatrix::new(4, 2, 0.));
+
+        try_learn(&mut weights, &x, &y).unwrap();
+
+        let (mut a, mut b) = (x.clone(), vec![0.; 3]);
+        assert!(try_recall(&weights, &mut a, &mut b, 5).is_err());
+        assert!(try_update_forward(&weights, &x, &mut b).is_err());
+        assert!(try_update_backward(&weights, &y, &mut b).is_err());
+        assert_eq!(try_energy(&weights, &x, &y), Ok(-8.));
+    }
+}
@@ -0,0 +1,283 @@
+//! Estimation of the basins of attraction of stored patterns. A pattern is
+//! corrupted with an increasing number of bit flips and recalled many times;
+//! the basin radius is the largest number of flips from which (nearly) all
+//! recalls succeed.
+//!
+//! Every trial draws its flips from its own generator, seeded from the
+//! configured seed, the pattern, the number of flips and the trial number.
+//! Results are therefore reproducible and do not depend on whether the trials
+//! run in parallel (with the `parallel` feature) or not.
+
+use crate::classic::{self, Weights};
+use crate::dmatrix::DMatrix;
+use crate::metrics;
+use crate::modern;
+use crate::scalar::Scalar;
+use crate::seed::Seed;
+use crate::state::State;
+use crate::MaybeSync;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// A network that recalls patterns by running its dynamics from a cue.
+pub trait Retrieve<T: Scalar>: MaybeSync {
+    fn neurons(&self) -> usize;
+
+    /// The state reached from `cue` after at most `max_steps` updates.
+    fn retrieve(&self, cue: &[T], max_steps: usize) -> Vec<T>;
+}
+
+/// A classic network, updated synchronously until it reaches a fixed point.
+pub struct Classic<'a, T, W> {
+    pub weights: &'a W,
+    pub bias: &'a [T],
+}
+
+impl<T: Scalar, W: Weights<T> + MaybeSync> Retrieve<T> for Classic<'_, T, W> {
+    fn neurons(&self) -> usize {
+        self.weights.rows()
+    }
+
+    fn retrieve(&self, cue: &[T], max_steps: usize) -> Vec<T> {
+        iterate(cue, max_steps, |s, next| {
+            classic::update_state_sync(self.weights, self.bias, s, next)
+        })
+    }
+}
+
+/// A modern network, updated until it reaches a fixed point.
+pub struct Modern<'a, T> {
+    pub memory: &'a DMatrix<T>,
+}
+
+impl<T: Scalar> Retrieve<T> for Modern<'_, T> {
+    fn neurons(&self) -> usize {
+        self.memory.cols()
+    }
+
+    fn retrieve(&self, cue: &[T], max_steps: usize) -> Vec<T> {
+        iterate(cue, max_steps, |s, next| {
+            modern::update_state_sync(self.memory, s, next)
+        })
+    }
+}
+
+fn iterate<T: Scalar, F: Fn(&[T], &mut [T])>(cue: &[T], max_steps: usize, update: F) -> Vec<T> {
+    let mut state = cue.to_vec();
+    let mut next = vec![T::ZERO; cue.len()];
+
+    for _i in 0..max_steps {
+        update(&state, &mut next);
+
+        if next == state {
+            break;
+        }
+
+        std::mem::swap(&mut state, &mut next);
+    }
+
+    state
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BasinConfig {
+    /// Recalls per number of flips.
+    pub trials: usize,
+    /// The increment in the number of flips. Up to half the neurons are
+    /// flipped; beyond that a cue is closer to the reversed pattern.
+    pub step: usize,
+    /// The fraction of successful recalls needed at a number of flips for it
+    /// to be inside the basin.
+    pub success_rate: f64,
+    /// The overlap with the pattern from which a recall counts as successful.
+    pub min_overlap: f64,
+    /// The maximum number of updates per recall.
+    pub max_steps: usize,
+    pub seed: Seed,
+}
+
+impl Default for BasinConfig {
+    fn default() -> Self {
+        Self {
+            trials: 50,
+            step: 1,
+            success_rate: 0.9,
+            min_overlap: 1.,
+            max_steps: 100,
+            seed: Seed(0),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Basin {
+    /// The largest number of flips such that at least `success_rate` of the
+    /// recalls succeeded for it and every smaller tested number of flips.
+    pub radius: usize,
+    /// `radius` divided by the number of neurons.
+    pub relative_radius: f64,
+    /// The fraction of successful recalls for each tested number of flips,
+    /// up to the first one outside the basin.
+    pub success: Vec<(usize, f64)>,
+}
+
+fn success_rate<T: Scalar, M: Retrieve<T>>(
+    model: &M,
+    pattern: &[T],
+    flips: usize,
+    config: &BasinConfig,
+    seed: Seed,
+) -> f64 {
+    let trial = |t: usize| {
+        let mut rng = seed.child(flips as u64).child(t as u64).rng();
+        let mut cue = pattern.to_vec();
+        cue.flip_random(&mut rng, flips);
+
+        let result = model.retrieve(&cue, config.max_steps);
+
+        metrics::overlap(pattern, &result) >= config.min_overlap
+    };
+
+    #[cfg(feature = "parallel")]
+    let successes = (0..config.trials)
+        .into_par_iter()
+        .filter(|t| trial(*t))
+        .count();
+    #[cfg(not(feature = "parallel"))]
+    let successes = (0..config.trials).filter(|t| trial(*t)).count();
+
+    successes as f64 / config.trials.max(1) as f64
+}
+
+fn estimate<T: Scalar, M: Retrieve<T>>(
+    model: &M,
+    pattern: &[T],
+    config: &BasinConfig,
+    seed: Seed,
+) -> Basin {
+    debug_assert_eq!(model.neurons(), pattern.len());
+
+    let n = pattern.len();
+    let mut radius = 0;
+    let mut success = Vec::new();
+
+    for flips in (0..=n / 2).step_by(config.step.max(1)) {
+        let rate = success_rate(model, pattern, flips, config, seed);
+        success.push((flips, rate));
+
+        if rate < config.success_rate {
+            break;
+        }
+
+        radius = flips;
+    }
+
+    Basin {
+        radius,
+        relative_radius: radius as f64 / n.max(1) as f64,
+        success,
+    }
+}
+
+/// Estimate the basin of attraction of `pattern` in `model`.
+pub fn estimate_basin<T: Scalar, M: Retrieve<T>>(
+    model: &M,
+    pattern: &[T],
+    config: &BasinConfig,
+) -> Basin {
+    estimate(model, pattern, config, config.seed)
+}
+
+/// Estimate the basin of attraction of each of the stored `patterns`.
+pub fn estimate_basins<T: Scalar, M: Retrieve<T>, P: AsRef<[T]>>(
+    model: &M,
+    patterns: &[P],
+    config: &BasinConfig,
+) -> Vec<Basin> {
+    patterns
+        .iter()
+        .enumerate()
+        .map(|(k, p)| estimate(model, p.as_ref(), config, config.seed.child(k as u64)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::smatrix::SMatrix;
+    use rand::Rng;
+
+    fn random_patterns(count: usize, n: usize, seed: u64) -> Vec<Vec<f64>> {
+        let mut rng = Seed(seed).rng();
+
+        (0..count)
+            .map(|_| {
+                (0..n)
+                    .map(|_| if rng.random_bool(0.5) { 1. } else { -1. })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn basins_shrink_with_load() {
+        const N: usize = 100;
+
+        let config = BasinConfig {
+            trials: 20,
+            step: 5,
+            ..BasinConfig::default()
+        };
+        let bias = vec![0.; N];
+        let mut radii = Vec::new();
+
+        for count in [1, 12] {
+            let patterns = random_patterns(count, N, 7);
+            let mut weights = SMatrix::new(N, 0.);
+            for p in &patterns {
+                classic::hebb_learn(&mut weights, p);
+            }
+
+            let model = Classic {
+                weights: &weights,
+                bias: &bias,
+            };
+            let basins = estimate_basins(&model, &patterns, &config);
+
+            assert_eq!(basins, estimate_basins(&model, &patterns, &config));
+            radii.push(basins[0].radius);
+        }
+
+        // A single pattern is recalled from anything closer to it than to its
+        // reverse.
+        assert_eq!(radii[0], 45);
+        assert!(radii[1] < radii[0]);
+    }
+
+    #[test]
+    fn modern_basin() {
+        const N: usize = 64;
+
+        let patterns = random_patterns(8, N, 3);
+        let mut memory = DMatrix::new(0, N, 0.);
+        for p in &patterns {
+            modern::learn(&mut memory, p);
+        }
+
+        let basin = estimate_basin(
+            &Modern { memory: &memory },
+            &patterns[0],
+            &BasinConfig {
+                trials: 10,
+                step: 4,
+                ..BasinConfig::default()
+            },
+        );
+
+        assert_eq!(basin.success[0], (0, 1.));
+        assert!(basin.radius >= 8);
+        assert_eq!(basin.relative_radius, basin.radius as f64 / N as f64);
+    }
+}
@@ -0,0 +1,574 @@
+//! Bit-packed bipolar states and weights for large classic Hopfield networks.
+//! A `BitState` stores one bit per neuron (set for `+1`, clear for `-1`) in
+//! `u64` words, so distances between states are computed with popcounts. The
+//! local fields can be computed against integer weights (`SMatrix<i8>` or
+//! `SMatrix<i16>`) or against sign-quantised weights in a `BitMatrix`, where
+//! a whole row is multiplied with the state using XOR and popcount.
+
+use crate::error::{check_index, check_len, HopfieldError};
+use crate::scalar::Scalar;
+use crate::smatrix::SMatrix;
+use crate::MaybeSync;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::fmt::{self, Display, Formatter};
+
+const BITS: usize = u64::BITS as usize;
+
+fn word_count(len: usize) -> usize {
+    len.div_ceil(BITS)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "RawBitState")
+)]
+pub struct BitState {
+    len: usize,
+    // Bits past `len` in the last word are always clear.
+    words: Vec<u64>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawBitState {
+    len: usize,
+    words: Vec<u64>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawBitState> for BitState {
+    type Error = String;
+
+    fn try_from(raw: RawBitState) -> Result<Self, String> {
+        if raw.words.len() != word_count(raw.len) {
+            return Err(format!(
+                "a state of {} neurons needs {} words, found {}",
+                raw.len,
+                word_count(raw.len),
+                raw.words.len()
+            ));
+        }
+        if !raw.len.is_multiple_of(BITS)
+            && raw.words.last().is_some_and(|w| w >> (raw.len % BITS) != 0)
+        {
+            return Err(format!("bits set past neuron {}", raw.len));
+        }
+
+        Ok(Self {
+            len: raw.len,
+            words: raw.words,
+        })
+    }
+}
+
+impl Display for BitState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for i in 0..self.len {
+            write!(f, "{}", if self.get(i) { '+' } else { '-' })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl BitState {
+    /// A state of `len` neurons, all `-1`.
+    pub fn new(len: usize) -> Self {
+        Self {
+            len,
+            words: vec![0; word_count(len)],
+        }
+    }
+
+    /// Convert a bipolar state: non-negative values become `+1`, negative
+    /// values `-1`.
+    pub fn from_slice<T: Scalar>(state: &[T]) -> Self {
+        let mut s = Self::new(state.len());
+
+        for i in 0..state.len() {
+            s.set(i, state[i] >= T::ZERO);
+        }
+
+        s
+    }
+
+    /// Write the state as `+1`/`-1` values into `state`.
+    pub fn copy_to<T: Scalar>(&self, state: &mut [T]) {
+        for i in 0..self.len.min(state.len()) {
+            state[i] = if self.get(i) { T::ONE } else { -T::ONE };
+        }
+    }
+
+    pub fn to_vec<T: Scalar>(&self) -> Vec<T> {
+        let mut v = vec![T::ZERO; self.len];
+        self.copy_to(&mut v);
+
+        v
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The packed bits, neuron `i` being bit `i % 64` of word `i / 64`.
+    pub fn as_words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Whether neuron `i` is `+1`.
+    pub fn get(&self, i: usize) -> bool {
+        debug_assert!(i < self.len);
+
+        self.words[i / BITS] >> (i % BITS) & 1 == 1
+    }
+
+    /// The value of neuron `i` as `+1` or `-1`.
+    pub fn value(&self, i: usize) -> i32 {
+        if self.get(i) {
+            1
+        } else {
+            -1
+        }
+    }
+
+    pub fn set(&mut self, i: usize, on: bool) {
+        debug_assert!(i < self.len);
+
+        let bit = 1 << (i % BITS);
+
+        if on {
+            self.words[i / BITS] |= bit;
+        } else {
+            self.words[i / BITS] &= !bit;
+        }
+    }
+
+    pub fn flip(&mut self, i: usize) {
+        debug_assert!(i < self.len);
+
+        self.words[i / BITS] ^= 1 << (i % BITS);
+    }
+
+    /// The number of neurons that differ between the two states.
+    pub fn hamming(&self, other: &BitState) -> usize {
+        debug_assert_eq!(self.len, other.len);
+
+        self.words
+            .iter()
+            .zip(&other.words)
+            .map(|(a, b)| (a ^ b).count_ones() as usize)
+            .sum()
+    }
+
+    /// The overlap `(1/N) sum_i s_i t_i`, between `-1` and `1`.
+    pub fn overlap(&self, other: &BitState) -> f64 {
+        if self.len == 0 {
+            return 0.;
+        }
+
+        let h = self.hamming(other) as f64;
+        let n = self.len as f64;
+
+        (n - 2. * h) / n
+    }
+
+    /// Like `hamming`, but checks that both states have the same size.
+    pub fn try_hamming(&self, other: &BitState) -> Result<usize, HopfieldError> {
+        check_len("other", self.len, other.len)?;
+
+        Ok(self.hamming(other))
+    }
+
+    /// Like `overlap`, but checks that both states have the same size.
+    pub fn try_overlap(&self, other: &BitState) -> Result<f64, HopfieldError> {
+        check_len("other", self.len, other.len)?;
+
+        Ok(self.overlap(other))
+    }
+}
+
+impl<T: Scalar> From<&[T]> for BitState {
+    fn from(state: &[T]) -> Self {
+        Self::from_slice(state)
+    }
+}
+
+/// Sign-quantised symmetric weights: each weight is `+1` or `-1`. Rows are
+/// stored in full as packed bits, so the local field of a neuron is computed
+/// from the popcount of the row XOR the state. Self-couplings are always
+/// treated as zero.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "RawBitMatrix")
+)]
+pub struct BitMatrix {
+    rows: usize,
+    words: usize,
+    data: Vec<u64>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawBitMatrix {
+    rows: usize,
+    words: usize,
+    data: Vec<u64>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawBitMatrix> for BitMatrix {
+    type Error = String;
+
+    fn try_from(raw: RawBitMatrix) -> Result<Self, String> {
+        if raw.words != word_count(raw.rows)
+            || raw.rows.checked_mul(raw.words) != Some(raw.data.len())
+        {
+            return Err(format!(
+                "a bit matrix with {} rows needs {} words per row and {} in total",
+                raw.rows,
+                word_count(raw.rows),
+                raw.rows.saturating_mul(word_count(raw.rows))
+            ));
+        }
+
+        Ok(Self {
+            rows: raw.rows,
+            words: raw.words,
+            data: raw.data,
+        })
+    }
+}
+
+impl BitMatrix {
+    /// All weights `-1`.
+    pub fn new(rows: usize) -> Self {
+        let words = word_count(rows);
+
+        Self {
+            rows,
+            words,
+            data: vec![0; rows * words],
+        }
+    }
+
+    /// Quantise `weights` to their signs, zero becoming `+1`.
+    pub fn from_smatrix<T: Scalar>(weights: &SMatrix<T>) -> Self {
+        let mut m = Self::new(weights.rows());
+
+        for r in 0..m.rows {
+            for c in r..m.rows {
+                m.set(r, c, weights[(r, c)] >= T::ZERO);
+            }
+        }
+
+        m
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.rows
+    }
+
+    fn row(&self, r: usize) -> &[u64] {
+        &self.data[r * self.words..(r + 1) * self.words]
+    }
+
+    /// Whether the weight at `(r, c)` is `+1`.
+    pub fn get(&self, r: usize, c: usize) -> bool {
+        self.data[r * self.words + c / BITS] >> (c % BITS) & 1 == 1
+    }
+
+    /// Set the weights at `(r, c)` and `(c, r)`.
+    pub fn set(&mut self, r: usize, c: usize, on: bool) {
+        debug_assert!(r < self.rows && c < self.rows);
+
+        for (i, j) in [(r, c), (c, r)] {
+            let bit = 1 << (j % BITS);
+            let w = &mut self.data[i * self.words + j / BITS];
+
+            if on {
+                *w |= bit;
+            } else {
+                *w &= !bit;
+            }
+        }
+    }
+}
+
+/// Weights that can compute integer local fields for a `BitState`.
+pub trait BitWeights: MaybeSync {
+    fn rows(&self) -> usize;
+
+    /// The local field `sum_j w_ij s_j` of neuron `i`. It is summed in `i64`
+    /// so it cannot overflow even for millions of neurons with `i16` weights.
+    fn local_field(&self, state: &BitState, i: usize) -> i64;
+
+    /// The local fields of all neurons.
+    fn local_fields(&self, state: &BitState, fields: &mut [i64]) {
+        debug_assert_eq!(self.rows(), state.len());
+        debug_assert_eq!(self.rows(), fields.len());
+
+        #[cfg(feature = "parallel")]
+        if self.rows() * self.rows() >= crate::PARALLEL_THRESHOLD {
+            fields
+                .par_iter_mut()
+                .enumerate()
+                .for_each(|(i, h)| *h = self.local_field(state, i));
+
+            return;
+        }
+
+        for i in 0..fields.len() {
+            fields[i] = self.local_field(state, i);
+        }
+    }
+}
+
+impl BitWeights for BitMatrix {
+    fn rows(&self) -> usize {
+        self.rows
+    }
+
+    fn local_field(&self, state: &BitState, i: usize) -> i64 {
+        debug_assert_eq!(self.rows, state.len());
+
+        let differ: u64 = self
+            .row(i)
+            .iter()
+            .zip(&state.words)
+            .map(|(w, s)| u64::from((w ^ s).count_ones()))
+            .sum();
+        let sum = self.rows as i64 - 2 * differ as i64;
+        let self_coupling = if self.get(i, i) { 1 } else { -1 } * i64::from(state.value(i));
+
+        sum - self_coupling
+    }
+}
+
+macro_rules! impl_int_bit_weights {
+    ($t:ty) => {
+        impl BitWeights for SMatrix<$t> {
+            fn rows(&self) -> usize {
+                SMatrix::rows(self)
+            }
+
+            fn local_field(&self, state: &BitState, i: usize) -> i64 {
+                debug_assert_eq!(SMatrix::rows(self), state.len());
+
+                let mut acc = 0;
+
+                for j in 0..state.len() {
+                    let w = i64::from(self[(i, j)]);
+
+                    if state.get(j) {
+                        acc += w;
+                    } else {
+                        acc -= w;
+                    }
+                }
+
+                acc
+            }
+        }
+    };
+}
+
+impl_int_bit_weights!(i8);
+impl_int_bit_weights!(i16);
+
+fn activation(field: i64, bias: i64) -> bool {
+    field >= bias
+}
+
+/// Synchronous update of all neurons, as `classic::update_state_sync`.
+pub fn update_state_sync<W: BitWeights>(
+    weights: &W,
+    bias: &[i64],
+    input_state: &BitState,
+    output_state: &mut BitState,
+) {
+    debug_assert_eq!(input_state.len(), output_state.len());
+
+    let mut fields = vec![0; weights.rows()];
+    weights.local_fields(input_state, &mut fields);
+
+    for i in 0..output_state.len() {
+        output_state.set(i, activation(fields[i], bias[i]));
+    }
+}
+
+/// Update the single neuron `index` in place, as
+/// `classic::update_state_async`.
+pub fn update_state_async<W: BitWeights>(
+    weights: &W,
+    bias: &[i64],
+    state: &mut BitState,
+    index: usize,
+) {
+    let on = activation(weights.local_field(state, index), bias[index]);
+
+    state.set(index, on);
+}
+
+/// Like `update_state_sync`, but checks the sizes of `bias` and both states.
+pub fn try_update_state_sync<W: BitWeights>(
+    weights: &W,
+    bias: &[i64],
+    input_state: &BitState,
+    output_state: &mut BitState,
+) -> Result<(), HopfieldError> {
+    let n = weights.rows();
+
+    check_len("bias", n, bias.len())?;
+    check_len("input state", n, input_state.len())?;
+    check_len("output state", n, output_state.len())?;
+
+    update_state_sync(weights, bias, input_state, output_state);
+    Ok(())
+}
+
+/// Like `update_state_async`, but checks the sizes of `bias` and `state` and
+/// `index`.
+pub fn try_update_state_async<W: BitWeights>(
+    weights: &W,
+    bias: &[i64],
+    state: &mut BitState,
+    index: usize,
+) -> Result<(), HopfieldError> {
+    let n = weights.rows();
+
+    check_len("bias", n, bias.len())?;
+    check_len("state", n, state.len())?;
+    check_index("index", index, n)?;
+
+    update_state_async(weights, bias, state, index);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::classic;
+
+    fn pattern(n: usize, seed: usize) -> Vec<f64> {
+        (0..n)
+            .map(|i| if (i * seed + i / 3) % 5 < 2 { 1. } else { -1. })
+            .collect()
+    }
+
+    #[test]
+    fn conversions_and_distances() {
+        let p = pattern(130, 7);
+        let q = pattern(130, 3);
+        let a = BitState::from_slice(&p);
+        let b = BitState::from(q.as_slice());
+
+        assert_eq!(a.to_vec::<f64>(), p);
+        assert_eq!(a.len(), 130);
+        assert_eq!(a.as_words()[2] >> 2, 0);
+
+        let differ = (0..130).filter(|i| p[*i] != q[*i]).count();
+        let overlap: f64 = (0..130).map(|i| p[i] * q[i]).sum::<f64>() / 130.;
+
+        assert_eq!(a.hamming(&b), differ);
+        assert!((a.overlap(&b) - overlap).abs() < 1e-12);
+        assert_eq!(a.try_hamming(&b), Ok(differ));
+        assert!(a.try_overlap(&BitState::new(129)).is_err());
+        assert_eq!(
+            format!("{}", BitState::from_slice(&[1., -1., -1., 1.])),
+            "+--+"
+        );
+    }
+
+    #[test]
+    fn local_fields_match_dense() {
+        const N: usize = 100;
+
+        let p = pattern(N, 7);
+        let q = pattern(N, 3);
+        let s = pattern(N, 11);
+        let mut weights = SMatrix::new(N, 0.);
+        classic::hebb_learn(&mut weights, &p);
+        classic::hebb_learn(&mut weights, &q);
+
+        let ints: SMatrix<i16> =
+            SMatrix::from_fn2(N, &mut |r, c| (weights[(r, c)] * N as f64).round() as i16);
+        let signs = BitMatrix::from_smatrix(&weights);
+        let state = BitState::from_slice(&s);
+        let mut fields = vec![0; N];
+
+        ints.local_fields(&state, &mut fields);
+
+        for i in 0..N {
+            let expected: f64 = (0..N).map(|j| ints[(i, j)] as f64 * s[j]).sum();
+            assert_eq!(fields[i] as f64, expected);
+        }
+
+        signs.local_fields(&state, &mut fields);
+
+        for i in 0..N {
+            let expected: f64 = (0..N)
+                .filter(|j| *j != i)
+                .map(|j| if weights[(i, j)] >= 0. { s[j] } else { -s[j] })
+                .sum();
+            assert_eq!(fields[i] as f64, expected);
+        }
+    }
+
+    #[test]
+    fn clipped_recall() {
+        const N: usize = 200;
+
+        let p = pattern(N, 7);
+        let mut weights = SMatrix::new(N, 0.);
+        classic::hebb_learn(&mut weights, &p);
+
+        let signs = BitMatrix::from_smatrix(&weights);
+        let bias = vec![0; N];
+        let target = BitState::from_slice(&p);
+        let mut state = target.clone();
+        let mut output = BitState::new(N);
+
+        for i in (0..N).step_by(10) {
+            state.flip(i);
+        }
+
+        update_state_sync(&signs, &bias, &state, &mut output);
+
+        assert_eq!(output, target);
+
+        for i in 0..N {
+            update_state_async(&signs, &bias, &mut state, i);
+        }
+
+        assert_eq!(state.hamming(&target), 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let s = BitState::from_slice(&pattern(70, 3));
+        let m = BitMatrix::from_smatrix(&SMatrix::from_fn2(70, &mut |r, c| r as f64 - c as f64));
+
+        let json = serde_json::to_string(&s).unwrap();
+
+        assert_eq!(serde_json::from_str::<BitState>(&json).unwrap(), s);
+        assert_eq!(
+            serde_json::from_str::<BitMatrix>(&serde_json::to_string(&m).unwrap()).unwrap(),
+            m
+        );
+        assert!(serde_json::from_str::<BitState>(r#"{"len":3,"words":[8]}"#).is_err());
+    }
+}
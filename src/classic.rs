@@ -1,90 +1,794 @@
-//! Classic Hopfield network functions. The network is represented as 
-//! a symmetric matrix for the weights and a state: an `f64` slice. The
-//! functions in the module allow for doing both learning and recall.
+//! Classic Hopfield network functions. The network is represented as
+//! a symmetric matrix for the weights and a state: a slice of `Scalar`s
+//! (usually `f64`). The functions in the module allow for doing both learning
+//! and recall. They work with any symmetric matrix implementing `Weights`:
+//! the dense `SMatrix`, or the sparse `SpMatrix` for diluted networks.
 
+use crate::error::{
+    check_index, check_len, check_non_negative, check_rate, check_vec, HopfieldError,
+};
+use crate::scalar::Scalar;
 use crate::smatrix::SMatrix;
+use crate::spmatrix::SpMatrix;
+use crate::MaybeSync;
+use rand::seq::SliceRandom;
 use rand::Rng;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
-fn activation(v: f64, bias: f64) -> f64 {
+/// The operations on a symmetric weight matrix needed by the network.
+pub trait Weights<T: Scalar> {
+    fn rows(&self) -> usize;
+
+    /// The dot product of row `row` with `vec`, added to `init`.
+    fn row_mul(&self, row: usize, vec: &[T], init: T) -> T;
+
+    /// `output = A * input`.
+    fn mul_vec(&self, input: &[T], output: &mut [T]);
+
+    /// Call `f(r, c, &mut a_rc)` for every (stored) element with `r <= c`,
+    /// keeping the matrix symmetric.
+    fn for_each_upper_mut<F: Fn(usize, usize, &mut T) + MaybeSync>(&mut self, f: F);
+
+    /// `A += alpha * x * x^T`, leaving the diagonal unchanged.
+    fn syr_hollow(&mut self, alpha: T, x: &[T]) {
+        self.for_each_upper_mut(|r, c, a| {
+            if r != c {
+                *a += alpha * x[r] * x[c];
+            }
+        });
+    }
+}
+
+impl<T: Scalar> Weights<T> for SMatrix<T> {
+    fn rows(&self) -> usize {
+        SMatrix::rows(self)
+    }
+
+    fn row_mul(&self, row: usize, vec: &[T], init: T) -> T {
+        SMatrix::row_mul(self, row, vec, init)
+    }
+
+    fn mul_vec(&self, input: &[T], output: &mut [T]) {
+        SMatrix::mul_vec(self, input, output)
+    }
+
+    fn for_each_upper_mut<F: Fn(usize, usize, &mut T) + MaybeSync>(&mut self, f: F) {
+        SMatrix::for_each_upper_mut(self, f)
+    }
+
+    fn syr_hollow(&mut self, alpha: T, x: &[T]) {
+        SMatrix::syr_hollow(self, alpha, x)
+    }
+}
+
+impl<T: Scalar> Weights<T> for SpMatrix<T> {
+    fn rows(&self) -> usize {
+        SpMatrix::rows(self)
+    }
+
+    fn row_mul(&self, row: usize, vec: &[T], init: T) -> T {
+        SpMatrix::row_mul(self, row, vec, init)
+    }
+
+    fn mul_vec(&self, input: &[T], output: &mut [T]) {
+        SpMatrix::mul_vec(self, input, output)
+    }
+
+    fn for_each_upper_mut<F: Fn(usize, usize, &mut T) + MaybeSync>(&mut self, f: F) {
+        SpMatrix::for_each_upper_mut(self, f)
+    }
+
+    fn syr_hollow(&mut self, alpha: T, x: &[T]) {
+        SpMatrix::syr_hollow(self, alpha, x)
+    }
+}
+
+fn activation<T: Scalar>(v: T, bias: T) -> T {
     if v >= bias {
-        1.
+        T::ONE
     } else {
-        -1.
+        -T::ONE
     }
 }
 
-pub fn initialize_weights<R: Rng>(weights: &mut SMatrix<f64>, rng: &mut R, amount: f64) {
+pub fn initialize_weights<T: Scalar, R: Rng>(weights: &mut SMatrix<T>, rng: &mut R, amount: T) {
+    let amount = amount.to_f64();
+
     for r in 0..weights.rows() {
         for c in r..weights.cols() {
             if c == r {
-                weights[(r, c)] = 0.;
+                weights[(r, c)] = T::ZERO;
             } else {
-                weights[(r, c)] = rng.random_range(-amount..amount);
+                weights[(r, c)] = T::from_f64(rng.random_range(-amount..amount));
             }
         }
     }
 }
 
-pub fn update_state_sync(
-    weights: &SMatrix<f64>,
-    bias: &[f64],
-    input_state: &[f64],
-    output_state: &mut [f64],
+/// Like `initialize_weights`, but checks that `amount` is finite and positive.
+pub fn try_initialize_weights<T: Scalar, R: Rng>(
+    weights: &mut SMatrix<T>,
+    rng: &mut R,
+    amount: T,
+) -> Result<(), HopfieldError> {
+    check_rate("amount", amount)?;
+
+    initialize_weights(weights, rng, amount);
+    Ok(())
+}
+
+pub fn update_state_sync<T: Scalar, W: Weights<T>>(
+    weights: &W,
+    bias: &[T],
+    input_state: &[T],
+    output_state: &mut [T],
 ) {
     let l = weights
         .rows()
         .min(input_state.len())
         .min(output_state.len());
 
-    for i in 0..l {
-        output_state[i] = activation(weights.row_mul(i, input_state, 0.), bias[i]);
+    if input_state.len() == weights.rows() {
+        let mut field = vec![T::ZERO; weights.rows()];
+        weights.mul_vec(input_state, &mut field);
+
+        for i in 0..l {
+            output_state[i] = activation(field[i], bias[i]);
+        }
+    } else {
+        for i in 0..l {
+            output_state[i] = activation(weights.row_mul(i, input_state, T::ZERO), bias[i]);
+        }
     }
 }
 
-pub fn update_state_async(weights: &SMatrix<f64>, bias: &[f64], state: &mut [f64], index: usize) {
+/// Like `update_state_sync`, but requires the bias and both states to have one
+/// value per neuron instead of updating only the neurons they share, and
+/// rejects non-finite inputs.
+pub fn try_update_state_sync<T: Scalar, W: Weights<T>>(
+    weights: &W,
+    bias: &[T],
+    input_state: &[T],
+    output_state: &mut [T],
+) -> Result<(), HopfieldError> {
+    let n = weights.rows();
+
+    check_vec("bias", n, bias)?;
+    check_vec("input state", n, input_state)?;
+    check_len("output state", n, output_state.len())?;
+
+    update_state_sync(weights, bias, input_state, output_state);
+    Ok(())
+}
+
+pub fn update_state_async<T: Scalar, W: Weights<T>>(
+    weights: &W,
+    bias: &[T],
+    state: &mut [T],
+    index: usize,
+) {
     debug_assert!(index < state.len());
     debug_assert!(index < weights.rows());
 
-    let new_state_value = activation(weights.row_mul(index, state, 0.), bias[index]);
+    let new_state_value = activation(weights.row_mul(index, state, T::ZERO), bias[index]);
 
     state[index] = new_state_value;
 }
 
-pub fn energy(weights: &SMatrix<f64>, bias: &[f64], state: &[f64]) -> f64 {
-    let mut acc = 0.;
+/// Like `update_state_async`, but checks the sizes of `bias` and `state`, the
+/// values in them and `index`.
+pub fn try_update_state_async<T: Scalar, W: Weights<T>>(
+    weights: &W,
+    bias: &[T],
+    state: &mut [T],
+    index: usize,
+) -> Result<(), HopfieldError> {
+    let n = weights.rows();
 
-    for r in 0..state.len() {
-        for c in 0..state.len() {
-            acc -= weights[(r, c)] * state[r] * state[c];
+    check_vec("bias", n, bias)?;
+    check_vec("state", n, state)?;
+    check_index("index", index, n)?;
+
+    update_state_async(weights, bias, state, index);
+    Ok(())
+}
+
+/// Stochastic (Glauber) update of neuron `index` at `temperature`: the neuron
+/// becomes 1 with probability `1 / (1 + exp(-2 (h - b) / temperature))` for
+/// its local field `h` and bias `b`, and -1 otherwise. At zero temperature this
+/// is `update_state_async` and nothing is drawn from `rng`.
+pub fn update_state_glauber<T: Scalar, W: Weights<T>, R: Rng>(
+    weights: &W,
+    bias: &[T],
+    state: &mut [T],
+    index: usize,
+    temperature: T,
+    rng: &mut R,
+) {
+    debug_assert!(temperature >= T::ZERO);
+
+    if temperature <= T::ZERO {
+        update_state_async(weights, bias, state, index);
+        return;
+    }
+
+    let field = (weights.row_mul(index, state, T::ZERO) - bias[index]).to_f64();
+    let p = 1. / (1. + (-2. * field / temperature.to_f64()).exp());
+
+    state[index] = if rng.random::<f64>() < p {
+        T::ONE
+    } else {
+        -T::ONE
+    };
+}
+
+/// Like `update_state_glauber`, but checks `bias`, `state`, `index` and that
+/// `temperature` is finite and non-negative.
+pub fn try_update_state_glauber<T: Scalar, W: Weights<T>, R: Rng>(
+    weights: &W,
+    bias: &[T],
+    state: &mut [T],
+    index: usize,
+    temperature: T,
+    rng: &mut R,
+) -> Result<(), HopfieldError> {
+    let n = weights.rows();
+
+    check_vec("bias", n, bias)?;
+    check_vec("state", n, state)?;
+    check_index("index", index, n)?;
+    check_non_negative("temperature", temperature)?;
+
+    update_state_glauber(weights, bias, state, index, temperature, rng);
+    Ok(())
+}
+
+/// Simulated annealing: for each temperature in `schedule`, one sweep of
+/// Glauber updates over all neurons in a random order drawn from `rng`. A
+/// schedule that ends with zero temperatures lets the state settle in a
+/// minimum of the energy.
+pub fn anneal<T: Scalar, W: Weights<T>, R: Rng>(
+    weights: &W,
+    bias: &[T],
+    state: &mut [T],
+    schedule: impl IntoIterator<Item = T>,
+    rng: &mut R,
+) {
+    let mut order: Vec<usize> = (0..weights.rows().min(state.len())).collect();
+
+    for temperature in schedule {
+        order.shuffle(rng);
+
+        for i in &order {
+            update_state_glauber(weights, bias, state, *i, temperature, rng);
         }
+    }
+}
+
+/// Like `anneal`, but checks `bias`, `state` and that the temperatures are
+/// finite and non-negative. The schedule is checked before any update.
+pub fn try_anneal<T: Scalar, W: Weights<T>, R: Rng>(
+    weights: &W,
+    bias: &[T],
+    state: &mut [T],
+    schedule: impl IntoIterator<Item = T>,
+    rng: &mut R,
+) -> Result<(), HopfieldError> {
+    let n = weights.rows();
+    let schedule: Vec<T> = schedule.into_iter().collect();
 
+    check_vec("bias", n, bias)?;
+    check_vec("state", n, state)?;
+    for t in &schedule {
+        check_non_negative("temperature", *t)?;
+    }
+
+    anneal(weights, bias, state, schedule, rng);
+    Ok(())
+}
+
+/// The order in which `recall_async` updates the neurons. A sweep is `N`
+/// updates for `N` neurons.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UpdateOrder {
+    /// Neurons `0..N` in order.
+    Sequential,
+    /// A new random permutation of the neurons for every sweep.
+    Permutation,
+    /// Neurons drawn uniformly at random with replacement, so some neurons
+    /// may be updated several times in a sweep and others not at all.
+    Sampling,
+    /// The neurons are split into consecutive blocks of the given size. The
+    /// blocks are updated one after another and the neurons within a block
+    /// all at once from the same state, in parallel with the `parallel`
+    /// feature.
+    Blocks(usize),
+}
+
+/// The result of `recall_async`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AsyncRecall {
+    /// The number of neurons that changed in each sweep.
+    pub flips: Vec<usize>,
+    /// Whether the state is a fixed point of the dynamics.
+    pub converged: bool,
+}
+
+impl AsyncRecall {
+    pub fn sweeps(&self) -> usize {
+        self.flips.len()
+    }
+}
+
+// `update_state_async`, returning whether the neuron changed.
+fn update_changed<T: Scalar, W: Weights<T>>(
+    weights: &W,
+    bias: &[T],
+    state: &mut [T],
+    index: usize,
+) -> bool {
+    let old = state[index];
+
+    update_state_async(weights, bias, state, index);
+    state[index] != old
+}
+
+/// Asynchronous recall: sweeps of updates in the given `order` until a sweep
+/// changes no neuron, or at most `max_sweeps` sweeps. `rng` is only used by
+/// the random orders.
+///
+/// With symmetric weights and a non-negative diagonal the energy decreases
+/// with every change under the sequential, permutation and sampling orders, so
+/// they always reach a fixed point (Bruck, 1990). Block updates share this
+/// guarantee only if no two neurons in a block are coupled; otherwise they
+/// can end in a cycle, which is reported with `converged` set to false.
+///
+/// With `Sampling`, a sweep without changes need not have visited every
+/// neuron; the recall stops once every neuron has been updated without change
+/// since the last change.
+pub fn recall_async<T: Scalar, W: Weights<T> + MaybeSync, R: Rng>(
+    weights: &W,
+    bias: &[T],
+    state: &mut [T],
+    order: UpdateOrder,
+    max_sweeps: usize,
+    rng: &mut R,
+) -> AsyncRecall {
+    let n = weights.rows().min(state.len());
+    let mut indices: Vec<usize> = (0..n).collect();
+    let mut flips = Vec::new();
+    // For `Sampling`: the neurons updated without change since the last
+    // change, and how many there are.
+    let mut stable = vec![false; n];
+    let mut stable_count = 0;
+
+    for _s in 0..max_sweeps {
+        let mut count = 0;
+
+        match order {
+            UpdateOrder::Sequential | UpdateOrder::Permutation => {
+                if order == UpdateOrder::Permutation {
+                    indices.shuffle(rng);
+                }
+
+                for i in &indices {
+                    if update_changed(weights, bias, state, *i) {
+                        count += 1;
+                    }
+                }
+            }
+            UpdateOrder::Sampling => {
+                for _i in 0..n {
+                    let i = rng.random_range(0..n);
+
+                    if update_changed(weights, bias, state, i) {
+                        count += 1;
+                        stable.fill(false);
+                        stable_count = 0;
+                    } else if !stable[i] {
+                        stable[i] = true;
+                        stable_count += 1;
+                    }
+                }
+            }
+            UpdateOrder::Blocks(size) => {
+                debug_assert!(size > 0);
+
+                for block in indices.chunks(size.max(1)) {
+                    let field =
+                        |i: &usize| activation(weights.row_mul(*i, state, T::ZERO), bias[*i]);
+
+                    #[cfg(feature = "parallel")]
+                    let next: Vec<T> = if block.len() * n >= crate::PARALLEL_THRESHOLD {
+                        block.par_iter().map(field).collect()
+                    } else {
+                        block.iter().map(field).collect()
+                    };
+                    #[cfg(not(feature = "parallel"))]
+                    let next: Vec<T> = block.iter().map(field).collect();
+
+                    for (i, v) in block.iter().zip(next) {
+                        if state[*i] != v {
+                            state[*i] = v;
+                            count += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        flips.push(count);
+
+        let converged = match order {
+            UpdateOrder::Sampling => stable_count == n,
+            _ => count == 0,
+        };
+
+        if converged {
+            return AsyncRecall { flips, converged };
+        }
+    }
+
+    AsyncRecall {
+        flips,
+        converged: false,
+    }
+}
+
+/// Like `recall_async`, but checks the sizes of and values in `bias` and
+/// `state` and that blocks are not empty.
+pub fn try_recall_async<T: Scalar, W: Weights<T> + MaybeSync, R: Rng>(
+    weights: &W,
+    bias: &[T],
+    state: &mut [T],
+    order: UpdateOrder,
+    max_sweeps: usize,
+    rng: &mut R,
+) -> Result<AsyncRecall, HopfieldError> {
+    let n = weights.rows();
+
+    check_vec("bias", n, bias)?;
+    check_vec("state", n, state)?;
+    if order == UpdateOrder::Blocks(0) {
+        return Err(HopfieldError::InvalidParameter {
+            name: "block size",
+            reason: "must be positive",
+        });
+    }
+
+    Ok(recall_async(weights, bias, state, order, max_sweeps, rng))
+}
+
+pub fn energy<T: Scalar, W: Weights<T>>(weights: &W, bias: &[T], state: &[T]) -> T {
+    let mut acc = T::ZERO;
+
+    for r in 0..state.len() {
+        acc -= weights.row_mul(r, state, T::ZERO) * state[r];
         acc -= bias[r] * state[r];
     }
 
     acc
 }
 
-pub fn hebb_learn(weights: &mut SMatrix<f64>, pattern: &[f64]) {
-    let n_inv = 1. / (pattern.len() as f64);
+/// Like `energy`, but checks the sizes of and values in `bias` and `state`.
+pub fn try_energy<T: Scalar, W: Weights<T>>(
+    weights: &W,
+    bias: &[T],
+    state: &[T],
+) -> Result<T, HopfieldError> {
+    let n = weights.rows();
 
-    for r in 0..weights.rows() {
-        for c in (r + 1)..weights.cols() {
-            weights[(r, c)] += n_inv * pattern[r] * pattern[c];
+    check_vec("bias", n, bias)?;
+    check_vec("state", n, state)?;
+
+    Ok(energy(weights, bias, state))
+}
+
+pub fn hebb_learn<T: Scalar, W: Weights<T>>(weights: &mut W, pattern: &[T]) {
+    let n_inv = T::ONE / T::from_usize(pattern.len());
+
+    weights.syr_hollow(n_inv, pattern);
+}
+
+/// Like `hebb_learn`, but checks the size of and values in `pattern`.
+pub fn try_hebb_learn<T: Scalar, W: Weights<T>>(
+    weights: &mut W,
+    pattern: &[T],
+) -> Result<(), HopfieldError> {
+    check_vec("pattern", weights.rows(), pattern)?;
+
+    hebb_learn(weights, pattern);
+    Ok(())
+}
+
+pub fn storkey_learn<T: Scalar, W: Weights<T>>(weights: &mut W, pattern: &[T], amount: T) {
+    let mut h = vec![T::ZERO; pattern.len()];
+
+    weights.mul_vec(pattern, &mut h);
+
+    weights.for_each_upper_mut(|r, c, w| {
+        if r != c {
+            *w += amount * (pattern[r] * pattern[c] - pattern[r] * h[c] - h[r] * pattern[c]);
         }
-    }
+    });
 }
 
-pub fn storkey_learn(weights: &mut SMatrix<f64>, pattern: &[f64], amount: f64) {
-    let mut h = Vec::with_capacity(pattern.len());
+/// Like `storkey_learn`, but checks `pattern` and that `amount` is finite and
+/// positive.
+pub fn try_storkey_learn<T: Scalar, W: Weights<T>>(
+    weights: &mut W,
+    pattern: &[T],
+    amount: T,
+) -> Result<(), HopfieldError> {
+    check_vec("pattern", weights.rows(), pattern)?;
+    check_rate("amount", amount)?;
+
+    storkey_learn(weights, pattern, amount);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn storkey_matches_elementwise_update() {
+        // Large enough to take the parallel path with the `parallel` feature.
+        const N: usize = 200;
+
+        let p: Vec<f64> = (0..N).map(|i| if i % 3 == 0 { 1. } else { -1. }).collect();
+        let mut weights = SMatrix::from_fn2(N, &mut |r, c| {
+            if r == c {
+                0.
+            } else {
+                ((r + 2 * c) as f64).sin() * 0.1
+            }
+        });
+        let mut expected = weights.clone();
+
+        let h: Vec<f64> = (0..N).map(|i| expected.row_mul(i, &p, 0.)).collect();
+        for r in 0..N {
+            for c in (r + 1)..N {
+                expected[(r, c)] += 0.1 * (p[r] * p[c] - p[r] * h[c] - h[r] * p[c]);
+            }
+        }
+
+        storkey_learn(&mut weights, &p, 0.1);
 
-    for i in 0..pattern.len() {
-        h.push(weights.row_mul(i, pattern, 0.));
+        for r in 0..N {
+            for c in 0..N {
+                assert!((weights[(r, c)] - expected[(r, c)]).abs() < 1e-12);
+            }
+        }
     }
 
-    for r in 0..weights.rows() {
-        for c in (r + 1)..weights.cols() {
-            weights[(r, c)] +=
-                amount * (pattern[r] * pattern[c] - pattern[r] * h[c] - h[r] * pattern[c]);
+    #[test]
+    fn hebb_learned_pattern_is_fixed_point() {
+        let p: Vec<f64> = vec![1., -1., 1., 1., -1., -1., 1., -1.];
+        let bias = vec![0.; 8];
+        let mut weights = SMatrix::new(8, 0.);
+        let mut output = vec![0.; 8];
+
+        hebb_learn(&mut weights, &p);
+
+        for r in 0..8 {
+            assert_eq!(weights[(r, r)], 0.);
         }
+
+        update_state_sync(&weights, &bias, &p, &mut output);
+
+        assert_eq!(output, p);
+        assert!(energy(&weights, &bias, &p) < 0.);
+    }
+
+    #[test]
+    fn diluted_hebb_matches_masked_dense() {
+        use crate::seed::Seed;
+
+        const N: usize = 60;
+
+        let mut rng = Seed(3).rng();
+        let p: Vec<f64> = (0..N).map(|i| if i % 4 < 2 { 1. } else { -1. }).collect();
+        let bias = vec![0.; N];
+        let mut sparse = SpMatrix::erdos_renyi(N, 0.3, 0., &mut rng);
+        let mut dense = SMatrix::new(N, 0.);
+        let mut output = vec![0.; N];
+
+        hebb_learn(&mut sparse, &p);
+        hebb_learn(&mut dense, &p);
+
+        for r in 0..N {
+            for c in 0..N {
+                if sparse.contains(r, c) {
+                    assert_eq!(sparse[(r, c)], dense[(r, c)]);
+                } else {
+                    assert_eq!(sparse[(r, c)], 0.);
+                }
+            }
+        }
+
+        update_state_sync(&sparse, &bias, &p, &mut output);
+
+        assert_eq!(output, p);
+        assert!(energy(&sparse, &bias, &p) < 0.);
+    }
+
+    #[test]
+    fn try_variants_reject_malformed_input() {
+        let p = vec![1., -1., 1., -1.];
+        let mut weights = SMatrix::new(4, 0.);
+        let mut output = vec![0.; 4];
+
+        assert_eq!(
+            try_hebb_learn(&mut weights, &p[..3]),
+            Err(HopfieldError::DimensionMismatch {
+                what: "pattern",
+                expected: 4,
+                found: 3
+            })
+        );
+        assert_eq!(
+            try_hebb_learn(&mut weights, &[1., f64::NAN, 1., 1.]),
+            Err(HopfieldError::NonFinite {
+                what: "pattern",
+                index: 1
+            })
+        );
+        assert_eq!(weights, SMatrix::new(4, 0.));
+
+        try_hebb_learn(&mut weights, &p).unwrap();
+
+        assert!(try_update_state_sync(&weights, &[0.; 4], &p, &mut output[..3]).is_err());
+        assert!(try_update_state_sync(&weights, &[0.; 3], &p, &mut output).is_err());
+        assert!(try_update_state_async(&weights, &[0.; 4], &mut output, 4).is_err());
+        assert!(try_storkey_learn(&mut weights, &p, -0.1).is_err());
+
+        try_update_state_sync(&weights, &[0.; 4], &p, &mut output).unwrap();
+        assert_eq!(output, p);
+        assert!(try_energy(&weights, &[0.; 4], &p).unwrap() < 0.);
+    }
+
+    #[test]
+    fn async_recall_converges_in_every_order() {
+        use crate::seed::Seed;
+        use crate::state::State;
+
+        const N: usize = 60;
+
+        let p: Vec<f64> = (0..N).map(|i| if i % 7 < 3 { 1. } else { -1. }).collect();
+        let bias = vec![0.; N];
+        let mut weights = SMatrix::new(N, 0.);
+        hebb_learn(&mut weights, &p);
+
+        let mut rng = Seed(4).rng();
+        let mut cue = p.clone();
+        cue.flip_random(&mut rng, 12);
+
+        let orders = [
+            UpdateOrder::Sequential,
+            UpdateOrder::Permutation,
+            UpdateOrder::Sampling,
+            UpdateOrder::Blocks(1),
+        ];
+
+        for order in orders {
+            let mut state = cue.clone();
+            let recall = recall_async(&weights, &bias, &mut state, order, 20, &mut rng);
+
+            assert!(recall.converged);
+            assert_eq!(state, p);
+            assert_eq!(recall.flips.iter().sum::<usize>(), 12);
+            assert_eq!(recall.flips.last(), Some(&0));
+        }
+
+        // Random symmetric weights have many fixed points, but asynchronous
+        // updates still reach one of them.
+        initialize_weights(&mut weights, &mut rng, 1.);
+        let mut state = cue.clone();
+        let recall = recall_async(
+            &weights,
+            &bias,
+            &mut state,
+            UpdateOrder::Permutation,
+            1000,
+            &mut rng,
+        );
+        let mut next = vec![0.; N];
+        update_state_sync(&weights, &bias, &state, &mut next);
+
+        assert!(recall.converged);
+        assert_eq!(next, state);
+        assert!(try_recall_async(
+            &weights,
+            &bias,
+            &mut state,
+            UpdateOrder::Blocks(0),
+            1,
+            &mut rng
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn coupled_blocks_can_cycle() {
+        // Two neurons that want to disagree, updated at the same time, swap
+        // their values forever.
+        let mut weights = SMatrix::new(2, 0.);
+        weights[(0, 1)] = -1.;
+        let mut state = vec![1., 1.];
+        let mut rng = crate::seed::Seed(0).rng();
+
+        let recall = recall_async(
+            &weights,
+            &[0.; 2],
+            &mut state,
+            UpdateOrder::Blocks(2),
+            5,
+            &mut rng,
+        );
+
+        assert!(!recall.converged);
+        assert_eq!(recall.flips, vec![2; 5]);
+
+        let recall = recall_async(
+            &weights,
+            &[0.; 2],
+            &mut state,
+            UpdateOrder::Sequential,
+            5,
+            &mut rng,
+        );
+
+        assert!(recall.converged);
+        assert_eq!(recall.sweeps(), 2);
+    }
+
+    #[test]
+    fn annealing_is_reproducible_and_finds_pattern() {
+        use crate::seed::Seed;
+
+        const N: usize = 40;
+
+        let p: Vec<f64> = (0..N).map(|i| if i % 5 < 2 { 1. } else { -1. }).collect();
+        let bias = vec![0.; N];
+        let mut weights = SMatrix::new(N, 0.);
+        hebb_learn(&mut weights, &p);
+
+        let schedule: Vec<f64> = (0..30)
+            .map(|k| 2. * 0.8_f64.powi(k))
+            .chain([0.; 3])
+            .collect();
+        let run = |seed: Seed| {
+            let mut rng = seed.rng();
+            let mut state: Vec<f64> = (0..N)
+                .map(|_| if rng.random_bool(0.5) { 1. } else { -1. })
+                .collect();
+
+            anneal(
+                &weights,
+                &bias,
+                &mut state,
+                schedule.iter().copied(),
+                &mut rng,
+            );
+            state
+        };
+
+        let state = run(Seed(11));
+        let reversed: Vec<f64> = p.iter().map(|v| -v).collect();
+
+        assert_eq!(state, run(Seed(11)));
+        assert!(state == p || state == reversed);
+
+        let mut rng = Seed(0).rng();
+        let mut state = vec![1.; N];
+        assert!(try_anneal(&weights, &bias, &mut state, [1., -1.], &mut rng).is_err());
+        assert_eq!(state, vec![1.; N]);
+        assert!(
+            try_update_state_glauber(&weights, &bias, &mut state, 0, f64::NAN, &mut rng).is_err()
+        );
     }
 }
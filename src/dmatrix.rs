@@ -2,24 +2,91 @@
 //! memory. Single column matrices (aka column-vectors) can be represented
 //! using slices. This makes matrix-vector multiplication (a little) faster.
 
-use std::fmt::Debug;
-use std::ops::{AddAssign, Index, IndexMut, Mul};
-
+use crate::error::{check_len, HopfieldError};
+use crate::simd::Kernel;
+use crate::{MaybeSend, MaybeSync};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::fmt::{self, Display, Formatter};
+use std::ops::{AddAssign, Index, IndexMut};
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "RawDMatrix<T>")
+)]
 pub struct DMatrix<T> {
     rows: usize,
     cols: usize,
     data: Vec<T>,
 }
 
-impl<T: Debug> DMatrix<T> {
-    #[allow(dead_code)]
-    pub fn pp(&self) {
+// The serialised form of `DMatrix`, checked for a consistent shape on load.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawDMatrix<T> {
+    rows: usize,
+    cols: usize,
+    data: Vec<T>,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<RawDMatrix<T>> for DMatrix<T> {
+    type Error = String;
+
+    fn try_from(raw: RawDMatrix<T>) -> Result<Self, String> {
+        if raw.rows.checked_mul(raw.cols) != Some(raw.data.len()) {
+            return Err(format!(
+                "a {}x{} matrix needs {} elements, found {}",
+                raw.rows,
+                raw.cols,
+                raw.rows.saturating_mul(raw.cols),
+                raw.data.len()
+            ));
+        }
+
+        Ok(Self {
+            rows: raw.rows,
+            cols: raw.cols,
+            data: raw.data,
+        })
+    }
+}
+
+// Write the values separated by spaces, using the precision and width of the
+// formatter if any were given.
+pub(crate) fn fmt_values<'a, T: Display + 'a>(
+    f: &mut Formatter<'_>,
+    values: impl IntoIterator<Item = &'a T>,
+) -> fmt::Result {
+    let width = f.width().unwrap_or(0);
+
+    for (i, v) in values.into_iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+
+        match f.precision() {
+            Some(p) => write!(f, "{:>width$.p$}", v)?,
+            None => write!(f, "{:>width$}", v)?,
+        }
+    }
+
+    Ok(())
+}
+
+impl<T: Display> Display for DMatrix<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         for r in 0..self.rows {
-            for c in 0..self.cols {
-                print!("{:?} ", self[(r, c)]);
+            if r > 0 {
+                writeln!(f)?;
             }
-            println!("");
+
+            fmt_values(f, &self.data[r * self.cols..(r + 1) * self.cols])?;
         }
+
+        Ok(())
     }
 }
 
@@ -32,6 +99,26 @@ impl<T> DMatrix<T> {
         self.cols
     }
 
+    /// The elements of the matrix, row by row.
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    /// The elements of row `r`.
+    pub fn row(&self, r: usize) -> &[T] {
+        &self.data[r * self.cols..(r + 1) * self.cols]
+    }
+
+    pub(crate) fn from_vec(rows: usize, cols: usize, data: Vec<T>) -> Self {
+        debug_assert_eq!(data.len(), rows * cols);
+
+        Self { rows, cols, data }
+    }
+
     fn row_col_to_index(&self, r: usize, c: usize) -> usize {
         r * self.cols + c
     }
@@ -53,18 +140,17 @@ impl<T> IndexMut<(usize, usize)> for DMatrix<T> {
     }
 }
 
-impl<T: Mul<Output = T> + Default + AddAssign + Copy> DMatrix<T> {
+impl<T: Kernel + MaybeSend + MaybeSync> DMatrix<T> {
     pub fn mul_row_col(&self, m: &DMatrix<T>, r: usize, c: usize) -> T {
         debug_assert_eq!(self.cols, m.rows);
 
         let mut acc = T::default();
 
-        let mut i1 = r * self.cols;
+        let row = &self.data[r * self.cols..(r + 1) * self.cols];
         let mut i2 = c;
 
-        for _i in 0..self.cols {
-            acc += self.data[i1] * m.data[i2];
-            i1 += 1;
+        for i1 in 0..self.cols {
+            acc += row[i1] * m.data[i2];
             i2 += m.cols;
         }
 
@@ -75,16 +161,7 @@ impl<T: Mul<Output = T> + Default + AddAssign + Copy> DMatrix<T> {
         debug_assert_eq!(self.cols, v.len());
         debug_assert!(r < self.rows);
 
-        let mut acc = T::default();
-
-        let mut i1 = r * self.cols;
-
-        for i2 in 0..v.len() {
-            acc += self.data[i1] * v[i2];
-            i1 += 1;
-        }
-
-        acc
+        T::dot(self.row(r), v)
     }
 
     pub fn mul_col_vec(&self, v: &[T], c: usize) -> T {
@@ -114,23 +191,89 @@ impl<T: Mul<Output = T> + Default + AddAssign + Copy> DMatrix<T> {
         }
     }
 
+    /// Like `mul_assign`, but checks the shapes of the matrices.
+    pub fn try_mul_assign(
+        &mut self,
+        m1: &DMatrix<T>,
+        m2: &DMatrix<T>,
+    ) -> Result<(), HopfieldError> {
+        check_len("rows of m1", self.rows, m1.rows)?;
+        check_len("columns of m2", self.cols, m2.cols)?;
+        check_len("rows of m2", m1.cols, m2.rows)?;
+
+        self.mul_assign(m1, m2);
+        Ok(())
+    }
+
     pub fn mul_vec(&self, input: &[T], output: &mut [T]) {
         debug_assert_eq!(self.cols, input.len());
         debug_assert_eq!(self.rows, output.len());
 
+        #[cfg(feature = "parallel")]
+        if self.data.len() >= crate::PARALLEL_THRESHOLD {
+            output
+                .par_iter_mut()
+                .enumerate()
+                .for_each(|(i, o)| *o = self.mul_row_vec(input, i));
+
+            return;
+        }
+
         for i in 0..output.len() {
             output[i] = self.mul_row_vec(input, i);
         }
     }
 
+    /// Like `mul_vec`, but checks the sizes of `input` and `output`.
+    pub fn try_mul_vec(&self, input: &[T], output: &mut [T]) -> Result<(), HopfieldError> {
+        check_len("input", self.cols, input.len())?;
+        check_len("output", self.rows, output.len())?;
+
+        self.mul_vec(input, output);
+        Ok(())
+    }
+
+    /// Computes `output = A^T * input` by accumulating the rows of the matrix,
+    /// scaled by the elements of `input`, into `output`. This reads the matrix
+    /// in storage order.
     pub fn trans_mul_vec(&self, input: &[T], output: &mut [T]) {
         debug_assert_eq!(self.rows, input.len());
         debug_assert_eq!(self.cols, output.len());
 
-        for i in 0..output.len() {
-            output[i] = self.mul_col_vec(input, i);
+        output.fill(T::default());
+
+        // Each thread accumulates all rows into its own block of columns.
+        #[cfg(feature = "parallel")]
+        if self.data.len() >= crate::PARALLEL_THRESHOLD {
+            const BLOCK: usize = 256;
+
+            output
+                .par_chunks_mut(BLOCK)
+                .enumerate()
+                .for_each(|(b, out)| {
+                    let start = b * BLOCK;
+
+                    for r in 0..self.rows {
+                        T::axpy(input[r], &self.row(r)[start..start + out.len()], out);
+                    }
+                });
+
+            return;
+        }
+
+        for r in 0..self.rows {
+            T::axpy(input[r], self.row(r), output);
         }
     }
+
+    /// Like `trans_mul_vec`, but checks the sizes of `input` and `output`.
+    pub fn try_trans_mul_vec(&self, input: &[T], output: &mut [T]) -> Result<(), HopfieldError> {
+        check_len("input", self.rows, input.len())?;
+        check_len("output", self.cols, output.len())?;
+
+        self.trans_mul_vec(input, output);
+        Ok(())
+    }
 }
 
 impl<T: AddAssign + Copy> DMatrix<T> {
@@ -142,6 +285,15 @@ impl<T: AddAssign + Copy> DMatrix<T> {
             self.data[i] += mat.data[i];
         }
     }
+
+    /// Like `add_matrix`, but checks that both matrices have the same shape.
+    pub fn try_add_matrix(&mut self, mat: &DMatrix<T>) -> Result<(), HopfieldError> {
+        check_len("rows", self.rows, mat.rows)?;
+        check_len("columns", self.cols, mat.cols)?;
+
+        self.add_matrix(mat);
+        Ok(())
+    }
 }
 
 impl<T: Clone> DMatrix<T> {
@@ -154,6 +306,15 @@ impl<T: Clone> DMatrix<T> {
         }
     }
 
+    /// Like `assign`, but checks that both matrices have the same shape.
+    pub fn try_assign(&mut self, m: &DMatrix<T>) -> Result<(), HopfieldError> {
+        check_len("rows", self.rows, m.rows)?;
+        check_len("columns", self.cols, m.cols)?;
+
+        self.assign(m);
+        Ok(())
+    }
+
     pub fn add_row(&mut self, row: &[T]) {
         debug_assert_eq!(self.cols, row.len());
 
@@ -164,9 +325,98 @@ impl<T: Clone> DMatrix<T> {
         self.rows += 1;
     }
 
+    /// Like `add_row`, but checks the length of `row`.
+    pub fn try_add_row(&mut self, row: &[T]) -> Result<(), HopfieldError> {
+        check_len("row", self.cols, row.len())?;
+
+        self.add_row(row);
+        Ok(())
+    }
+
     pub fn new(rows: usize, cols: usize, default: T) -> Self {
         let data = vec![default; rows * cols];
 
         Self { rows, cols, data }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn try_variants_check_shapes() {
+        let mut m = DMatrix::new(2, 3, 1.);
+        let mut out = vec![0.; 2];
+
+        assert_eq!(
+            m.try_mul_vec(&[1.; 2], &mut out),
+            Err(HopfieldError::DimensionMismatch {
+                what: "input",
+                expected: 3,
+                found: 2
+            })
+        );
+        assert!(m.try_trans_mul_vec(&[1.; 2], &mut out).is_err());
+        assert!(m.try_add_matrix(&DMatrix::new(3, 2, 1.)).is_err());
+        assert!(m.try_add_row(&[1.; 2]).is_err());
+
+        m.try_mul_vec(&[1.; 3], &mut out).unwrap();
+        assert_eq!(out, vec![3.; 2]);
+        m.try_add_matrix(&DMatrix::new(2, 3, 1.)).unwrap();
+        assert_eq!(m[(1, 2)], 2.);
+    }
+
+    #[test]
+    fn mul_vec_matches_row_products() {
+        // Large enough to take the parallel path with the `parallel` feature.
+        const R: usize = 170;
+        const C: usize = 130;
+
+        let mut m = DMatrix::new(R, C, 0.);
+        for r in 0..R {
+            for c in 0..C {
+                m[(r, c)] = ((r * C + c) as f64 * 0.37).sin();
+            }
+        }
+        let x: Vec<f64> = (0..C).map(|i| (i as f64) - 2.).collect();
+        let y: Vec<f64> = (0..R).map(|i| (i as f64) * 0.5).collect();
+        let mut mx = vec![0.; R];
+        let mut mty = vec![0.; C];
+
+        m.mul_vec(&x, &mut mx);
+        m.trans_mul_vec(&y, &mut mty);
+
+        for r in 0..R {
+            assert_eq!(mx[r], m.mul_row_vec(&x, r));
+        }
+        for c in 0..C {
+            assert!((mty[c] - m.mul_col_vec(&y, c)).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn display_uses_precision() {
+        let mut m = DMatrix::new(2, 2, 0.);
+        m[(0, 1)] = 1. / 3.;
+        m[(1, 0)] = -2.;
+
+        assert_eq!(format!("{:.2}", m), "0.00 0.33\n-2.00 0.00");
+        assert_eq!(format!("{:5.1}", m), "  0.0   0.3\n -2.0   0.0");
+        assert_eq!(format!("{}", DMatrix::new(1, 3, 7)), "7 7 7");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut m = DMatrix::new(2, 3, 0.);
+        m[(1, 2)] = 1.5;
+
+        let json = serde_json::to_string(&m).unwrap();
+
+        assert_eq!(serde_json::from_str::<DMatrix<f64>>(&json).unwrap(), m);
+        assert!(
+            serde_json::from_str::<DMatrix<f64>>(r#"{"rows":2,"cols":2,"data":[1.0]}"#).is_err()
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! The error type returned by the fallible `try_` variants of the operations
+//! in this crate. The infallible versions only check their arguments with
+//! `debug_assert!`, so release builds may truncate or panic on malformed
+//! input; the `try_` variants check shapes, hyper-parameters and values up
+//! front and leave their arguments untouched when they return an error.
+//!
+//! The exceptions, which have no `try_` variant, are:
+//!
+//! - accessors and element access (`get`, `set`, `flip`, `row`, indexing),
+//!   which panic on out-of-range indices like slice indexing does;
+//! - the low-level matrix products `DMatrix::mul_row_col`, `mul_row_vec` and
+//!   `mul_col_vec` and `SMatrix::row_mul`, `SpMatrix::row_mul`, which are
+//!   building blocks for the checked operations;
+//! - reading and writing in `format`, `image` and `npy`, which already return
+//!   an `io::Result`;
+//! - operations that cannot fail, like `State::flip_random`, `erase_random`
+//!   and `erase_block`, which clip their counts to the state.
+
+use crate::scalar::Scalar;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HopfieldError {
+    /// An argument does not have the size required by the model or matrix.
+    DimensionMismatch {
+        what: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    /// The operation needs at least one stored pattern (or memory code).
+    EmptyMemory,
+    /// A hyper-parameter is out of range.
+    InvalidParameter {
+        name: &'static str,
+        reason: &'static str,
+    },
+    /// An argument contains a NaN or infinite value at `index`.
+    NonFinite { what: &'static str, index: usize },
+}
+
+impl Display for HopfieldError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            HopfieldError::DimensionMismatch {
+                what,
+                expected,
+                found,
+            } => write!(f, "{} has size {}, expected {}", what, found, expected),
+            HopfieldError::EmptyMemory => write!(f, "no patterns are stored"),
+            HopfieldError::InvalidParameter { name, reason } => {
+                write!(f, "invalid {}: {}", name, reason)
+            }
+            HopfieldError::NonFinite { what, index } => {
+                write!(f, "{} has a non-finite value at index {}", what, index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HopfieldError {}
+
+pub(crate) fn check_len(
+    what: &'static str,
+    expected: usize,
+    found: usize,
+) -> Result<(), HopfieldError> {
+    if expected != found {
+        return Err(HopfieldError::DimensionMismatch {
+            what,
+            expected,
+            found,
+        });
+    }
+
+    Ok(())
+}
+
+pub(crate) fn check_finite<T: Scalar>(
+    what: &'static str,
+    values: &[T],
+) -> Result<(), HopfieldError> {
+    match values.iter().position(|v| !v.to_f64().is_finite()) {
+        Some(index) => Err(HopfieldError::NonFinite { what, index }),
+        None => Ok(()),
+    }
+}
+
+// Check the length of `values` and that all of them are finite.
+pub(crate) fn check_vec<T: Scalar>(
+    what: &'static str,
+    expected: usize,
+    values: &[T],
+) -> Result<(), HopfieldError> {
+    check_len(what, expected, values.len())?;
+    check_finite(what, values)
+}
+
+// Learning rates and step sizes must be finite and positive.
+pub(crate) fn check_rate<T: Scalar>(name: &'static str, rate: T) -> Result<(), HopfieldError> {
+    if !rate.to_f64().is_finite() || rate <= T::ZERO {
+        return Err(HopfieldError::InvalidParameter {
+            name,
+            reason: "must be finite and positive",
+        });
+    }
+
+    Ok(())
+}
+
+// Temperatures and similar parameters must be finite and non-negative.
+pub(crate) fn check_non_negative<T: Scalar>(
+    name: &'static str,
+    value: T,
+) -> Result<(), HopfieldError> {
+    if !value.to_f64().is_finite() || value < T::ZERO {
+        return Err(HopfieldError::InvalidParameter {
+            name,
+            reason: "must be finite and non-negative",
+        });
+    }
+
+    Ok(())
+}
+
+// Decay rates like the momentum and the Adam betas must be in `[0, 1)`.
+pub(crate) fn check_unit<T: Scalar>(name: &'static str, v: T) -> Result<(), HopfieldError> {
+    if !(v >= T::ZERO && v < T::ONE) {
+        return Err(HopfieldError::InvalidParameter {
+            name,
+            reason: "must be in [0, 1)",
+        });
+    }
+
+    Ok(())
+}
+
+// Probabilities must be in `[0, 1]`.
+pub(crate) fn check_probability(name: &'static str, p: f64) -> Result<(), HopfieldError> {
+    if !(0. ..=1.).contains(&p) {
+        return Err(HopfieldError::InvalidParameter {
+            name,
+            reason: "must be in [0, 1]",
+        });
+    }
+
+    Ok(())
+}
+
+pub(crate) fn check_index(
+    what: &'static str,
+    index: usize,
+    len: usize,
+) -> Result<(), HopfieldError> {
+    if index >= len {
+        return Err(HopfieldError::InvalidParameter {
+            name: what,
+            reason: "index out of range",
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,825 @@
+//! A compact, versioned binary file format for trained networks. Unlike the
+//! `serde` support this format is fixed, so files can be read by other tools
+//! and across versions of this crate. All numbers are little-endian.
+//!
+//! | offset | size | contents                                          |
+//! |--------|------|---------------------------------------------------|
+//! | 0      | 4    | magic bytes `HOPF`                                |
+//! | 4      | 2    | format version, currently 1                       |
+//! | 6      | 1    | model kind: 1 classic, 2 modern, 3 PCN            |
+//! | 7      | 1    | scalar type: 1 `f32`, 2 `f64`, 3 `f16`            |
+//! | 8      | 8    | payload length in bytes                           |
+//! | 16     | 4    | CRC-32 (IEEE) of the payload                      |
+//! | 20     | 4    | reserved, zero                                    |
+//! | 24     |      | payload                                           |
+//!
+//! The payload starts with the shape as `u64`s followed by the values:
+//!
+//! - classic: `rows`, then the upper triangle of the `SMatrix` row by row
+//!   (row `r` holding columns `r..rows`), `rows * (rows + 1) / 2` values.
+//! - modern: `rows` (stored patterns), `cols`, then the patterns row by row.
+//! - PCN: `layers`, `neurons`, `sensors`, `learn_steps`, `prior_count` and
+//!   `codes`, then the generative weights of each layer, the recognition
+//!   weights of each layer, the prior mean, the prior sum of squared
+//!   deviations and finally the codebook. Matrices are stored row by row.
+//!
+//! Values start at an offset that is a multiple of 8, so a modern memory can
+//! be used directly from a memory mapped file with the `mmap` feature.
+
+use crate::dmatrix::DMatrix;
+use crate::pcn::{PCNParams, PCN};
+use crate::scalar::Scalar;
+use crate::smatrix::SMatrix;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read, Write};
+
+pub const MAGIC: [u8; 4] = *b"HOPF";
+pub const VERSION: u16 = 1;
+
+const HEADER_SIZE: usize = 24;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Classic = 1,
+    Modern = 2,
+    Pcn = 3,
+}
+
+impl Kind {
+    fn from_code(code: u8) -> Option<Kind> {
+        match code {
+            1 => Some(Kind::Classic),
+            2 => Some(Kind::Modern),
+            3 => Some(Kind::Pcn),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalarType {
+    F32 = 1,
+    F64 = 2,
+    F16 = 3,
+}
+
+impl ScalarType {
+    fn from_code(code: u8) -> Option<ScalarType> {
+        match code {
+            1 => Some(ScalarType::F32),
+            2 => Some(ScalarType::F64),
+            3 => Some(ScalarType::F16),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u16),
+    UnknownKind(u8),
+    UnknownScalar(u8),
+    WrongKind {
+        expected: Kind,
+        found: Kind,
+    },
+    WrongScalar {
+        expected: ScalarType,
+        found: ScalarType,
+    },
+    /// The file ends before the payload does.
+    Truncated,
+    ChecksumMismatch {
+        expected: u32,
+        found: u32,
+    },
+    /// The shapes in the payload are inconsistent.
+    InvalidShape(String),
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Io(e) => write!(f, "i/o error: {}", e),
+            FormatError::BadMagic => write!(f, "not a Hopfield model file"),
+            FormatError::UnsupportedVersion(v) => {
+                write!(f, "unsupported format version {} (expected {})", v, VERSION)
+            }
+            FormatError::UnknownKind(k) => write!(f, "unknown model kind {}", k),
+            FormatError::UnknownScalar(s) => write!(f, "unknown scalar type {}", s),
+            FormatError::WrongKind { expected, found } => {
+                write!(f, "expected a {:?} model, found {:?}", expected, found)
+            }
+            FormatError::WrongScalar { expected, found } => {
+                write!(f, "expected {:?} values, found {:?}", expected, found)
+            }
+            FormatError::Truncated => write!(f, "file is truncated"),
+            FormatError::ChecksumMismatch { expected, found } => write!(
+                f,
+                "checksum mismatch: expected {:08x}, found {:08x}",
+                expected, found
+            ),
+            FormatError::InvalidShape(s) => write!(f, "invalid shape: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FormatError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FormatError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            FormatError::Truncated
+        } else {
+            FormatError::Io(e)
+        }
+    }
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+
+        while k < 8 {
+            c = if c & 1 == 1 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+
+        table[i] = c;
+        i += 1;
+    }
+
+    table
+};
+
+/// Incremental CRC-32 (IEEE 802.3), as used by zip and png.
+#[derive(Clone, Debug)]
+pub(crate) struct Crc32(u32);
+
+impl Crc32 {
+    pub(crate) fn new() -> Self {
+        Crc32(0xffff_ffff)
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = CRC_TABLE[((self.0 ^ *b as u32) & 0xff) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+
+    crc.finish()
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for f32 {}
+    impl Sealed for f64 {}
+    #[cfg(feature = "half")]
+    impl Sealed for half::f16 {}
+}
+
+/// Scalar types that can be stored in model files. The trait is sealed: all
+/// implementing types are plain little-endian numbers for which every bit
+/// pattern is a valid value.
+pub trait FormatScalar: Scalar + sealed::Sealed {
+    const TYPE: ScalarType;
+    const SIZE: usize;
+
+    fn write_le(self, out: &mut Vec<u8>);
+    fn read_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_format_scalar {
+    ($t:ty, $code:expr) => {
+        impl FormatScalar for $t {
+            const TYPE: ScalarType = $code;
+            const SIZE: usize = std::mem::size_of::<$t>();
+
+            fn write_le(self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn read_le(bytes: &[u8]) -> Self {
+                <$t>::from_le_bytes(bytes.try_into().unwrap())
+            }
+        }
+    };
+}
+
+impl_format_scalar!(f32, ScalarType::F32);
+impl_format_scalar!(f64, ScalarType::F64);
+#[cfg(feature = "half")]
+impl_format_scalar!(half::f16, ScalarType::F16);
+
+/// The fixed size header at the start of every model file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub version: u16,
+    pub kind: Kind,
+    pub scalar: ScalarType,
+    pub payload_len: u64,
+    pub checksum: u32,
+}
+
+impl Header {
+    fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut b = [0; HEADER_SIZE];
+
+        b[0..4].copy_from_slice(&MAGIC);
+        b[4..6].copy_from_slice(&self.version.to_le_bytes());
+        b[6] = self.kind as u8;
+        b[7] = self.scalar as u8;
+        b[8..16].copy_from_slice(&self.payload_len.to_le_bytes());
+        b[16..20].copy_from_slice(&self.checksum.to_le_bytes());
+
+        b
+    }
+
+    /// Parse and check a header. The version, kind and scalar type must be
+    /// known to this version of the crate.
+    pub fn from_bytes(b: &[u8]) -> Result<Header, FormatError> {
+        if b.len() < HEADER_SIZE {
+            return Err(FormatError::Truncated);
+        }
+        if b[0..4] != MAGIC {
+            return Err(FormatError::BadMagic);
+        }
+
+        let version = u16::from_le_bytes([b[4], b[5]]);
+        if version != VERSION {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+
+        Ok(Header {
+            version,
+            kind: Kind::from_code(b[6]).ok_or(FormatError::UnknownKind(b[6]))?,
+            scalar: ScalarType::from_code(b[7]).ok_or(FormatError::UnknownScalar(b[7]))?,
+            payload_len: u64::from_le_bytes(b[8..16].try_into().unwrap()),
+            checksum: u32::from_le_bytes(b[16..20].try_into().unwrap()),
+        })
+    }
+
+    fn expect<T: FormatScalar>(&self, kind: Kind) -> Result<(), FormatError> {
+        if self.kind != kind {
+            return Err(FormatError::WrongKind {
+                expected: kind,
+                found: self.kind,
+            });
+        }
+        if self.scalar != T::TYPE {
+            return Err(FormatError::WrongScalar {
+                expected: T::TYPE,
+                found: self.scalar,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+// The payload as a sequence of `u64` shape values and slices of scalars.
+enum Part<'a, T> {
+    Size(usize),
+    Values(&'a [T]),
+}
+
+// Encode the parts chunk by chunk, so large payloads are never held in memory.
+fn encode<T: FormatScalar>(
+    parts: &[Part<'_, T>],
+    mut sink: impl FnMut(&[u8]) -> io::Result<()>,
+) -> io::Result<()> {
+    const CHUNK: usize = 4096;
+
+    let mut buf = Vec::with_capacity(CHUNK * T::SIZE);
+
+    for part in parts {
+        match part {
+            Part::Size(s) => sink(&(*s as u64).to_le_bytes())?,
+            Part::Values(values) => {
+                for chunk in values.chunks(CHUNK) {
+                    buf.clear();
+                    for v in chunk {
+                        v.write_le(&mut buf);
+                    }
+                    sink(&buf)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_model<T: FormatScalar, W: Write>(
+    writer: &mut W,
+    kind: Kind,
+    parts: &[Part<'_, T>],
+) -> Result<(), FormatError> {
+    let mut crc = Crc32::new();
+    let mut len = 0;
+
+    encode(parts, |b| {
+        crc.update(b);
+        len += b.len() as u64;
+        Ok(())
+    })?;
+
+    let header = Header {
+        version: VERSION,
+        kind,
+        scalar: T::TYPE,
+        payload_len: len,
+        checksum: crc.finish(),
+    };
+
+    writer.write_all(&header.to_bytes())?;
+    encode(parts, |b| writer.write_all(b))?;
+
+    Ok(())
+}
+
+fn read_payload<T: FormatScalar, R: Read>(
+    reader: &mut R,
+    kind: Kind,
+) -> Result<Vec<u8>, FormatError> {
+    let mut header = [0; HEADER_SIZE];
+    reader.read_exact(&mut header)?;
+
+    let header = Header::from_bytes(&header)?;
+    header.expect::<T>(kind)?;
+
+    let mut payload = Vec::new();
+    reader.take(header.payload_len).read_to_end(&mut payload)?;
+
+    if (payload.len() as u64) < header.payload_len {
+        return Err(FormatError::Truncated);
+    }
+
+    check_crc(&header, &payload)?;
+
+    Ok(payload)
+}
+
+fn check_crc(header: &Header, payload: &[u8]) -> Result<(), FormatError> {
+    let found = crc32(payload);
+
+    if found != header.checksum {
+        return Err(FormatError::ChecksumMismatch {
+            expected: header.checksum,
+            found,
+        });
+    }
+
+    Ok(())
+}
+
+// Reads shapes and values from a payload, failing on missing bytes.
+struct Cursor<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], FormatError> {
+        if n > self.bytes.len() {
+            return Err(FormatError::Truncated);
+        }
+
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+
+        Ok(head)
+    }
+
+    fn size(&mut self) -> Result<usize, FormatError> {
+        let v = u64::from_le_bytes(self.take(8)?.try_into().unwrap());
+
+        usize::try_from(v)
+            .map_err(|_| FormatError::InvalidShape(format!("size {} is too large", v)))
+    }
+
+    fn values<T: FormatScalar>(&mut self, count: usize) -> Result<Vec<T>, FormatError> {
+        let n = count.checked_mul(T::SIZE).ok_or(FormatError::Truncated)?;
+
+        Ok(self
+            .take(n)?
+            .chunks_exact(T::SIZE)
+            .map(T::read_le)
+            .collect())
+    }
+
+    fn finish(&self) -> Result<(), FormatError> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(FormatError::InvalidShape(format!(
+                "{} bytes left after the last value",
+                self.bytes.len()
+            )))
+        }
+    }
+}
+
+fn product(a: usize, b: usize) -> Result<usize, FormatError> {
+    a.checked_mul(b)
+        .ok_or_else(|| FormatError::InvalidShape(format!("{}x{} is too large", a, b)))
+}
+
+/// Write the weights of a classic network.
+pub fn write_classic<T: FormatScalar, W: Write>(
+    writer: &mut W,
+    weights: &SMatrix<T>,
+) -> Result<(), FormatError> {
+    write_model(
+        writer,
+        Kind::Classic,
+        &[Part::Size(weights.rows()), Part::Values(weights.packed())],
+    )
+}
+
+pub fn read_classic<T: FormatScalar, R: Read>(reader: &mut R) -> Result<SMatrix<T>, FormatError> {
+    let payload = read_payload::<T, R>(reader, Kind::Classic)?;
+    let mut cursor = Cursor { bytes: &payload };
+
+    let rows = cursor.size()?;
+    let size = product(rows, rows.saturating_add(1))? / 2;
+    let data = cursor.values(size)?;
+    cursor.finish()?;
+
+    Ok(SMatrix::from_packed(rows, data))
+}
+
+/// Write the memory matrix of a modern network (one stored pattern per row).
+pub fn write_modern<T: FormatScalar, W: Write>(
+    writer: &mut W,
+    memory: &DMatrix<T>,
+) -> Result<(), FormatError> {
+    write_model(
+        writer,
+        Kind::Modern,
+        &[
+            Part::Size(memory.rows()),
+            Part::Size(memory.cols()),
+            Part::Values(memory.as_slice()),
+        ],
+    )
+}
+
+pub fn read_modern<T: FormatScalar, R: Read>(reader: &mut R) -> Result<DMatrix<T>, FormatError> {
+    let payload = read_payload::<T, R>(reader, Kind::Modern)?;
+    let mut cursor = Cursor { bytes: &payload };
+
+    let rows = cursor.size()?;
+    let cols = cursor.size()?;
+    let data = cursor.values(product(rows, cols)?)?;
+    cursor.finish()?;
+
+    Ok(DMatrix::from_vec(rows, cols, data))
+}
+
+/// Write the learned parameters of a PCN. As with `serde`, neuron activity
+/// and optimiser state are not saved.
+pub fn write_pcn<T: FormatScalar, W: Write>(
+    writer: &mut W,
+    pcn: &PCN<T>,
+) -> Result<(), FormatError> {
+    let p = pcn.params();
+
+    let mut parts = vec![
+        Part::Size(p.layers),
+        Part::Size(p.weights[0].cols()),
+        Part::Size(p.weights[0].rows()),
+        Part::Size(p.learn_steps),
+        Part::Size(p.prior.count),
+        Part::Size(p.codebook.len()),
+    ];
+    parts.extend(p.weights.iter().map(|w| Part::Values(w.as_slice())));
+    parts.extend(p.recognition.iter().map(|w| Part::Values(w.as_slice())));
+    parts.push(Part::Values(&p.prior.mean));
+    parts.push(Part::Values(&p.prior.m2));
+    parts.extend(p.codebook.iter().map(|c| Part::Values(c.as_slice())));
+
+    write_model(writer, Kind::Pcn, &parts)
+}
+
+pub fn read_pcn<T: FormatScalar, R: Read>(reader: &mut R) -> Result<PCN<T>, FormatError> {
+    let payload = read_payload::<T, R>(reader, Kind::Pcn)?;
+    let mut cursor = Cursor { bytes: &payload };
+
+    let layers = cursor.size()?;
+    let n = cursor.size()?;
+    let d = cursor.size()?;
+    let learn_steps = cursor.size()?;
+    let count = cursor.size()?;
+    let codes = cursor.size()?;
+
+    if layers < 2 || n == 0 || d < 2 {
+        return Err(FormatError::InvalidShape(format!(
+            "{} layers of {} neurons and {} sensors",
+            layers, n, d
+        )));
+    }
+
+    let mut weights = Vec::new();
+    for l in 0..(layers - 1) {
+        let rows = if l == 0 { d } else { n };
+        weights.push(DMatrix::from_vec(
+            rows,
+            n,
+            cursor.values(product(rows, n)?)?,
+        ));
+    }
+
+    let mut recognition = Vec::new();
+    for l in 0..(layers - 1) {
+        let cols = if l == 0 { d } else { n };
+        recognition.push(DMatrix::from_vec(
+            n,
+            cols,
+            cursor.values(product(n, cols)?)?,
+        ));
+    }
+
+    let mean = cursor.values(n)?;
+    let m2 = cursor.values(n)?;
+
+    let mut codebook = Vec::new();
+    for _i in 0..codes {
+        codebook.push(cursor.values(n)?);
+    }
+
+    cursor.finish()?;
+
+    let params = PCNParams {
+        layers,
+        weights,
+        recognition,
+        prior: crate::pcn::MemoryPrior { mean, m2, count },
+        codebook,
+        learn_steps,
+    };
+
+    PCN::try_from(params).map_err(FormatError::InvalidShape)
+}
+
+/// A modern network memory used directly from a memory mapped model file,
+/// without reading it into memory first.
+#[cfg(all(feature = "mmap", target_endian = "little"))]
+pub struct MappedModern<T> {
+    map: memmap2::Mmap,
+    rows: usize,
+    cols: usize,
+    _values: std::marker::PhantomData<T>,
+}
+
+#[cfg(all(feature = "mmap", target_endian = "little"))]
+impl<T: FormatScalar> MappedModern<T> {
+    // Offset of the first value: the header and two shape values.
+    const DATA_OFFSET: usize = HEADER_SIZE + 16;
+
+    /// Map the model file at `path`. The header and checksum are verified,
+    /// which reads the file once. The file must not be modified while it is
+    /// mapped.
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self, FormatError> {
+        let file = std::fs::File::open(path)?;
+
+        // SAFETY: the mapping is read only, and modifying the file while it
+        // is mapped is documented as not allowed.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+
+        let header = Header::from_bytes(&map)?;
+        header.expect::<T>(Kind::Modern)?;
+
+        let payload = &map[HEADER_SIZE..];
+        if (payload.len() as u64) < header.payload_len {
+            return Err(FormatError::Truncated);
+        }
+
+        let payload = &payload[..header.payload_len as usize];
+        check_crc(&header, payload)?;
+
+        let mut cursor = Cursor { bytes: payload };
+        let rows = cursor.size()?;
+        let cols = cursor.size()?;
+        let size = product(product(rows, cols)?, T::SIZE)?;
+
+        if cursor.bytes.len() != size {
+            return Err(FormatError::InvalidShape(format!(
+                "a {}x{} memory needs {} bytes of values, found {}",
+                rows,
+                cols,
+                size,
+                cursor.bytes.len()
+            )));
+        }
+        let address = map[Self::DATA_OFFSET..].as_ptr() as usize;
+        if !address.is_multiple_of(std::mem::align_of::<T>()) {
+            return Err(FormatError::InvalidShape(
+                "values are not aligned".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            map,
+            rows,
+            cols,
+            _values: std::marker::PhantomData,
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// All stored patterns, row by row.
+    pub fn as_slice(&self) -> &[T] {
+        let bytes = &self.map[Self::DATA_OFFSET..];
+
+        // SAFETY: `open` checked the length and alignment of the values, the
+        // file is little-endian like the target and `FormatScalar` is only
+        // implemented for types where every bit pattern is a valid value.
+        unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const T, self.rows * self.cols) }
+    }
+
+    /// Stored pattern `r`.
+    pub fn row(&self, r: usize) -> &[T] {
+        &self.as_slice()[r * self.cols..(r + 1) * self.cols]
+    }
+
+    /// Copy the memory into a `DMatrix`.
+    pub fn to_dmatrix(&self) -> DMatrix<T> {
+        DMatrix::from_vec(self.rows, self.cols, self.as_slice().to_vec())
+    }
+
+    /// One recall step, as `modern::update_state_sync`.
+    pub fn update_state_sync(&self, input_state: &[T], output_state: &mut [T]) {
+        use crate::state::State;
+
+        debug_assert_eq!(self.cols, input_state.len());
+        debug_assert_eq!(self.cols, output_state.len());
+
+        let mut scores: Vec<T> = (0..self.rows)
+            .map(|r| T::dot(self.row(r), input_state))
+            .collect();
+        scores.softmax();
+
+        output_state.fill(T::ZERO);
+        for r in 0..self.rows {
+            T::axpy(scores[r], self.row(r), output_state);
+        }
+
+        for v in output_state.iter_mut() {
+            *v = if *v >= T::ZERO { T::ONE } else { -T::ONE };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seed::Seed;
+
+    #[test]
+    fn crc_of_check_string() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn classic_round_trip() {
+        let w: SMatrix<f32> = SMatrix::from_fn2(5, &mut |r, c| (r * 5 + c) as f32 * 0.5);
+        let mut bytes = Vec::new();
+
+        write_classic(&mut bytes, &w).unwrap();
+
+        assert_eq!(&bytes[0..4], b"HOPF");
+        assert_eq!(bytes.len(), HEADER_SIZE + 8 + 15 * 4);
+        assert_eq!(read_classic::<f32, _>(&mut bytes.as_slice()).unwrap(), w);
+    }
+
+    #[test]
+    fn detects_corruption() {
+        let mut m = DMatrix::new(3, 4, 0.);
+        m[(1, 2)] = 2.;
+        let mut bytes = Vec::new();
+
+        write_modern(&mut bytes, &m).unwrap();
+
+        assert_eq!(read_modern::<f64, _>(&mut bytes.as_slice()).unwrap(), m);
+        assert!(matches!(
+            read_modern::<f32, _>(&mut bytes.as_slice()),
+            Err(FormatError::WrongScalar { .. })
+        ));
+        assert!(matches!(
+            read_classic::<f64, _>(&mut bytes.as_slice()),
+            Err(FormatError::WrongKind { .. })
+        ));
+        assert!(matches!(
+            read_modern::<f64, _>(&mut &bytes[..bytes.len() - 1]),
+            Err(FormatError::Truncated)
+        ));
+
+        let mut flipped = bytes.clone();
+        flipped[50] ^= 1;
+        assert!(matches!(
+            read_modern::<f64, _>(&mut flipped.as_slice()),
+            Err(FormatError::ChecksumMismatch { .. })
+        ));
+
+        let mut newer = bytes.clone();
+        newer[4] = 2;
+        assert!(matches!(
+            read_modern::<f64, _>(&mut newer.as_slice()),
+            Err(FormatError::UnsupportedVersion(2))
+        ));
+
+        let mut other = bytes.clone();
+        other[3] = b'E';
+        assert!(matches!(
+            read_modern::<f64, _>(&mut other.as_slice()),
+            Err(FormatError::BadMagic)
+        ));
+    }
+
+    #[test]
+    fn pcn_round_trip() {
+        let s: Vec<f64> = (0..6).map(|i| if i % 2 == 0 { 1. } else { -1. }).collect();
+        let m: Vec<f64> = vec![1., -1., 1.];
+        let mut pcn = PCN::new(3, 3, 6);
+        let mut rng = Seed(1).rng();
+
+        pcn.add_code(&mut rng);
+        for _i in 0..3 {
+            pcn.inference(&m, &s, 0.1, 50);
+            pcn.learn(0.1);
+        }
+
+        let mut bytes = Vec::new();
+        write_pcn(&mut bytes, &pcn).unwrap();
+        let mut loaded: PCN = read_pcn(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(loaded.learn_steps(), 3);
+        assert_eq!(loaded.codebook(), pcn.codebook());
+        assert_eq!(
+            loaded.memory_prior().variance(),
+            pcn.memory_prior().variance()
+        );
+        assert_eq!(loaded.generate(&m), pcn.generate(&m));
+    }
+
+    #[cfg(all(feature = "mmap", target_endian = "little"))]
+    #[test]
+    fn mapped_modern_recall() {
+        use crate::modern;
+
+        let p: Vec<f64> = vec![1., 1., -1., -1., 1., 1., -1., -1.];
+        let a: Vec<f64> = vec![1., 0., -1., 0., 0., 1., 0., 0.];
+        let mut m = DMatrix::new(1, 8, 0.);
+        modern::learn(&mut m, &p);
+
+        let path = std::env::temp_dir().join(format!("hopfield-mmap-{}.hopf", std::process::id()));
+        write_modern(&mut std::fs::File::create(&path).unwrap(), &m).unwrap();
+
+        let mapped = MappedModern::<f64>::open(&path).unwrap();
+        let mut expected = vec![0.; 8];
+        let mut output = vec![0.; 8];
+
+        modern::update_state_sync(&m, &a, &mut expected);
+        mapped.update_state_sync(&a, &mut output);
+
+        assert_eq!(mapped.to_dmatrix(), m);
+        assert_eq!(output, expected);
+
+        drop(mapped);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,417 @@
+//! Small images as patterns. Images are read from PBM and PGM files (plain
+//! and raw) or ASCII-art grids, turned into bipolar or real-valued states,
+//! and states are rendered back to PBM/PGM or to block art for the terminal.
+//!
+//! Pixels are stored as ink intensities in `[0, 1]`: 1 is black (a set bit in
+//! PBM, zero in PGM) and maps to the state value 1, white maps to -1.
+
+use crate::error::{check_len, HopfieldError};
+use crate::scalar::Scalar;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read, Write};
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    /// Not a PBM or PGM file.
+    BadMagic,
+    BadHeader(String),
+    Truncated,
+}
+
+impl Display for ImageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Io(e) => write!(f, "i/o error: {}", e),
+            ImageError::BadMagic => write!(f, "not a PBM or PGM file"),
+            ImageError::BadHeader(s) => write!(f, "bad header: {}", s),
+            ImageError::Truncated => write!(f, "pixel data is truncated"),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImageError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImageError {
+    fn from(e: io::Error) -> Self {
+        ImageError::Io(e)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    data: Vec<f64>,
+}
+
+// Reads the whitespace separated header and plain pixel data of PBM and PGM
+// files, skipping `#` comments.
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_space(&mut self) {
+        while let Some(b) = self.bytes.get(self.pos) {
+            if *b == b'#' {
+                while self.pos < self.bytes.len() && self.bytes[self.pos] != b'\n' {
+                    self.pos += 1;
+                }
+            } else if b.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn number(&mut self, what: &str) -> Result<usize, ImageError> {
+        self.skip_space();
+
+        let start = self.pos;
+        while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_digit() {
+            self.pos += 1;
+        }
+
+        std::str::from_utf8(&self.bytes[start..self.pos])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| ImageError::BadHeader(format!("expected {}", what)))
+    }
+
+    // A plain PBM pixel is a single `0` or `1`, which need not be separated.
+    fn bit(&mut self) -> Result<bool, ImageError> {
+        self.skip_space();
+
+        let b = self.bytes.get(self.pos).ok_or(ImageError::Truncated)?;
+        self.pos += 1;
+
+        match b {
+            b'0' => Ok(false),
+            b'1' => Ok(true),
+            _ => Err(ImageError::BadHeader("bad pixel in plain PBM".to_string())),
+        }
+    }
+}
+
+impl Image {
+    /// A white image.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0.; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The ink intensities, row by row.
+    pub fn as_slice(&self) -> &[f64] {
+        &self.data
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> f64 {
+        self.data[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, ink: f64) {
+        self.data[y * self.width + x] = ink.clamp(0., 1.);
+    }
+
+    /// Read a PBM (`P1`, `P4`) or PGM (`P2`, `P5`) file.
+    pub fn read_pnm<R: Read>(reader: &mut R) -> Result<Self, ImageError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        let magic = bytes.get(0..2).ok_or(ImageError::BadMagic)?;
+        let kind = match magic {
+            b"P1" | b"P2" | b"P4" | b"P5" => magic[1],
+            _ => return Err(ImageError::BadMagic),
+        };
+
+        let mut p = Parser {
+            bytes: &bytes,
+            pos: 2,
+        };
+        let width = p.number("width")?;
+        let height = p.number("height")?;
+        let maxval = match kind {
+            b'2' | b'5' => p.number("maximum value")?,
+            _ => 1,
+        };
+
+        if maxval == 0 || maxval > 65535 {
+            return Err(ImageError::BadHeader(format!("maximum value {}", maxval)));
+        }
+
+        // Every pixel takes at least one bit, so this rejects bogus sizes
+        // before allocating.
+        match width.checked_mul(height) {
+            Some(n) if n <= bytes.len() * 8 => (),
+            _ => return Err(ImageError::Truncated),
+        }
+
+        let mut image = Image::new(width, height);
+        let ink = |v: usize| 1. - v.min(maxval) as f64 / maxval as f64;
+
+        match kind {
+            b'1' => {
+                for i in 0..image.data.len() {
+                    image.data[i] = if p.bit()? { 1. } else { 0. };
+                }
+            }
+            b'2' => {
+                for i in 0..image.data.len() {
+                    image.data[i] = ink(p.number("pixel").map_err(|_| ImageError::Truncated)?);
+                }
+            }
+            _ => {
+                // A single whitespace character separates the header from the
+                // raster.
+                let raster = bytes.get(p.pos + 1..).ok_or(ImageError::Truncated)?;
+
+                if kind == b'4' {
+                    let stride = width.div_ceil(8);
+
+                    if raster.len() < stride * height {
+                        return Err(ImageError::Truncated);
+                    }
+
+                    for y in 0..height {
+                        for x in 0..width {
+                            let bit = (raster[y * stride + x / 8] >> (7 - x % 8)) & 1;
+                            image.data[y * width + x] = bit as f64;
+                        }
+                    }
+                } else {
+                    let size = if maxval < 256 { 1 } else { 2 };
+
+                    if raster.len() < size * width * height {
+                        return Err(ImageError::Truncated);
+                    }
+
+                    for i in 0..image.data.len() {
+                        let v = match size {
+                            1 => raster[i] as usize,
+                            _ => u16::from_be_bytes([raster[2 * i], raster[2 * i + 1]]) as usize,
+                        };
+
+                        image.data[i] = ink(v);
+                    }
+                }
+            }
+        }
+
+        Ok(image)
+    }
+
+    /// Parse an ASCII-art grid, one line per row. Spaces, `.`, `-`, `_` and
+    /// `0` are white, any other character is black. Short lines are padded
+    /// with white.
+    pub fn from_ascii(text: &str) -> Self {
+        let lines: Vec<&str> = text.lines().collect();
+        let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let mut image = Image::new(width, lines.len());
+
+        for (y, line) in lines.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                if !matches!(c, ' ' | '.' | '-' | '_' | '0') {
+                    image.data[y * width + x] = 1.;
+                }
+            }
+        }
+
+        image
+    }
+
+    /// The image as an ASCII-art grid of `#` and `.`, thresholded at
+    /// `threshold`.
+    pub fn to_ascii(&self, threshold: f64) -> String {
+        let mut text = String::with_capacity((self.width + 1) * self.height);
+
+        for row in self.data.chunks(self.width.max(1)) {
+            for v in row {
+                text.push(if *v >= threshold { '#' } else { '.' });
+            }
+            text.push('\n');
+        }
+
+        text
+    }
+
+    /// An image of the given size from a state, mapping -1 to white and 1 to
+    /// black. Values outside `[-1, 1]` are clamped.
+    pub fn from_state<T: Scalar>(
+        width: usize,
+        height: usize,
+        state: &[T],
+    ) -> Result<Self, HopfieldError> {
+        check_len("state", width * height, state.len())?;
+
+        Ok(Self {
+            width,
+            height,
+            data: state
+                .iter()
+                .map(|v| ((v.to_f64() + 1.) / 2.).clamp(0., 1.))
+                .collect(),
+        })
+    }
+
+    /// A bipolar state: 1 for pixels with at least `threshold` ink, -1 for
+    /// the others.
+    pub fn to_bipolar<T: Scalar>(&self, threshold: f64) -> Vec<T> {
+        self.data
+            .iter()
+            .map(|v| if *v >= threshold { T::ONE } else { -T::ONE })
+            .collect()
+    }
+
+    /// A real-valued state in `[-1, 1]`, linear in the ink intensity.
+    pub fn to_real<T: Scalar>(&self) -> Vec<T> {
+        self.data.iter().map(|v| T::from_f64(2. * v - 1.)).collect()
+    }
+
+    /// Write a raw PBM (`P4`) file, thresholded at `threshold`.
+    pub fn write_pbm<W: Write>(&self, writer: &mut W, threshold: f64) -> Result<(), ImageError> {
+        let stride = self.width.div_ceil(8);
+        let mut raster = vec![0_u8; stride * self.height];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.get(x, y) >= threshold {
+                    raster[y * stride + x / 8] |= 0x80 >> (x % 8);
+                }
+            }
+        }
+
+        write!(writer, "P4\n{} {}\n", self.width, self.height)?;
+        writer.write_all(&raster)?;
+
+        Ok(())
+    }
+
+    /// Write a raw 8-bit PGM (`P5`) file.
+    pub fn write_pgm<W: Write>(&self, writer: &mut W) -> Result<(), ImageError> {
+        let raster: Vec<u8> = self
+            .data
+            .iter()
+            .map(|v| ((1. - v) * 255.).round() as u8)
+            .collect();
+
+        write!(writer, "P5\n{} {}\n255\n", self.width, self.height)?;
+        writer.write_all(&raster)?;
+
+        Ok(())
+    }
+}
+
+/// Block art for the terminal, two pixel rows per line, with pixels of at
+/// least half intensity drawn as ink.
+impl Display for Image {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for y in (0..self.height).step_by(2) {
+            if y > 0 {
+                writeln!(f)?;
+            }
+
+            for x in 0..self.width {
+                let top = self.get(x, y) >= 0.5;
+                let bottom = y + 1 < self.height && self.get(x, y + 1) >= 0.5;
+
+                let c = match (top, bottom) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                };
+
+                write!(f, "{}", c)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LETTER: &str = "\
+.###.
+#...#
+#####
+#...#
+#...#";
+
+    #[test]
+    fn ascii_and_states() {
+        let image = Image::from_ascii(LETTER);
+        let state: Vec<f64> = image.to_bipolar(0.5);
+
+        assert_eq!((image.width(), image.height()), (5, 5));
+        assert_eq!(&state[..5], &[-1., 1., 1., 1., -1.]);
+        assert_eq!(image.to_ascii(0.5), format!("{}\n", LETTER));
+        assert_eq!(Image::from_state(5, 5, &state).unwrap(), image);
+        assert!(Image::from_state(4, 5, &state).is_err());
+        assert_eq!(image.to_string(), "▄▀▀▀▄\n█▀▀▀█\n▀   ▀");
+    }
+
+    #[test]
+    fn pbm_and_pgm_round_trip() {
+        let mut image = Image::from_ascii(LETTER);
+        image.set(0, 0, 0.4);
+
+        let mut pbm = Vec::new();
+        image.write_pbm(&mut pbm, 0.5).unwrap();
+        let read = Image::read_pnm(&mut pbm.as_slice()).unwrap();
+
+        assert_eq!(read, Image::from_ascii(LETTER));
+
+        let mut pgm = Vec::new();
+        image.write_pgm(&mut pgm).unwrap();
+        let read = Image::read_pnm(&mut pgm.as_slice()).unwrap();
+
+        for i in 0..25 {
+            assert!((read.as_slice()[i] - image.as_slice()[i]).abs() < 1. / 255.);
+        }
+    }
+
+    #[test]
+    fn reads_plain_formats() {
+        let pbm = "P1\n# a comment\n3 2\n010\n1 0 1\n";
+        let image = Image::read_pnm(&mut pbm.as_bytes()).unwrap();
+
+        assert_eq!(image.as_slice(), &[0., 1., 0., 1., 0., 1.]);
+
+        let pgm = "P2 2 1 4\n0 3\n";
+        let image = Image::read_pnm(&mut pgm.as_bytes()).unwrap();
+
+        assert_eq!(image.as_slice(), &[1., 0.25]);
+        assert!(matches!(
+            Image::read_pnm(&mut "P3 1 1 1 0".as_bytes()),
+            Err(ImageError::BadMagic)
+        ));
+        assert!(matches!(
+            Image::read_pnm(&mut "P1 3 2 0101".as_bytes()),
+            Err(ImageError::Truncated)
+        ));
+    }
+}
@@ -0,0 +1,359 @@
+//! Exhaustive enumeration of the attractor landscape of small classic
+//! networks. Every one of the `2^N` states is updated with one sweep of
+//! asynchronous updates (`classic::update_state_async` for neurons `0..N` in
+//! order), which gives a transition graph whose sinks are the fixed points of
+//! the dynamics. For each fixed point the report gives its energy, the number
+//! of states that end up in it and whether it is a stored pattern, the
+//! negation of one or a spurious state.
+//!
+//! States are encoded as integers with bit `i` set when neuron `i` is 1. With
+//! symmetric weights and non-negative self-couplings every state reaches a
+//! fixed point; negative self-couplings can cause cycles, whose states are
+//! counted separately.
+
+use crate::classic::{self, Weights};
+use crate::error::{check_len, check_vec, HopfieldError};
+use crate::metrics;
+use crate::scalar::Scalar;
+use crate::MaybeSync;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::fmt::{self, Display, Formatter, Write};
+
+/// The largest network that can be enumerated.
+pub const MAX_NEURONS: usize = 24;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Kind {
+    /// Stored pattern `k`.
+    Pattern(usize),
+    /// The negation of stored pattern `k`.
+    Negation(usize),
+    /// A spurious state, such as a mixture of patterns.
+    Spurious,
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Pattern(k) => write!(f, "pattern {}", k),
+            Kind::Negation(k) => write!(f, "negation of pattern {}", k),
+            Kind::Spurious => write!(f, "spurious"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FixedPoint<T = f64> {
+    /// The encoded state.
+    pub state: u32,
+    pub energy: T,
+    /// The number of states, including the fixed point itself, that end up
+    /// in this fixed point.
+    pub basin: usize,
+    pub kind: Kind,
+    /// The overlap with each stored pattern.
+    pub overlaps: Vec<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Landscape<T = f64> {
+    neurons: usize,
+    next: Vec<u32>,
+    attractor: Vec<u32>,
+    fixed_points: Vec<FixedPoint<T>>,
+    cycle_states: usize,
+}
+
+// Markers in `attractor` for states that are not resolved to a fixed point.
+const UNKNOWN: u32 = u32::MAX;
+const ON_PATH: u32 = u32::MAX - 1;
+const CYCLE: u32 = u32::MAX - 2;
+
+fn decode<T: Scalar>(neurons: usize, state: u32) -> Vec<T> {
+    (0..neurons)
+        .map(|i| {
+            if (state >> i) & 1 == 1 {
+                T::ONE
+            } else {
+                -T::ONE
+            }
+        })
+        .collect()
+}
+
+fn encode<T: Scalar>(values: &[T]) -> u32 {
+    values
+        .iter()
+        .enumerate()
+        .filter(|(_, v)| **v >= T::ZERO)
+        .fold(0, |acc, (i, _)| acc | 1 << i)
+}
+
+/// Enumerate the landscape of the network with the given weights and bias.
+/// `patterns` are the stored patterns the fixed points are compared with.
+pub fn enumerate<T: Scalar, W: Weights<T> + MaybeSync, P: AsRef<[T]>>(
+    weights: &W,
+    bias: &[T],
+    patterns: &[P],
+) -> Result<Landscape<T>, HopfieldError> {
+    let n = weights.rows();
+
+    if n > MAX_NEURONS {
+        return Err(HopfieldError::InvalidParameter {
+            name: "neurons",
+            reason: "at most 24 neurons can be enumerated",
+        });
+    }
+
+    check_vec("bias", n, bias)?;
+    for p in patterns {
+        check_len("pattern", n, p.as_ref().len())?;
+    }
+
+    let sweep = |state: u32| {
+        let mut values = decode(n, state);
+
+        for i in 0..n {
+            classic::update_state_async(weights, bias, &mut values, i);
+        }
+
+        encode(&values)
+    };
+
+    let count = 1_u32 << n;
+
+    #[cfg(feature = "parallel")]
+    let next: Vec<u32> = (0..count).into_par_iter().map(sweep).collect();
+    #[cfg(not(feature = "parallel"))]
+    let next: Vec<u32> = (0..count).map(sweep).collect();
+
+    let mut attractor = vec![UNKNOWN; count as usize];
+    let mut fixed_points = Vec::new();
+    let coded: Vec<u32> = patterns.iter().map(|p| encode(p.as_ref())).collect();
+    let all = count - 1;
+
+    for s in 0..count {
+        if next[s as usize] == s {
+            let values = decode(n, s);
+            let kind = match coded.iter().position(|p| *p == s) {
+                Some(k) => Kind::Pattern(k),
+                None => match coded.iter().position(|p| *p ^ all == s) {
+                    Some(k) => Kind::Negation(k),
+                    None => Kind::Spurious,
+                },
+            };
+
+            attractor[s as usize] = fixed_points.len() as u32;
+            fixed_points.push(FixedPoint {
+                state: s,
+                energy: classic::energy(weights, bias, &values),
+                basin: 0,
+                kind,
+                overlaps: metrics::overlaps(patterns, &values),
+            });
+        }
+    }
+
+    // Follow each state until it reaches a resolved state, then resolve the
+    // whole path.
+    let mut path = Vec::new();
+
+    for s in 0..count {
+        let mut t = s;
+
+        while attractor[t as usize] == UNKNOWN {
+            attractor[t as usize] = ON_PATH;
+            path.push(t);
+            t = next[t as usize];
+        }
+
+        let end = match attractor[t as usize] {
+            ON_PATH => CYCLE,
+            a => a,
+        };
+
+        for u in path.drain(..) {
+            attractor[u as usize] = end;
+        }
+    }
+
+    let mut cycle_states = 0;
+
+    for a in &attractor {
+        match *a {
+            CYCLE => cycle_states += 1,
+            a => fixed_points[a as usize].basin += 1,
+        }
+    }
+
+    Ok(Landscape {
+        neurons: n,
+        next,
+        attractor,
+        fixed_points,
+        cycle_states,
+    })
+}
+
+impl<T> Landscape<T> {
+    pub fn neurons(&self) -> usize {
+        self.neurons
+    }
+
+    /// The fixed points in order of their encoded states.
+    pub fn fixed_points(&self) -> &[FixedPoint<T>] {
+        &self.fixed_points
+    }
+
+    /// The number of states that end up in a cycle instead of a fixed point.
+    pub fn cycle_states(&self) -> usize {
+        self.cycle_states
+    }
+
+    /// The state reached from `state` by one sweep of updates.
+    pub fn next(&self, state: u32) -> u32 {
+        self.next[state as usize]
+    }
+
+    /// The fixed point reached from `state`, if any.
+    pub fn attractor(&self, state: u32) -> Option<&FixedPoint<T>> {
+        self.fixed_points
+            .get(self.attractor[state as usize] as usize)
+    }
+
+    // The state as a string of `+` and `-`, neuron 0 first.
+    fn label(&self, state: u32) -> String {
+        (0..self.neurons)
+            .map(|i| if (state >> i) & 1 == 1 { '+' } else { '-' })
+            .collect()
+    }
+}
+
+impl<T: Scalar> Landscape<T> {
+    /// The state-transition graph in Graphviz DOT format, with the fixed
+    /// points drawn as double circles labelled with their energy. The graph
+    /// has a node per state, so it is only readable for about 10 neurons.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph landscape {\n    node [shape=circle];\n");
+
+        for fp in &self.fixed_points {
+            let _ = writeln!(
+                dot,
+                "    {} [shape=doublecircle, label=\"{}\\nE={:.3}\\n{}\"];",
+                fp.state,
+                self.label(fp.state),
+                fp.energy,
+                fp.kind
+            );
+        }
+
+        for s in 0..self.next.len() as u32 {
+            let t = self.next(s);
+
+            if t != s {
+                let _ = writeln!(dot, "    {} [label=\"{}\"];", s, self.label(s));
+                let _ = writeln!(dot, "    {} -> {};", s, t);
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// A table of the fixed points.
+impl<T: Scalar> Display for Landscape<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} fixed points in {} states",
+            self.fixed_points.len(),
+            self.next.len()
+        )?;
+
+        if self.cycle_states > 0 {
+            writeln!(f, "{} states end in cycles", self.cycle_states)?;
+        }
+
+        for fp in &self.fixed_points {
+            writeln!(
+                f,
+                "{}  E={:.4}  basin={}  {}",
+                self.label(fp.state),
+                fp.energy,
+                fp.basin,
+                fp.kind
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::smatrix::SMatrix;
+
+    #[test]
+    fn single_pattern_landscape() {
+        let p: Vec<f64> = vec![1., -1., 1., 1., -1., -1.];
+        let mut weights = SMatrix::new(6, 0.);
+        classic::hebb_learn(&mut weights, &p);
+
+        let landscape = enumerate(&weights, &[0.; 6], &[&p]).unwrap();
+        let kinds: Vec<Kind> = landscape.fixed_points().iter().map(|fp| fp.kind).collect();
+
+        assert!(kinds.contains(&Kind::Pattern(0)));
+        assert!(kinds.contains(&Kind::Negation(0)));
+        assert_eq!(
+            landscape
+                .fixed_points()
+                .iter()
+                .map(|fp| fp.basin)
+                .sum::<usize>(),
+            64
+        );
+        assert_eq!(landscape.cycle_states(), 0);
+
+        let start = encode(&[1., -1., 1., 1., 1., 1.]);
+        let end = landscape.attractor(start).unwrap();
+        assert_eq!(end.kind, Kind::Pattern(0));
+        assert_eq!(end.overlaps, vec![1.]);
+        assert!(end.energy < 0.);
+
+        let dot = landscape.to_dot();
+        assert!(dot.starts_with("digraph landscape {"));
+        assert!(dot.contains(&format!("{} [shape=doublecircle", encode(&p))));
+        assert!(landscape.to_string().contains("+-++--  E="));
+    }
+
+    #[test]
+    fn overloaded_network_has_spurious_states() {
+        const N: usize = 10;
+
+        let patterns: Vec<Vec<f64>> = (0..4)
+            .map(|k| {
+                (0..N)
+                    .map(|i| if (i * (k + 3) + k) % 5 < 2 { 1. } else { -1. })
+                    .collect()
+            })
+            .collect();
+        let mut weights = SMatrix::new(N, 0.);
+        for p in &patterns {
+            classic::hebb_learn(&mut weights, p);
+        }
+
+        let landscape = enumerate(&weights, &[0.; N], &patterns).unwrap();
+
+        assert!(landscape
+            .fixed_points()
+            .iter()
+            .any(|fp| fp.kind == Kind::Spurious));
+        assert!(enumerate(&SMatrix::new(25, 0.), &[0.; 25], &patterns).is_err());
+        assert!(enumerate(&weights, &[0.; N], &[&patterns[0][1..]]).is_err());
+    }
+}
@@ -1,8 +1,58 @@
 //! A library for computing with various associative memory models.
 
+// The numerical code in this crate is written with explicit index loops, which
+// keeps it close to the equations in the referenced papers.
+#![allow(clippy::needless_range_loop)]
+
+pub mod bam;
+pub mod basin;
+pub mod bitstate;
 pub mod classic;
 pub mod dmatrix;
+pub mod error;
+pub mod format;
+pub mod image;
+pub mod landscape;
+pub mod metrics;
 pub mod modern;
+pub mod npy;
+pub mod optim;
 pub mod pcn;
+pub mod quantize;
+pub mod scalar;
+pub mod seed;
+pub mod sequence;
+pub mod simd;
 pub mod smatrix;
+pub mod spmatrix;
 pub mod state;
+
+/// Matrices with fewer elements than this are processed serially even with the
+/// `parallel` feature, as the threading overhead would dominate.
+#[cfg(feature = "parallel")]
+pub(crate) const PARALLEL_THRESHOLD: usize = 1 << 14;
+
+/// `Send` with the `parallel` feature, and implemented by every type without
+/// it, so that the element types of the matrices only need to be `Send` when
+/// they are actually shared between threads.
+#[cfg(feature = "parallel")]
+pub trait MaybeSend: Send {}
+#[cfg(feature = "parallel")]
+impl<T: Send + ?Sized> MaybeSend for T {}
+
+#[cfg(not(feature = "parallel"))]
+pub trait MaybeSend {}
+#[cfg(not(feature = "parallel"))]
+impl<T: ?Sized> MaybeSend for T {}
+
+/// `Sync` with the `parallel` feature, and implemented by every type without
+/// it. See `MaybeSend`.
+#[cfg(feature = "parallel")]
+pub trait MaybeSync: Sync {}
+#[cfg(feature = "parallel")]
+impl<T: Sync + ?Sized> MaybeSync for T {}
+
+#[cfg(not(feature = "parallel"))]
+pub trait MaybeSync {}
+#[cfg(not(feature = "parallel"))]
+impl<T: ?Sized> MaybeSync for T {}
@@ -0,0 +1,423 @@
+//! Measures of retrieval quality. States and patterns are compared by sign
+//! (values `>= 0` count as 1, as in the update rules) for the overlap and the
+//! Hamming distance, and by value for the MSE and cosine similarity of
+//! continuous states.
+//!
+//! The `masked_` variants only count the positions where the mask is `true`.
+//! With the masks returned by the corruption methods of `State` they measure
+//! retrieval on the corrupted and (with the negated mask) intact positions
+//! separately.
+//!
+//! Each function also has a `try_` variant that checks the sizes of (and the
+//! values in) its arguments, and for `classify` the target index, instead of
+//! truncating or panicking.
+
+use crate::error::{check_finite, check_index, check_len, check_vec, HopfieldError};
+use crate::scalar::Scalar;
+
+fn sign<T: Scalar>(v: T) -> f64 {
+    if v >= T::ZERO {
+        1.
+    } else {
+        -1.
+    }
+}
+
+/// The Mattis overlap `m = 1/N sum_i sign(p_i) sign(s_i)`: 1 when the state
+/// equals the pattern, -1 for the reversed pattern and around `1/sqrt(N)` in
+/// magnitude for unrelated random states.
+pub fn overlap<T: Scalar>(pattern: &[T], state: &[T]) -> f64 {
+    debug_assert_eq!(pattern.len(), state.len());
+
+    if pattern.is_empty() {
+        return 0.;
+    }
+
+    let acc: f64 = (0..pattern.len())
+        .map(|i| sign(pattern[i]) * sign(state[i]))
+        .sum();
+
+    acc / pattern.len() as f64
+}
+
+/// The overlap counting only the positions where `mask` is `true`.
+pub fn masked_overlap<T: Scalar>(pattern: &[T], state: &[T], mask: &[bool]) -> f64 {
+    debug_assert_eq!(pattern.len(), state.len());
+    debug_assert_eq!(pattern.len(), mask.len());
+
+    let mut acc = 0.;
+    let mut count = 0;
+
+    for i in 0..pattern.len() {
+        if mask[i] {
+            acc += sign(pattern[i]) * sign(state[i]);
+            count += 1;
+        }
+    }
+
+    if count == 0 {
+        0.
+    } else {
+        acc / count as f64
+    }
+}
+
+/// The overlap of `state` with every pattern in `patterns`.
+pub fn overlaps<T: Scalar, P: AsRef<[T]>>(patterns: &[P], state: &[T]) -> Vec<f64> {
+    patterns
+        .iter()
+        .map(|p| overlap(p.as_ref(), state))
+        .collect()
+}
+
+/// The fraction of positions where the signs of `a` and `b` differ, equal to
+/// `(1 - m) / 2` for the overlap `m`, or 0 for empty states.
+pub fn hamming<T: Scalar>(a: &[T], b: &[T]) -> f64 {
+    if a.is_empty() {
+        return 0.;
+    }
+
+    (1. - overlap(a, b)) / 2.
+}
+
+/// The fraction of the positions where `mask` is `true` at which the signs of
+/// `a` and `b` differ, or 0 if `mask` is `false` everywhere.
+pub fn masked_hamming<T: Scalar>(a: &[T], b: &[T], mask: &[bool]) -> f64 {
+    if !mask.contains(&true) {
+        return 0.;
+    }
+
+    (1. - masked_overlap(a, b, mask)) / 2.
+}
+
+/// Mean squared error between two continuous states.
+pub fn mse<T: Scalar>(a: &[T], b: &[T]) -> f64 {
+    debug_assert_eq!(a.len(), b.len());
+
+    if a.is_empty() {
+        return 0.;
+    }
+
+    let acc: f64 = (0..a.len())
+        .map(|i| {
+            let d = a[i].to_f64() - b[i].to_f64();
+            d * d
+        })
+        .sum();
+
+    acc / a.len() as f64
+}
+
+/// Cosine similarity between two continuous states, or 0 if either is zero.
+pub fn cosine<T: Scalar>(a: &[T], b: &[T]) -> f64 {
+    debug_assert_eq!(a.len(), b.len());
+
+    let (mut ab, mut aa, mut bb) = (0., 0., 0.);
+
+    for i in 0..a.len() {
+        let (x, y) = (a[i].to_f64(), b[i].to_f64());
+
+        ab += x * y;
+        aa += x * x;
+        bb += y * y;
+    }
+
+    if aa == 0. || bb == 0. {
+        0.
+    } else {
+        ab / (aa * bb).sqrt()
+    }
+}
+
+/// The index and overlap of the stored pattern with the largest overlap with
+/// `state`, or `None` if there are no patterns.
+pub fn best_match<T: Scalar, P: AsRef<[T]>>(patterns: &[P], state: &[T]) -> Option<(usize, f64)> {
+    overlaps(patterns, state)
+        .into_iter()
+        .enumerate()
+        .fold(None, |best, (k, m)| match best {
+            Some((_, b)) if b >= m => best,
+            _ => Some((k, m)),
+        })
+}
+
+// Check that `a` and `b` have the same size and only finite values.
+fn check_pair<T: Scalar>(a: &[T], b: &[T]) -> Result<(), HopfieldError> {
+    check_finite("a", a)?;
+    check_vec("b", a.len(), b)
+}
+
+// Like `check_pair`, and that `mask` has the same size too.
+fn check_masked<T: Scalar>(a: &[T], b: &[T], mask: &[bool]) -> Result<(), HopfieldError> {
+    check_pair(a, b)?;
+    check_len("mask", a.len(), mask.len())
+}
+
+/// Like `overlap`, but checks the sizes of and values in both states.
+pub fn try_overlap<T: Scalar>(pattern: &[T], state: &[T]) -> Result<f64, HopfieldError> {
+    check_pair(pattern, state)?;
+
+    Ok(overlap(pattern, state))
+}
+
+/// Like `masked_overlap`, but checks the sizes of both states and the mask.
+pub fn try_masked_overlap<T: Scalar>(
+    pattern: &[T],
+    state: &[T],
+    mask: &[bool],
+) -> Result<f64, HopfieldError> {
+    check_masked(pattern, state, mask)?;
+
+    Ok(masked_overlap(pattern, state, mask))
+}
+
+/// Like `overlaps`, but checks that every pattern has the size of `state`.
+pub fn try_overlaps<T: Scalar, P: AsRef<[T]>>(
+    patterns: &[P],
+    state: &[T],
+) -> Result<Vec<f64>, HopfieldError> {
+    for p in patterns {
+        check_pair(state, p.as_ref())?;
+    }
+
+    Ok(overlaps(patterns, state))
+}
+
+/// Like `hamming`, but checks the sizes of and values in both states.
+pub fn try_hamming<T: Scalar>(a: &[T], b: &[T]) -> Result<f64, HopfieldError> {
+    check_pair(a, b)?;
+
+    Ok(hamming(a, b))
+}
+
+/// Like `masked_hamming`, but checks the sizes of both states and the mask.
+pub fn try_masked_hamming<T: Scalar>(
+    a: &[T],
+    b: &[T],
+    mask: &[bool],
+) -> Result<f64, HopfieldError> {
+    check_masked(a, b, mask)?;
+
+    Ok(masked_hamming(a, b, mask))
+}
+
+/// Like `mse`, but checks the sizes of and values in both states.
+pub fn try_mse<T: Scalar>(a: &[T], b: &[T]) -> Result<f64, HopfieldError> {
+    check_pair(a, b)?;
+
+    Ok(mse(a, b))
+}
+
+/// Like `cosine`, but checks the sizes of and values in both states.
+pub fn try_cosine<T: Scalar>(a: &[T], b: &[T]) -> Result<f64, HopfieldError> {
+    check_pair(a, b)?;
+
+    Ok(cosine(a, b))
+}
+
+/// Like `best_match`, but checks that every pattern has the size of `state`.
+pub fn try_best_match<T: Scalar, P: AsRef<[T]>>(
+    patterns: &[P],
+    state: &[T],
+) -> Result<Option<(usize, f64)>, HopfieldError> {
+    for p in patterns {
+        check_pair(state, p.as_ref())?;
+    }
+
+    Ok(best_match(patterns, state))
+}
+
+/// What a network converged to when asked to recall a target pattern.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Outcome {
+    /// The target pattern.
+    Correct,
+    /// The reversed target pattern, also an attractor of classic networks.
+    Reversed,
+    /// Another stored pattern (or its reverse).
+    WrongPattern(usize),
+    /// A spurious state with a significant overlap with several patterns.
+    Mixture,
+    /// A spurious state unrelated to any of the patterns.
+    SpinGlass,
+}
+
+/// Overlap levels used by `classify`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Thresholds {
+    /// The overlap from which a pattern counts as retrieved.
+    pub recall: f64,
+    /// The overlap from which a pattern counts as part of a mixture. This
+    /// should be well above the `1/sqrt(N)` overlaps of random states.
+    pub mixture: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            recall: 0.9,
+            mixture: 0.25,
+        }
+    }
+}
+
+/// Classify the final `state` of an attempt to recall pattern `target`.
+pub fn classify<T: Scalar, P: AsRef<[T]>>(
+    patterns: &[P],
+    target: usize,
+    state: &[T],
+    thresholds: &Thresholds,
+) -> Outcome {
+    let m = overlaps(patterns, state);
+
+    if m[target] >= thresholds.recall {
+        return Outcome::Correct;
+    }
+    if m[target] <= -thresholds.recall {
+        return Outcome::Reversed;
+    }
+
+    let other = (0..m.len())
+        .filter(|k| *k != target)
+        .max_by(|a, b| m[*a].abs().total_cmp(&m[*b].abs()));
+
+    if let Some(k) = other {
+        if m[k].abs() >= thresholds.recall {
+            return Outcome::WrongPattern(k);
+        }
+    }
+
+    if m.iter().filter(|v| v.abs() >= thresholds.mixture).count() >= 2 {
+        Outcome::Mixture
+    } else {
+        Outcome::SpinGlass
+    }
+}
+
+/// Like `classify`, but checks `target` and that every pattern has the size of
+/// `state`.
+pub fn try_classify<T: Scalar, P: AsRef<[T]>>(
+    patterns: &[P],
+    target: usize,
+    state: &[T],
+    thresholds: &Thresholds,
+) -> Result<Outcome, HopfieldError> {
+    check_index("target", target, patterns.len())?;
+    for p in patterns {
+        check_pair(state, p.as_ref())?;
+    }
+
+    Ok(classify(patterns, target, state, thresholds))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seed::Seed;
+    use rand::Rng;
+
+    fn random_patterns(count: usize, n: usize, rng: &mut impl Rng) -> Vec<Vec<f64>> {
+        (0..count)
+            .map(|_| {
+                (0..n)
+                    .map(|_| if rng.random_bool(0.5) { 1. } else { -1. })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn distances() {
+        let a = vec![1., -1., 1., -1.];
+        let b = vec![1., 1., 1., -0.5];
+
+        assert_eq!(overlap(&a, &a), 1.);
+        assert_eq!(overlap(&a, &b), 0.5);
+        assert_eq!(hamming(&a, &b), 0.25);
+        assert_eq!(masked_hamming(&a, &b, &[false, true, true, false]), 0.5);
+        assert_eq!(masked_overlap(&a, &b, &[true, false, true, true]), 1.);
+        assert_eq!(mse(&a, &b), 1.0625);
+        assert!((cosine(&a, &[2., -2., 2., -2.]) - 1.).abs() < 1e-12);
+        assert_eq!(cosine(&a, &[0.; 4]), 0.);
+        assert_eq!(hamming::<f64>(&[], &[]), 0.);
+        assert_eq!(masked_hamming(&a, &b, &[false; 4]), 0.);
+    }
+
+    #[test]
+    fn try_variants_check_sizes() {
+        let a = vec![1., -1., 1., -1.];
+        let b = vec![1., 1., 1., -0.5];
+        let mismatch = Err(HopfieldError::DimensionMismatch {
+            what: "b",
+            expected: 4,
+            found: 3,
+        });
+
+        assert_eq!(try_overlap(&a, &b[..3]), mismatch);
+        assert_eq!(try_hamming(&a, &b[..3]), mismatch);
+        assert_eq!(try_mse(&a, &b[..3]), mismatch);
+        assert_eq!(try_cosine(&a, &b[..3]), mismatch);
+        assert!(try_overlap(&a[..3], &b).is_err());
+        assert!(try_mse(&a, &[1., f64::NAN, 1., 1.]).is_err());
+        assert!(try_masked_overlap(&a, &b, &[true; 3]).is_err());
+        assert!(try_masked_hamming(&a, &b, &[true; 5]).is_err());
+        assert!(try_overlaps(&[&a, &b[..3]], &a).is_err());
+        assert!(try_best_match(&[&b[..3]], &a).is_err());
+        assert_eq!(try_hamming(&a, &b), Ok(0.25));
+        assert_eq!(try_best_match(&[&a, &b], &a), Ok(Some((0, 1.))));
+
+        let thresholds = Thresholds::default();
+
+        assert_eq!(
+            try_classify(&[&a, &b], 2, &a, &thresholds),
+            Err(HopfieldError::InvalidParameter {
+                name: "target",
+                reason: "index out of range",
+            })
+        );
+        assert!(try_classify(&[&a, &b[..3]], 0, &a, &thresholds).is_err());
+        assert_eq!(
+            try_classify(&[&a, &b], 0, &a, &thresholds),
+            Ok(Outcome::Correct)
+        );
+    }
+
+    #[test]
+    fn classifies_outcomes() {
+        const N: usize = 400;
+
+        let mut rng = Seed(1).rng();
+        let patterns = random_patterns(3, N, &mut rng);
+        let thresholds = Thresholds::default();
+        let reversed: Vec<f64> = patterns[0].iter().map(|v| -v).collect();
+        let mixture: Vec<f64> = (0..N)
+            .map(|i| sign(patterns[0][i] + patterns[1][i] + patterns[2][i]))
+            .collect();
+        let random = &random_patterns(1, N, &mut rng)[0];
+
+        assert_eq!(best_match(&patterns, &patterns[2]), Some((2, 1.)));
+        assert_eq!(best_match::<f64, Vec<f64>>(&[], &patterns[2]), None);
+        assert_eq!(
+            classify(&patterns, 0, &patterns[0], &thresholds),
+            Outcome::Correct
+        );
+        assert_eq!(
+            classify(&patterns, 0, &reversed, &thresholds),
+            Outcome::Reversed
+        );
+        assert_eq!(
+            classify(&patterns, 0, &patterns[1], &thresholds),
+            Outcome::WrongPattern(1)
+        );
+        assert_eq!(
+            classify(&patterns, 0, &mixture, &thresholds),
+            Outcome::Mixture
+        );
+        assert_eq!(
+            classify(&patterns, 0, random, &thresholds),
+            Outcome::SpinGlass
+        );
+    }
+}
@@ -1,21 +1,23 @@
-//! Modern Hopfield Network. There is such a thing. Unlike classic Hopfield 
+//! Modern Hopfield Network. There is such a thing. Unlike classic Hopfield
 //! networks, the modern counterpart allows for one shot learning and recall.
 
 use crate::dmatrix::DMatrix;
+use crate::error::{check_len, check_vec, HopfieldError};
+use crate::scalar::Scalar;
 use crate::state::State;
 
-fn activation(state: &mut [f64]) {
+fn activation<T: Scalar>(state: &mut [T]) {
     for i in 0..state.len() {
-        if state[i] >= 0. {
-            state[i] = 1.;
+        if state[i] >= T::ZERO {
+            state[i] = T::ONE;
         } else {
-            state[i] = -1.;
+            state[i] = -T::ONE;
         }
     }
 }
 
-pub fn update_state_sync(mat: &DMatrix<f64>, input_state: &[f64], output_state: &mut [f64]) {
-    let mut temp_vec = vec![0.; mat.rows()];
+pub fn update_state_sync<T: Scalar>(mat: &DMatrix<T>, input_state: &[T], output_state: &mut [T]) {
+    let mut temp_vec = vec![T::ZERO; mat.rows()];
 
     mat.mul_vec(input_state, &mut temp_vec);
     temp_vec.softmax();
@@ -23,29 +25,118 @@ pub fn update_state_sync(mat: &DMatrix<f64>, input_state: &[f64], output_state:
     activation(output_state);
 }
 
-pub fn learn(mat: &mut DMatrix<f64>, pattern: &[f64]) {
+/// Like `update_state_sync`, but checks the sizes of the states, that the
+/// input is finite and that at least one pattern is stored.
+pub fn try_update_state_sync<T: Scalar>(
+    mat: &DMatrix<T>,
+    input_state: &[T],
+    output_state: &mut [T],
+) -> Result<(), HopfieldError> {
+    check_memory(mat)?;
+    check_vec("input state", mat.cols(), input_state)?;
+    check_len("output state", mat.cols(), output_state.len())?;
+
+    update_state_sync(mat, input_state, output_state);
+    Ok(())
+}
+
+/// Sequence recall: with the stored patterns taken as a sequence in row
+/// order, the state moves from a pattern to the next one. The attention each
+/// stored pattern gets is given to its successor instead of to itself. The
+/// last pattern is followed by the first if `cyclic` is set and by itself,
+/// ending the sequence, otherwise.
+pub fn update_sequence_sync<T: Scalar>(
+    mat: &DMatrix<T>,
+    cyclic: bool,
+    input_state: &[T],
+    output_state: &mut [T],
+) {
+    let m = mat.rows();
+    let mut attention = vec![T::ZERO; m];
+    let mut shifted = vec![T::ZERO; m];
+
+    mat.mul_vec(input_state, &mut attention);
+    attention.softmax();
+
+    for k in 0..m {
+        let next = if k + 1 < m {
+            k + 1
+        } else if cyclic {
+            0
+        } else {
+            k
+        };
+
+        shifted[next] += attention[k];
+    }
+
+    mat.trans_mul_vec(&shifted, output_state);
+    activation(output_state);
+}
+
+/// Like `update_sequence_sync`, with the checks of `try_update_state_sync`.
+pub fn try_update_sequence_sync<T: Scalar>(
+    mat: &DMatrix<T>,
+    cyclic: bool,
+    input_state: &[T],
+    output_state: &mut [T],
+) -> Result<(), HopfieldError> {
+    check_memory(mat)?;
+    check_vec("input state", mat.cols(), input_state)?;
+    check_len("output state", mat.cols(), output_state.len())?;
+
+    update_sequence_sync(mat, cyclic, input_state, output_state);
+    Ok(())
+}
+
+fn check_memory<T>(mat: &DMatrix<T>) -> Result<(), HopfieldError> {
+    if mat.rows() == 0 {
+        return Err(HopfieldError::EmptyMemory);
+    }
+
+    Ok(())
+}
+
+pub fn learn<T: Scalar>(mat: &mut DMatrix<T>, pattern: &[T]) {
     debug_assert_eq!(mat.cols(), pattern.len());
 
     mat.add_row(pattern);
 }
 
-fn lse(beta: f64, x: &[f64]) -> f64 {
-    let mut acc = 0.;
+/// Like `learn`, but checks the size of and values in `pattern`.
+pub fn try_learn<T: Scalar>(mat: &mut DMatrix<T>, pattern: &[T]) -> Result<(), HopfieldError> {
+    check_vec("pattern", mat.cols(), pattern)?;
+
+    learn(mat, pattern);
+    Ok(())
+}
+
+fn lse<T: Scalar>(beta: T, x: &[T]) -> T {
+    let mut acc = T::ZERO;
 
     for v in x {
-        acc += (beta * v).exp();
+        acc += (beta * *v).exp();
     }
 
     acc.ln() / beta
 }
 
-pub fn energy(mat: &DMatrix<f64>, pattern: &[f64]) -> f64 {
+pub fn energy<T: Scalar>(mat: &DMatrix<T>, pattern: &[T]) -> T {
     debug_assert_eq!(mat.cols(), pattern.len());
 
-    let mut temp_vec = vec![0.; mat.rows()];
-    mat.trans_mul_vec(pattern, &mut temp_vec);
+    let mut temp_vec = vec![T::ZERO; mat.rows()];
+    mat.mul_vec(pattern, &mut temp_vec);
+
+    -lse(T::ONE, &temp_vec).exp()
+}
+
+/// Like `energy`, but checks `pattern` and that at least one pattern is
+/// stored.
+pub fn try_energy<T: Scalar>(mat: &DMatrix<T>, pattern: &[T]) -> Result<T, HopfieldError> {
+    check_memory(mat)?;
+    check_vec("pattern", mat.cols(), pattern)?;
 
-    -lse(1., &temp_vec).exp()
+    Ok(energy(mat, pattern))
 }
 
 #[cfg(test)]
@@ -72,6 +163,20 @@ mod test {
         }
     }
 
+    #[test]
+    fn can_recall_pattern_f32() {
+        let mut m = DMatrix::new(1, 8, 0.0_f32);
+        let p: Vec<f32> = vec![1., 1., -1., -1., 1., 1., -1., -1.];
+        let a: Vec<f32> = vec![1., 0., -1., 0., 0., 1., 0., 0.];
+        let mut output: Vec<f32> = vec![0.; 8];
+
+        learn(&mut m, &p);
+
+        update_state_sync(&m, &a, &mut output);
+
+        assert_eq!(output, p);
+    }
+
     #[test]
     fn can_recall_pattern() {
         let mut m = DMatrix::new(1, 8, 0.);
@@ -88,4 +193,83 @@ mod test {
             assert_eq!(output[i], p[i]);
         }
     }
+
+    #[test]
+    fn energy_of_non_square_memory() {
+        let mut m = DMatrix::new(0, 8, 0.);
+        let p1: Vec<f64> = vec![1., 1., -1., -1., 1., 1., -1., -1.];
+        let p2: Vec<f64> = vec![1., -1., 1., -1., 1., -1., 1., -1.];
+
+        learn(&mut m, &p1);
+        learn(&mut m, &p2);
+
+        // The energy is minus the sum of the exponentiated similarities with
+        // the stored patterns, here 8 and 0.
+        assert!((energy(&m, &p1) + 8_f64.exp() + 1.).abs() < 1e-9);
+        assert!(energy(&m, &p1) < energy(&m, &[1.; 8]));
+    }
+
+    #[test]
+    fn try_variants_check_memory_and_shapes() {
+        let mut m = DMatrix::new(0, 4, 0.);
+        let p: Vec<f64> = vec![1., -1., 1., -1.];
+        let mut output = vec![0.; 4];
+
+        assert_eq!(
+            try_update_state_sync(&m, &p, &mut output),
+            Err(HopfieldError::EmptyMemory)
+        );
+        assert_eq!(try_energy(&m, &p), Err(HopfieldError::EmptyMemory));
+        assert!(try_learn(&mut m, &p[1..]).is_err());
+        assert!(try_learn(&mut m, &[1., f64::INFINITY, 1., 1.]).is_err());
+        assert_eq!(m.rows(), 0);
+
+        try_learn(&mut m, &p).unwrap();
+        try_learn(&mut m, &[1., 1., 1., 1.]).unwrap();
+
+        assert!(try_update_state_sync(&m, &p, &mut output[..2]).is_err());
+        try_update_state_sync(&m, &p, &mut output).unwrap();
+        assert_eq!(output, p);
+        assert!(try_energy(&m, &p).unwrap() < try_energy(&m, &[1., 1., -1., -1.]).unwrap());
+    }
+
+    #[test]
+    fn replays_sequence() {
+        const N: usize = 32;
+
+        let sequence: Vec<Vec<f64>> = (0..5)
+            .map(|k| {
+                (0..N)
+                    .map(|i| {
+                        if (i * (2 * k + 3) + k) % 7 < 3 {
+                            1.
+                        } else {
+                            -1.
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut m = DMatrix::new(0, N, 0.);
+        for p in &sequence {
+            learn(&mut m, p);
+        }
+
+        let mut state = sequence[0].clone();
+        state[0] = -state[0];
+        let mut next = vec![0.; N];
+
+        for k in 1..5 {
+            update_sequence_sync(&m, false, &state, &mut next);
+            std::mem::swap(&mut state, &mut next);
+            assert_eq!(state, sequence[k]);
+        }
+
+        // The end of the sequence is a fixed point unless it is cyclic.
+        update_sequence_sync(&m, false, &state, &mut next);
+        assert_eq!(next, sequence[4]);
+        update_sequence_sync(&m, true, &state, &mut next);
+        assert_eq!(next, sequence[0]);
+        assert!(try_update_sequence_sync(&m, true, &state, &mut next[1..]).is_err());
+    }
 }
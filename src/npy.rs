@@ -0,0 +1,868 @@
+//! Reading and writing NumPy `.npy` files and `.npz` archives, so weights and
+//! patterns can be inspected with `numpy.load`. Matrices are stored as 2D
+//! arrays, an `SMatrix` as its dense (square) expansion and a list of
+//! patterns as a 2D array with one pattern per row.
+//!
+//! Reading accepts format versions 1 to 3, little- and big-endian `f2`, `f4`
+//! and `f8` data (converted to the requested scalar type) and C or Fortran
+//! order. Archives must be uncompressed, as written by `numpy.savez`;
+//! `numpy.savez_compressed` archives are rejected.
+
+use crate::dmatrix::DMatrix;
+use crate::format::{crc32, FormatScalar, ScalarType};
+use crate::scalar::Scalar;
+use crate::smatrix::SMatrix;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+#[derive(Debug)]
+pub enum NpyError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u8),
+    /// The array header could not be parsed.
+    BadHeader(String),
+    UnsupportedDtype(String),
+    /// The array does not have the shape needed for the requested type.
+    ShapeMismatch(String),
+    /// The `.npz` archive is malformed or uses unsupported zip features.
+    BadArchive(String),
+    MissingArray(String),
+}
+
+impl Display for NpyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            NpyError::Io(e) => write!(f, "i/o error: {}", e),
+            NpyError::BadMagic => write!(f, "not a .npy file"),
+            NpyError::UnsupportedVersion(v) => write!(f, "unsupported .npy version {}", v),
+            NpyError::BadHeader(s) => write!(f, "bad .npy header: {}", s),
+            NpyError::UnsupportedDtype(s) => write!(f, "unsupported dtype {}", s),
+            NpyError::ShapeMismatch(s) => write!(f, "shape mismatch: {}", s),
+            NpyError::BadArchive(s) => write!(f, "bad .npz archive: {}", s),
+            NpyError::MissingArray(s) => write!(f, "no array named {} in archive", s),
+        }
+    }
+}
+
+impl std::error::Error for NpyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NpyError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NpyError {
+    fn from(e: io::Error) -> Self {
+        NpyError::Io(e)
+    }
+}
+
+fn descr<T: FormatScalar>() -> &'static str {
+    match T::TYPE {
+        ScalarType::F16 => "<f2",
+        ScalarType::F32 => "<f4",
+        ScalarType::F64 => "<f8",
+    }
+}
+
+/// Write a C-ordered array with the given shape.
+pub fn write_array<T: FormatScalar, W: Write>(
+    writer: &mut W,
+    shape: &[usize],
+    data: &[T],
+) -> Result<(), NpyError> {
+    if shape.iter().product::<usize>() != data.len() {
+        return Err(NpyError::ShapeMismatch(format!(
+            "shape {:?} does not hold {} values",
+            shape,
+            data.len()
+        )));
+    }
+
+    let dims: Vec<String> = shape.iter().map(|d| d.to_string()).collect();
+    let shape = match dims.len() {
+        1 => format!("({},)", dims[0]),
+        _ => format!("({})", dims.join(", ")),
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        descr::<T>(),
+        shape
+    );
+
+    // Pad with spaces and a newline so the data starts at a multiple of 64.
+    let unpadded = MAGIC.len() + 4 + header.len() + 1;
+    header.push_str(&" ".repeat(unpadded.next_multiple_of(64) - unpadded));
+    header.push('\n');
+
+    let len = u16::try_from(header.len())
+        .map_err(|_| NpyError::BadHeader("header is too long".to_string()))?;
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+
+    let mut buf = Vec::with_capacity(data.len() * T::SIZE);
+    for v in data {
+        v.write_le(&mut buf);
+    }
+    writer.write_all(&buf)?;
+
+    Ok(())
+}
+
+/// An array read from a `.npy` file, converted to `T` and C order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Array<T> {
+    pub shape: Vec<usize>,
+    pub data: Vec<T>,
+}
+
+// The fields of the header dictionary.
+struct Header {
+    descr: String,
+    fortran_order: bool,
+    shape: Vec<usize>,
+}
+
+// Find the value after `'key':` in the header dictionary.
+fn header_value<'a>(header: &'a str, key: &str) -> Result<&'a str, NpyError> {
+    let pattern = format!("'{}':", key);
+    let start = header
+        .find(&pattern)
+        .ok_or_else(|| NpyError::BadHeader(format!("missing {}", key)))?;
+
+    Ok(header[start + pattern.len()..].trim_start())
+}
+
+fn parse_header(header: &str) -> Result<Header, NpyError> {
+    let descr = header_value(header, "descr")?;
+    let descr = descr
+        .strip_prefix('\'')
+        .and_then(|d| d.split('\'').next())
+        .ok_or_else(|| NpyError::BadHeader("descr is not a string".to_string()))?;
+
+    let fortran_order = header_value(header, "fortran_order")?.starts_with("True");
+
+    let shape = header_value(header, "shape")?;
+    let shape = shape
+        .strip_prefix('(')
+        .and_then(|s| s.split(')').next())
+        .ok_or_else(|| NpyError::BadHeader("shape is not a tuple".to_string()))?;
+    let shape = shape
+        .split(',')
+        .map(|d| d.trim())
+        .filter(|d| !d.is_empty())
+        .map(|d| {
+            d.parse()
+                .map_err(|_| NpyError::BadHeader(format!("bad dimension {}", d)))
+        })
+        .collect::<Result<Vec<usize>, _>>()?;
+
+    Ok(Header {
+        descr: descr.to_string(),
+        fortran_order,
+        shape,
+    })
+}
+
+fn decode<T: Scalar>(descr: &str, bytes: &[u8]) -> Result<Vec<T>, NpyError> {
+    let (big_endian, kind) = match (descr.get(..1), descr.get(1..)) {
+        (Some("<" | "|" | "="), Some(k)) => (false, k),
+        (Some(">"), Some(k)) => (true, k),
+        _ => return Err(NpyError::UnsupportedDtype(descr.to_string())),
+    };
+    let size = match kind {
+        "f2" => 2,
+        "f4" => 4,
+        "f8" => 8,
+        _ => return Err(NpyError::UnsupportedDtype(descr.to_string())),
+    };
+
+    if !bytes.len().is_multiple_of(size) {
+        return Err(NpyError::ShapeMismatch(format!(
+            "{} bytes are not a whole number of {} values",
+            bytes.len(),
+            descr
+        )));
+    }
+
+    Ok(bytes
+        .chunks_exact(size)
+        .map(|c| {
+            let mut b = [0; 8];
+            b[..size].copy_from_slice(c);
+            if big_endian {
+                b[..size].reverse();
+            }
+
+            let v = match size {
+                2 => f16_to_f64(u16::from_le_bytes([b[0], b[1]])),
+                4 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                _ => f64::from_le_bytes(b),
+            };
+
+            T::from_f64(v)
+        })
+        .collect())
+}
+
+// Half precision values are widened by hand so reading them does not need
+// the `half` feature.
+fn f16_to_f64(bits: u16) -> f64 {
+    let sign = if bits >> 15 == 1 { -1. } else { 1. };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let fraction = (bits & 0x3ff) as f64;
+
+    sign * match exponent {
+        0 => fraction * 2f64.powi(-24),
+        0x1f if fraction == 0. => f64::INFINITY,
+        0x1f => f64::NAN,
+        _ => (1. + fraction / 1024.) * 2f64.powi(exponent - 15),
+    }
+}
+
+/// Read an array, converting the values to `T`. The array must take up the
+/// rest of `reader`: data after the values given by the shape is an error.
+pub fn read_array<T: Scalar, R: Read>(reader: &mut R) -> Result<Array<T>, NpyError> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+
+    if &magic[..6] != MAGIC {
+        return Err(NpyError::BadMagic);
+    }
+
+    let header_len = match magic[6] {
+        1 => {
+            let mut b = [0; 2];
+            reader.read_exact(&mut b)?;
+            u16::from_le_bytes(b) as usize
+        }
+        2 | 3 => {
+            let mut b = [0; 4];
+            reader.read_exact(&mut b)?;
+            u32::from_le_bytes(b) as usize
+        }
+        v => return Err(NpyError::UnsupportedVersion(v)),
+    };
+
+    let mut header = vec![0; header_len];
+    reader.read_exact(&mut header)?;
+    let header = String::from_utf8(header)
+        .map_err(|_| NpyError::BadHeader("header is not text".to_string()))?;
+    let header = parse_header(&header)?;
+
+    let count = header
+        .shape
+        .iter()
+        .try_fold(1_usize, |acc, d| acc.checked_mul(*d))
+        .ok_or_else(|| NpyError::ShapeMismatch("array is too large".to_string()))?;
+
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    let mut data = decode(&header.descr, &bytes)?;
+    if data.len() != count {
+        return Err(NpyError::ShapeMismatch(format!(
+            "shape {:?} needs {} values, found {}",
+            header.shape,
+            count,
+            data.len()
+        )));
+    }
+
+    if header.fortran_order && header.shape.len() == 2 {
+        let (rows, cols) = (header.shape[0], header.shape[1]);
+        let column_major = data.clone();
+
+        for r in 0..rows {
+            for c in 0..cols {
+                data[r * cols + c] = column_major[c * rows + r];
+            }
+        }
+    } else if header.fortran_order && header.shape.len() > 2 {
+        return Err(NpyError::ShapeMismatch(
+            "Fortran order is only supported up to two dimensions".to_string(),
+        ));
+    }
+
+    Ok(Array {
+        shape: header.shape,
+        data,
+    })
+}
+
+// The shape of an array as a matrix: 1D arrays are a single row.
+fn matrix_shape<T>(array: &Array<T>) -> Result<(usize, usize), NpyError> {
+    match array.shape.as_slice() {
+        [n] => Ok((1, *n)),
+        [r, c] => Ok((*r, *c)),
+        s => Err(NpyError::ShapeMismatch(format!(
+            "expected a 1D or 2D array, found shape {:?}",
+            s
+        ))),
+    }
+}
+
+pub fn write_matrix<T: FormatScalar, W: Write>(
+    writer: &mut W,
+    m: &DMatrix<T>,
+) -> Result<(), NpyError> {
+    write_array(writer, &[m.rows(), m.cols()], m.as_slice())
+}
+
+pub fn read_matrix<T: Scalar, R: Read>(reader: &mut R) -> Result<DMatrix<T>, NpyError> {
+    array_to_matrix(read_array(reader)?)
+}
+
+fn array_to_matrix<T>(array: Array<T>) -> Result<DMatrix<T>, NpyError> {
+    let (rows, cols) = matrix_shape(&array)?;
+
+    Ok(DMatrix::from_vec(rows, cols, array.data))
+}
+
+/// Write the dense `rows x rows` expansion of a symmetric matrix.
+pub fn write_smatrix<T: FormatScalar, W: Write>(
+    writer: &mut W,
+    m: &SMatrix<T>,
+) -> Result<(), NpyError> {
+    let n = m.rows();
+    let mut data = Vec::with_capacity(n * n);
+
+    for r in 0..n {
+        for c in 0..n {
+            data.push(m[(r, c)]);
+        }
+    }
+
+    write_array(writer, &[n, n], &data)
+}
+
+/// Read a square matrix into an `SMatrix`. The array must be symmetric.
+pub fn read_smatrix<T: Scalar, R: Read>(reader: &mut R) -> Result<SMatrix<T>, NpyError> {
+    array_to_smatrix(read_array(reader)?)
+}
+
+fn array_to_smatrix<T: Scalar>(array: Array<T>) -> Result<SMatrix<T>, NpyError> {
+    let (rows, cols) = matrix_shape(&array)?;
+
+    if rows != cols {
+        return Err(NpyError::ShapeMismatch(format!(
+            "expected a square matrix, found {}x{}",
+            rows, cols
+        )));
+    }
+
+    let n = rows;
+    let v = &array.data;
+
+    for r in 0..n {
+        for c in (r + 1)..n {
+            if v[r * n + c] != v[c * n + r] {
+                return Err(NpyError::ShapeMismatch(format!(
+                    "matrix is not symmetric at ({}, {})",
+                    r, c
+                )));
+            }
+        }
+    }
+
+    Ok(SMatrix::from_fn2(n, &mut |r, c| v[r * n + c]))
+}
+
+/// Write patterns of equal length as a 2D array, one pattern per row.
+pub fn write_patterns<T: FormatScalar, W: Write>(
+    writer: &mut W,
+    patterns: &[Vec<T>],
+) -> Result<(), NpyError> {
+    let len = patterns.first().map_or(0, |p| p.len());
+
+    if patterns.iter().any(|p| p.len() != len) {
+        return Err(NpyError::ShapeMismatch(
+            "patterns have different lengths".to_string(),
+        ));
+    }
+
+    write_array(writer, &[patterns.len(), len], &patterns.concat())
+}
+
+pub fn read_patterns<T: Scalar, R: Read>(reader: &mut R) -> Result<Vec<Vec<T>>, NpyError> {
+    array_to_patterns(read_array(reader)?)
+}
+
+fn array_to_patterns<T: Scalar>(array: Array<T>) -> Result<Vec<Vec<T>>, NpyError> {
+    let (rows, cols) = matrix_shape(&array)?;
+
+    if cols == 0 {
+        return Ok(vec![Vec::new(); rows]);
+    }
+
+    Ok(array.data.chunks(cols).map(|p| p.to_vec()).collect())
+}
+
+/// Writes an uncompressed `.npz` archive. Each array becomes an entry named
+/// `<name>.npy`, which `numpy.load` makes available as `archive[name]`.
+pub struct NpzWriter<W: Write> {
+    writer: W,
+    offset: u64,
+    entries: Vec<(String, u32, u32, u32)>,
+}
+
+impl<W: Write> NpzWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            offset: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, name: &str, npy: Vec<u8>) -> Result<(), NpyError> {
+        let name = format!("{}.npy", name);
+        let crc = crc32(&npy);
+        let size = u32::try_from(npy.len())
+            .map_err(|_| NpyError::BadArchive("arrays over 4 GiB need zip64".to_string()))?;
+        let offset = u32::try_from(self.offset)
+            .map_err(|_| NpyError::BadArchive("archives over 4 GiB need zip64".to_string()))?;
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&0x0403_4b50_u32.to_le_bytes());
+        header.extend_from_slice(&20_u16.to_le_bytes()); // version needed
+        header.extend_from_slice(&0_u16.to_le_bytes()); // flags
+        header.extend_from_slice(&0_u16.to_le_bytes()); // stored
+        header.extend_from_slice(&0_u16.to_le_bytes()); // time
+        header.extend_from_slice(&0x21_u16.to_le_bytes()); // date: 1980-01-01
+        header.extend_from_slice(&crc.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&0_u16.to_le_bytes()); // extra length
+        header.extend_from_slice(name.as_bytes());
+
+        self.writer.write_all(&header)?;
+        self.writer.write_all(&npy)?;
+
+        self.offset += (header.len() + npy.len()) as u64;
+        self.entries.push((name, crc, size, offset));
+
+        Ok(())
+    }
+
+    pub fn add_array<T: FormatScalar>(
+        &mut self,
+        name: &str,
+        shape: &[usize],
+        data: &[T],
+    ) -> Result<(), NpyError> {
+        let mut npy = Vec::new();
+        write_array(&mut npy, shape, data)?;
+
+        self.add(name, npy)
+    }
+
+    pub fn add_matrix<T: FormatScalar>(
+        &mut self,
+        name: &str,
+        m: &DMatrix<T>,
+    ) -> Result<(), NpyError> {
+        let mut npy = Vec::new();
+        write_matrix(&mut npy, m)?;
+
+        self.add(name, npy)
+    }
+
+    pub fn add_smatrix<T: FormatScalar>(
+        &mut self,
+        name: &str,
+        m: &SMatrix<T>,
+    ) -> Result<(), NpyError> {
+        let mut npy = Vec::new();
+        write_smatrix(&mut npy, m)?;
+
+        self.add(name, npy)
+    }
+
+    pub fn add_patterns<T: FormatScalar>(
+        &mut self,
+        name: &str,
+        patterns: &[Vec<T>],
+    ) -> Result<(), NpyError> {
+        let mut npy = Vec::new();
+        write_patterns(&mut npy, patterns)?;
+
+        self.add(name, npy)
+    }
+
+    /// Write the central directory and return the underlying writer.
+    pub fn finish(mut self) -> Result<W, NpyError> {
+        let mut directory = Vec::new();
+
+        for (name, crc, size, offset) in &self.entries {
+            directory.extend_from_slice(&0x0201_4b50_u32.to_le_bytes());
+            directory.extend_from_slice(&20_u16.to_le_bytes()); // version made by
+            directory.extend_from_slice(&20_u16.to_le_bytes()); // version needed
+            directory.extend_from_slice(&0_u16.to_le_bytes()); // flags
+            directory.extend_from_slice(&0_u16.to_le_bytes()); // stored
+            directory.extend_from_slice(&0_u16.to_le_bytes()); // time
+            directory.extend_from_slice(&0x21_u16.to_le_bytes()); // date
+            directory.extend_from_slice(&crc.to_le_bytes());
+            directory.extend_from_slice(&size.to_le_bytes());
+            directory.extend_from_slice(&size.to_le_bytes());
+            directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            directory.extend_from_slice(&[0; 12]); // extra, comment, disk, attributes
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+
+        let start = u32::try_from(self.offset)
+            .map_err(|_| NpyError::BadArchive("archives over 4 GiB need zip64".to_string()))?;
+        let count = u16::try_from(self.entries.len())
+            .map_err(|_| NpyError::BadArchive("too many arrays".to_string()))?;
+
+        directory.extend_from_slice(&0x0605_4b50_u32.to_le_bytes());
+        directory.extend_from_slice(&[0; 4]); // disk numbers
+        directory.extend_from_slice(&count.to_le_bytes());
+        directory.extend_from_slice(&count.to_le_bytes());
+        directory.extend_from_slice(&((directory.len() - 12) as u32).to_le_bytes());
+        directory.extend_from_slice(&start.to_le_bytes());
+        directory.extend_from_slice(&0_u16.to_le_bytes()); // comment length
+
+        self.writer.write_all(&directory)?;
+
+        Ok(self.writer)
+    }
+}
+
+/// An `.npz` archive read into memory.
+pub struct NpzReader {
+    bytes: Vec<u8>,
+    // Name (without `.npy`), offset and length of each array.
+    entries: Vec<(String, usize, usize)>,
+}
+
+// The `len` bytes at offset `i`, which may come from a corrupt archive.
+fn bytes_at(b: &[u8], i: usize, len: usize) -> Result<&[u8], NpyError> {
+    i.checked_add(len)
+        .and_then(|end| b.get(i..end))
+        .ok_or_else(|| NpyError::BadArchive("unexpected end of archive".to_string()))
+}
+
+fn u16_at(b: &[u8], i: usize) -> Result<u16, NpyError> {
+    bytes_at(b, i, 2).map(|s| u16::from_le_bytes([s[0], s[1]]))
+}
+
+fn u32_at(b: &[u8], i: usize) -> Result<u32, NpyError> {
+    bytes_at(b, i, 4).map(|s| u32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+}
+
+fn u64_at(b: &[u8], i: usize) -> Result<u64, NpyError> {
+    bytes_at(b, i, 8).map(|s| u64::from_le_bytes(s.try_into().unwrap()))
+}
+
+impl NpzReader {
+    pub fn new<R: Read>(mut reader: R) -> Result<Self, NpyError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        // The end of central directory record is the last 22 bytes, unless
+        // the archive has a comment.
+        let end = (0..=bytes.len().saturating_sub(22))
+            .rev()
+            .find(|i| bytes[*i..].starts_with(&0x0605_4b50_u32.to_le_bytes()))
+            .ok_or_else(|| NpyError::BadArchive("no end of central directory".to_string()))?;
+
+        let count = u16_at(&bytes, end + 10)? as usize;
+        let mut p = u32_at(&bytes, end + 16)? as usize;
+        let mut entries = Vec::with_capacity(count);
+
+        for _i in 0..count {
+            if u32_at(&bytes, p)? != 0x0201_4b50 {
+                return Err(NpyError::BadArchive(
+                    "bad central directory entry".to_string(),
+                ));
+            }
+
+            let method = u16_at(&bytes, p + 10)?;
+            let crc = u32_at(&bytes, p + 16)?;
+            let mut size = u32_at(&bytes, p + 20)? as u64;
+            let name_len = u16_at(&bytes, p + 28)? as usize;
+            let extra_len = u16_at(&bytes, p + 30)? as usize;
+            let comment_len = u16_at(&bytes, p + 32)? as usize;
+            let mut offset = u32_at(&bytes, p + 42)? as u64;
+
+            let name = bytes
+                .get(p + 46..p + 46 + name_len)
+                .ok_or_else(|| NpyError::BadArchive("unexpected end of archive".to_string()))?;
+            let name = String::from_utf8_lossy(name).into_owned();
+
+            // Sizes and offsets that do not fit are given in the zip64 extra
+            // field, in this order.
+            let mut e = p + 46 + name_len;
+            while e + 4 <= p + 46 + name_len + extra_len {
+                let id = u16_at(&bytes, e)?;
+                let len = u16_at(&bytes, e + 2)? as usize;
+
+                if id == 1 {
+                    let mut f = e + 4;
+                    if size == 0xffff_ffff {
+                        size = u64_at(&bytes, f)?; // uncompressed
+                        f += 8;
+                    }
+                    if u32_at(&bytes, p + 20)? == 0xffff_ffff {
+                        f += 8; // compressed
+                    }
+                    if offset == 0xffff_ffff {
+                        offset = u64_at(&bytes, f)?;
+                    }
+                }
+
+                e += 4 + len;
+            }
+
+            if method != 0 {
+                return Err(NpyError::BadArchive(format!(
+                    "{} is compressed, only numpy.savez archives are supported",
+                    name
+                )));
+            }
+
+            let out_of_range = || NpyError::BadArchive(format!("{} is out of range", name));
+            let local = usize::try_from(offset).map_err(|_| out_of_range())?;
+            let size = usize::try_from(size).map_err(|_| out_of_range())?;
+            if u32_at(&bytes, local)? != 0x0403_4b50 {
+                return Err(NpyError::BadArchive("bad local file header".to_string()));
+            }
+
+            let local_name_len = u16_at(&bytes, local.checked_add(26).ok_or_else(out_of_range)?)?;
+            let local_extra_len = u16_at(&bytes, local.checked_add(28).ok_or_else(out_of_range)?)?;
+            let start = local
+                .checked_add(30 + local_name_len as usize + local_extra_len as usize)
+                .ok_or_else(out_of_range)?;
+            let data = start
+                .checked_add(size)
+                .and_then(|end| bytes.get(start..end))
+                .ok_or_else(|| NpyError::BadArchive(format!("{} is truncated", name)))?;
+
+            if crc32(data) != crc {
+                return Err(NpyError::BadArchive(format!(
+                    "checksum mismatch in {}",
+                    name
+                )));
+            }
+
+            let name = name.strip_suffix(".npy").unwrap_or(&name).to_string();
+            entries.push((name, start, size));
+
+            p += 46 + name_len + extra_len + comment_len;
+        }
+
+        Ok(Self { bytes, entries })
+    }
+
+    /// The names of the arrays in the archive.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|e| e.0.as_str())
+    }
+
+    pub fn array<T: Scalar>(&self, name: &str) -> Result<Array<T>, NpyError> {
+        let (_, start, len) = self
+            .entries
+            .iter()
+            .find(|e| e.0 == name)
+            .ok_or_else(|| NpyError::MissingArray(name.to_string()))?;
+
+        read_array(&mut &self.bytes[*start..start + len])
+    }
+
+    pub fn matrix<T: Scalar>(&self, name: &str) -> Result<DMatrix<T>, NpyError> {
+        array_to_matrix(self.array(name)?)
+    }
+
+    pub fn smatrix<T: Scalar>(&self, name: &str) -> Result<SMatrix<T>, NpyError> {
+        array_to_smatrix(self.array(name)?)
+    }
+
+    pub fn patterns<T: Scalar>(&self, name: &str) -> Result<Vec<Vec<T>>, NpyError> {
+        array_to_patterns(self.array(name)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn npy_header_is_aligned() {
+        let mut bytes = Vec::new();
+        write_array(&mut bytes, &[3], &[1.0_f32, 2., 3.]).unwrap();
+
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+
+        assert_eq!((10 + header_len) % 64, 0);
+        assert!(header.starts_with("{'descr': '<f4', 'fortran_order': False, 'shape': (3,), }"));
+        assert!(header.ends_with('\n'));
+        assert_eq!(bytes.len(), 10 + header_len + 12);
+    }
+
+    #[test]
+    fn matrices_round_trip() {
+        let mut m = DMatrix::new(2, 3, 0.);
+        m[(1, 2)] = 4.5;
+        let s: SMatrix<f32> = SMatrix::from_fn2(4, &mut |r, c| (r * 4 + c) as f32);
+        let patterns = vec![vec![1., -1., 1.], vec![-1., -1., 1.]];
+
+        let mut bytes = Vec::new();
+        write_matrix(&mut bytes, &m).unwrap();
+        assert_eq!(read_matrix::<f64, _>(&mut bytes.as_slice()).unwrap(), m);
+
+        bytes.clear();
+        write_smatrix(&mut bytes, &s).unwrap();
+        assert_eq!(read_smatrix::<f32, _>(&mut bytes.as_slice()).unwrap(), s);
+        assert_eq!(
+            read_matrix::<f64, _>(&mut bytes.as_slice()).unwrap()[(3, 1)],
+            7.
+        );
+
+        bytes.clear();
+        write_patterns(&mut bytes, &patterns).unwrap();
+        assert_eq!(
+            read_patterns::<f64, _>(&mut bytes.as_slice()).unwrap(),
+            patterns
+        );
+        assert!(read_smatrix::<f64, _>(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn reads_fortran_order_and_big_endian() {
+        let header = "{'descr': '>f8', 'fortran_order': True, 'shape': (2, 3), }";
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        for v in [1., 4., 2., 5., 3., 6.] {
+            bytes.extend_from_slice(&f64::to_be_bytes(v));
+        }
+
+        let m = read_matrix::<f32, _>(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(m.as_slice(), &[1., 2., 3., 4., 5., 6.]);
+        assert!(matches!(
+            read_matrix::<f64, _>(&mut &bytes[1..]),
+            Err(NpyError::BadMagic)
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_descr() {
+        for descr in ["", "\u{e9}8"] {
+            let header = format!(
+                "{{'descr': '{}', 'fortran_order': False, 'shape': (1,), }}",
+                descr
+            );
+            let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+            bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(header.as_bytes());
+            bytes.extend_from_slice(&1_f64.to_le_bytes());
+
+            assert!(matches!(
+                read_array::<f64, _>(&mut bytes.as_slice()),
+                Err(NpyError::UnsupportedDtype(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_trailing_data() {
+        let mut bytes = Vec::new();
+        write_array(&mut bytes, &[2], &[1.0_f64, 2.]).unwrap();
+        bytes.extend_from_slice(&3_f64.to_le_bytes());
+
+        assert!(matches!(
+            read_array::<f64, _>(&mut bytes.as_slice()),
+            Err(NpyError::ShapeMismatch(_))
+        ));
+
+        bytes.truncate(bytes.len() - 4);
+        assert!(matches!(
+            read_array::<f64, _>(&mut bytes.as_slice()),
+            Err(NpyError::ShapeMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_huge_zip64_sizes() {
+        // A local header for an empty, unnamed entry followed by a central
+        // directory entry whose zip64 extra field gives the largest possible
+        // sizes.
+        let mut bytes = 0x0403_4b50_u32.to_le_bytes().to_vec();
+        bytes.resize(30, 0);
+
+        let mut central = 0x0201_4b50_u32.to_le_bytes().to_vec();
+        central.resize(46, 0);
+        central[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+        central[30..32].copy_from_slice(&28_u16.to_le_bytes());
+        central[42..46].copy_from_slice(&u32::MAX.to_le_bytes());
+        central.extend_from_slice(&1_u16.to_le_bytes());
+        central.extend_from_slice(&24_u16.to_le_bytes());
+        central.extend_from_slice(&u64::MAX.to_le_bytes());
+        central.extend_from_slice(&u64::MAX.to_le_bytes());
+        central.extend_from_slice(&0_u64.to_le_bytes());
+        bytes.extend_from_slice(&central);
+
+        let mut end = 0x0605_4b50_u32.to_le_bytes().to_vec();
+        end.resize(22, 0);
+        end[10..12].copy_from_slice(&1_u16.to_le_bytes());
+        end[16..20].copy_from_slice(&30_u32.to_le_bytes());
+        bytes.extend_from_slice(&end);
+
+        assert!(matches!(
+            NpzReader::new(bytes.as_slice()),
+            Err(NpyError::BadArchive(_))
+        ));
+    }
+
+    #[test]
+    fn half_precision_values() {
+        assert_eq!(f16_to_f64(0x3c00), 1.);
+        assert_eq!(f16_to_f64(0xc000), -2.);
+        assert_eq!(f16_to_f64(0x0001), 2f64.powi(-24));
+    }
+
+    #[test]
+    fn npz_round_trip() {
+        let mut m = DMatrix::new(2, 2, 1.);
+        m[(0, 1)] = -3.;
+        let s: SMatrix<f64> = SMatrix::from_fn2(3, &mut |r, c| (r + c) as f64);
+        let patterns = vec![vec![1.0_f32, -1.], vec![-1., 1.]];
+
+        let mut npz = NpzWriter::new(Vec::new());
+        npz.add_matrix("memory", &m).unwrap();
+        npz.add_smatrix("weights", &s).unwrap();
+        npz.add_patterns("patterns", &patterns).unwrap();
+        let bytes = npz.finish().unwrap();
+
+        let npz = NpzReader::new(bytes.as_slice()).unwrap();
+
+        assert_eq!(
+            npz.names().collect::<Vec<_>>(),
+            vec!["memory", "weights", "patterns"]
+        );
+        assert_eq!(npz.matrix::<f64>("memory").unwrap(), m);
+        assert_eq!(npz.smatrix::<f64>("weights").unwrap(), s);
+        assert_eq!(npz.patterns::<f32>("patterns").unwrap(), patterns);
+        assert!(matches!(
+            npz.matrix::<f64>("bias"),
+            Err(NpyError::MissingArray(_))
+        ));
+
+        let mut corrupt = bytes.clone();
+        corrupt[120] ^= 0xff;
+        assert!(NpzReader::new(corrupt.as_slice()).is_err());
+    }
+}
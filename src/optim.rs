@@ -0,0 +1,311 @@
+//! Optimisers and learning rate schedules for gradient based learning. An
+//! optimiser instance owns the state for a single block of parameters (eg. one
+//! weight matrix), so a model keeps one instance alongside each of its
+//! parameter blocks. Gradients follow the usual convention: parameters are
+//! moved in the direction of `-grads`.
+
+use crate::error::{check_non_negative, check_rate, check_unit, HopfieldError};
+use crate::scalar::Scalar;
+
+pub trait Optimizer<T: Scalar = f64> {
+    fn step(&mut self, params: &mut [T], grads: &[T], rate: T);
+}
+
+/// Plain stochastic gradient descent.
+#[derive(Clone, Debug, Default)]
+pub struct Sgd;
+
+impl<T: Scalar> Optimizer<T> for Sgd {
+    fn step(&mut self, params: &mut [T], grads: &[T], rate: T) {
+        debug_assert_eq!(params.len(), grads.len());
+
+        for i in 0..params.len() {
+            params[i] -= rate * grads[i];
+        }
+    }
+}
+
+/// Stochastic gradient descent with (heavy ball) momentum.
+#[derive(Clone, Debug)]
+pub struct Momentum<T = f64> {
+    momentum: T,
+    velocity: Vec<T>,
+}
+
+impl<T: Scalar> Momentum<T> {
+    pub fn new(momentum: T) -> Self {
+        debug_assert!(momentum >= T::ZERO && momentum < T::ONE);
+
+        Self {
+            momentum,
+            velocity: Vec::new(),
+        }
+    }
+
+    /// Like `new`, but returns an error unless `0 <= momentum < 1`.
+    pub fn try_new(momentum: T) -> Result<Self, HopfieldError> {
+        check_unit("momentum", momentum)?;
+
+        Ok(Self::new(momentum))
+    }
+}
+
+impl<T: Scalar> Optimizer<T> for Momentum<T> {
+    fn step(&mut self, params: &mut [T], grads: &[T], rate: T) {
+        debug_assert_eq!(params.len(), grads.len());
+
+        self.velocity.resize(params.len(), T::ZERO);
+
+        for i in 0..params.len() {
+            self.velocity[i] = self.momentum * self.velocity[i] + grads[i];
+            params[i] -= rate * self.velocity[i];
+        }
+    }
+}
+
+/// The Adam optimiser of Kingma and Ba with bias corrected moment estimates.
+#[derive(Clone, Debug)]
+pub struct Adam<T = f64> {
+    beta1: T,
+    beta2: T,
+    epsilon: T,
+    m: Vec<T>,
+    v: Vec<T>,
+    t: i32,
+}
+
+impl<T: Scalar> Adam<T> {
+    pub fn new(beta1: T, beta2: T, epsilon: T) -> Self {
+        debug_assert!(beta1 >= T::ZERO && beta1 < T::ONE);
+        debug_assert!(beta2 >= T::ZERO && beta2 < T::ONE);
+        debug_assert!(epsilon > T::ZERO);
+
+        Self {
+            beta1,
+            beta2,
+            epsilon,
+            m: Vec::new(),
+            v: Vec::new(),
+            t: 0,
+        }
+    }
+
+    /// Like `new`, but returns an error unless both betas are in `[0, 1)` and
+    /// `epsilon` is positive.
+    pub fn try_new(beta1: T, beta2: T, epsilon: T) -> Result<Self, HopfieldError> {
+        check_unit("beta1", beta1)?;
+        check_unit("beta2", beta2)?;
+        check_rate("epsilon", epsilon)?;
+
+        Ok(Self::new(beta1, beta2, epsilon))
+    }
+}
+
+impl<T: Scalar> Default for Adam<T> {
+    fn default() -> Self {
+        Self::new(T::from_f64(0.9), T::from_f64(0.999), T::from_f64(1e-8))
+    }
+}
+
+impl<T: Scalar> Optimizer<T> for Adam<T> {
+    fn step(&mut self, params: &mut [T], grads: &[T], rate: T) {
+        debug_assert_eq!(params.len(), grads.len());
+
+        self.m.resize(params.len(), T::ZERO);
+        self.v.resize(params.len(), T::ZERO);
+        self.t = self.t.saturating_add(1);
+
+        let c1 = T::ONE - self.beta1.powi(self.t);
+        let c2 = T::ONE - self.beta2.powi(self.t);
+
+        for i in 0..params.len() {
+            let g = grads[i];
+
+            self.m[i] = self.beta1 * self.m[i] + (T::ONE - self.beta1) * g;
+            self.v[i] = self.beta2 * self.v[i] + (T::ONE - self.beta2) * g * g;
+
+            let m_hat = self.m[i] / c1;
+            let v_hat = self.v[i] / c2;
+
+            params[i] -= rate * m_hat / (v_hat.sqrt() + self.epsilon);
+        }
+    }
+}
+
+/// Decoupled weight decay (as in AdamW) on top of another optimiser. The
+/// parameters shrink towards zero independently of the gradient.
+#[derive(Clone, Debug)]
+pub struct WeightDecay<O, T = f64> {
+    inner: O,
+    decay: T,
+}
+
+impl<T: Scalar, O: Optimizer<T>> WeightDecay<O, T> {
+    pub fn new(inner: O, decay: T) -> Self {
+        debug_assert!(decay >= T::ZERO);
+
+        Self { inner, decay }
+    }
+
+    /// Like `new`, but returns an error if `decay` is negative or not finite.
+    pub fn try_new(inner: O, decay: T) -> Result<Self, HopfieldError> {
+        check_non_negative("decay", decay)?;
+
+        Ok(Self::new(inner, decay))
+    }
+}
+
+impl<T: Scalar, O: Optimizer<T>> Optimizer<T> for WeightDecay<O, T> {
+    fn step(&mut self, params: &mut [T], grads: &[T], rate: T) {
+        for i in 0..params.len() {
+            params[i] -= rate * self.decay * params[i];
+        }
+
+        self.inner.step(params, grads, rate);
+    }
+}
+
+/// Learning rate as a function of the number of learning steps taken. Rates
+/// are given as `f64` and converted to the scalar type of the model.
+pub trait Schedule {
+    fn rate(&self, step: usize) -> f64;
+}
+
+#[derive(Clone, Debug)]
+pub struct Constant(pub f64);
+
+impl Schedule for Constant {
+    fn rate(&self, _step: usize) -> f64 {
+        self.0
+    }
+}
+
+/// Multiply the rate by `factor` every `every` steps.
+#[derive(Clone, Debug)]
+pub struct StepDecay {
+    pub rate: f64,
+    pub factor: f64,
+    pub every: usize,
+}
+
+impl Schedule for StepDecay {
+    fn rate(&self, step: usize) -> f64 {
+        let n = step / self.every.max(1);
+
+        self.rate * self.factor.powi(n.min(i32::MAX as usize) as i32)
+    }
+}
+
+/// Multiply the rate by `gamma` on every step.
+#[derive(Clone, Debug)]
+pub struct ExponentialDecay {
+    pub rate: f64,
+    pub gamma: f64,
+}
+
+impl Schedule for ExponentialDecay {
+    fn rate(&self, step: usize) -> f64 {
+        self.rate * self.gamma.powf(step as f64)
+    }
+}
+
+/// Cosine annealing from `max` to `min` over `period` steps, restarting
+/// after each period.
+#[derive(Clone, Debug)]
+pub struct CosineAnnealing {
+    pub max: f64,
+    pub min: f64,
+    pub period: usize,
+}
+
+impl Schedule for CosineAnnealing {
+    fn rate(&self, step: usize) -> f64 {
+        let period = self.period.max(1);
+        let x = (step % period) as f64 / period as f64;
+
+        self.min + 0.5 * (self.max - self.min) * (1. + (std::f64::consts::PI * x).cos())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn minimize<O: Optimizer>(opt: &mut O, rate: f64, steps: usize) -> f64 {
+        // f(x) = (x - 3)^2
+        let mut x = vec![0.];
+
+        for _i in 0..steps {
+            let g = vec![2. * (x[0] - 3.)];
+            opt.step(&mut x, &g, rate);
+        }
+
+        x[0]
+    }
+
+    #[test]
+    fn sgd_finds_minimum() {
+        assert!((minimize(&mut Sgd, 0.1, 100) - 3.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn momentum_finds_minimum() {
+        assert!((minimize(&mut Momentum::new(0.5), 0.1, 100) - 3.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn adam_finds_minimum() {
+        assert!((minimize(&mut Adam::default(), 0.1, 500) - 3.).abs() < 1e-2);
+    }
+
+    #[test]
+    fn adam_works_with_f32() {
+        let mut opt: Adam<f32> = Adam::default();
+        let mut x = vec![0.0_f32];
+
+        for _i in 0..500 {
+            let g = vec![2. * (x[0] - 3.)];
+            opt.step(&mut x, &g, 0.1);
+        }
+
+        assert!((x[0] - 3.).abs() < 1e-2);
+    }
+
+    #[test]
+    fn weight_decay_shrinks_parameters() {
+        let mut opt = WeightDecay::new(Sgd, 0.5);
+        let mut x = vec![2., -2.];
+
+        opt.step(&mut x, &[0., 0.], 1.);
+
+        assert_eq!(x, vec![1., -1.]);
+    }
+
+    #[test]
+    fn schedules_decay() {
+        let step = StepDecay {
+            rate: 1.,
+            factor: 0.5,
+            every: 10,
+        };
+        let cosine = CosineAnnealing {
+            max: 1.,
+            min: 0.,
+            period: 100,
+        };
+
+        assert_eq!(Constant(0.3).rate(1000), 0.3);
+        assert_eq!(step.rate(9), 1.);
+        assert_eq!(step.rate(25), 0.25);
+        assert!(
+            ExponentialDecay {
+                rate: 1.,
+                gamma: 0.9
+            }
+            .rate(10)
+                < 0.35
+        );
+        assert_eq!(cosine.rate(0), 1.);
+        assert!((cosine.rate(50) - 0.5).abs() < 1e-12);
+    }
+}
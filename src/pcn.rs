@@ -1,73 +1,251 @@
 //! Predictive Coding based associative memory. The memory unit is encapsulated
-//! in a `struct`. The methods allow for both inference and learning and the 
+//! in a `struct`. The methods allow for both inference and learning and the
 //! operations that inference consists of.
 
+use crate::dmatrix::fmt_values;
 use crate::dmatrix::DMatrix;
-use crate::state::State;
+use crate::error::{check_index, check_len, check_rate, check_vec, HopfieldError};
+use crate::optim::{Optimizer, Schedule, Sgd};
+use crate::scalar::Scalar;
+use crate::state::{gaussian, State};
+use rand::Rng;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::fmt::{self, Debug, Display, Formatter};
 
-fn activation(vs: &mut [f64]) {
+fn activation<T: Scalar>(vs: &mut [T]) {
     for i in 0..vs.len() {
         vs[i] = vs[i].tanh();
     }
 }
 
-fn activation_diff(vs: &mut [f64]) {
+fn activation_diff<T: Scalar>(vs: &mut [T]) {
     for i in 0..vs.len() {
         let t = vs[i].tanh();
-        vs[i] = 1. - t * t;
+        vs[i] = T::ONE - t * t;
     }
 }
 
-pub struct PCN {
-    weights: Vec<DMatrix<f64>>,
-    neurons: Vec<Vec<f64>>,
-    predictions: Vec<Vec<f64>>,
-    errors: Vec<Vec<f64>>,
-    layers: usize,
+/// A diagonal Gaussian over memory layer values, estimated from the memory
+/// layer at every learning step. Each stored pattern is therefore weighted by
+/// the number of `learn` steps spent on it; `PCN::memorize` trains every
+/// pattern for the same number of epochs, so they all have the same weight.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "RawMemoryPrior<T>")
+)]
+pub struct MemoryPrior<T = f64> {
+    pub(crate) mean: Vec<T>,
+    pub(crate) m2: Vec<T>,
+    pub(crate) count: usize,
 }
 
-impl PCN {
-    #[allow(dead_code)]
-    pub fn pp_state(&self) {
-        for l in 0..self.layers {
-            println!("Layer {}", l);
-            println!(" - neurons: {:?}", self.neurons[l]);
-            println!(" - predictions: {:?}", self.predictions[l]);
-            println!(" - errors: {:?}", self.errors[l]);
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawMemoryPrior<T> {
+    mean: Vec<T>,
+    m2: Vec<T>,
+    count: usize,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<RawMemoryPrior<T>> for MemoryPrior<T> {
+    type Error = String;
+
+    fn try_from(raw: RawMemoryPrior<T>) -> Result<Self, String> {
+        if raw.mean.len() != raw.m2.len() {
+            return Err(format!(
+                "prior mean has {} values but variance has {}",
+                raw.mean.len(),
+                raw.m2.len()
+            ));
+        }
+
+        Ok(Self {
+            mean: raw.mean,
+            m2: raw.m2,
+            count: raw.count,
+        })
+    }
+}
+
+impl<T: Scalar> MemoryPrior<T> {
+    fn new(n: usize) -> Self {
+        Self {
+            mean: vec![T::ZERO; n],
+            m2: vec![T::ZERO; n],
+            count: 0,
         }
     }
 
-    #[allow(dead_code)]
-    pub fn pp_weights(&self) {
-        for l in 0..(self.layers - 1) {
-            println!("Layer {} weights", l);
-            self.weights[l].pp();
+    // Welford's online algorithm for mean and variance.
+    fn observe(&mut self, memory: &[T]) {
+        self.count += 1;
+
+        let k = T::from_usize(self.count);
+
+        for i in 0..self.mean.len() {
+            let delta = memory[i] - self.mean[i];
+            self.mean[i] += delta / k;
+            self.m2[i] += delta * (memory[i] - self.mean[i]);
+        }
+    }
+
+    pub fn mean(&self) -> &[T] {
+        &self.mean
+    }
+
+    pub fn variance(&self) -> Vec<T> {
+        if self.count == 0 {
+            vec![T::ZERO; self.m2.len()]
+        } else {
+            let k = T::from_usize(self.count);
+
+            self.m2.iter().map(|v| *v / k).collect()
+        }
+    }
+
+    /// Number of memory values the prior has been estimated from.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+/// The result of completing a partially observed sensor pattern.
+pub struct Completion<T = f64> {
+    /// Observed sensors are copied through, missing sensors are inferred.
+    pub sensors: Vec<T>,
+    /// Absolute residual between each sensor and its top-down prediction.
+    pub uncertainty: Vec<T>,
+}
+
+/// The result of recalling a pattern from the codebook of a PCN.
+pub struct Recall<T = f64> {
+    /// Index of the selected code in the codebook.
+    pub index: usize,
+    pub completion: Completion<T>,
+}
+
+/// How the neuron values are initialised before inference with `PCN::infer`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InferenceStart {
+    /// Start from zero, independent of earlier calls.
+    Cold,
+    /// Start from the state cached at the end of the previous call to `infer`.
+    /// This suits streaming input where consecutive frames are similar.
+    Warm,
+    /// Start from a bottom-up guess made by the learned recognition weights.
+    Amortised,
+}
+
+/// A copy of the state of a PCN: activity, generative and recognition
+/// weights, memory prior, codebook and the state cached for warm starts. The
+/// internal state of the optimisers (eg. momentum or Adam moments) is not
+/// included, so two networks with equal snapshots behave the same unless they
+/// learn with stateful optimisers that have seen different gradients.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PCNSnapshot<T = f64> {
+    pub neurons: Vec<Vec<T>>,
+    pub predictions: Vec<Vec<T>>,
+    pub errors: Vec<Vec<T>>,
+    pub weights: Vec<DMatrix<T>>,
+    pub recognition: Vec<DMatrix<T>>,
+    pub prior: MemoryPrior<T>,
+    pub codebook: Vec<Vec<T>>,
+    pub cached: Option<Vec<Vec<T>>>,
+    pub learn_steps: usize,
+}
+
+impl<T: Scalar> PCNSnapshot<T> {
+    /// Compare two snapshots, allowing each value to differ by at most `tolerance`.
+    pub fn approx_eq(&self, other: &PCNSnapshot<T>, tolerance: T) -> bool {
+        fn close<T: Scalar>(a: &[T], b: &[T], tolerance: T) -> bool {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| (*x - *y).abs() <= tolerance)
+        }
+
+        fn all_close<T: Scalar>(a: &[Vec<T>], b: &[Vec<T>], tolerance: T) -> bool {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| close(x, y, tolerance))
+        }
+
+        fn all_close_matrices<T: Scalar>(a: &[DMatrix<T>], b: &[DMatrix<T>], tolerance: T) -> bool {
+            a.len() == b.len()
+                && a.iter().zip(b).all(|(x, y)| {
+                    x.rows() == y.rows()
+                        && x.cols() == y.cols()
+                        && close(x.as_slice(), y.as_slice(), tolerance)
+                })
         }
+
+        let cached_close = match (&self.cached, &other.cached) {
+            (Some(a), Some(b)) => all_close(a, b, tolerance),
+            (None, None) => true,
+            _ => false,
+        };
+
+        self.learn_steps == other.learn_steps
+            && self.prior.count == other.prior.count
+            && close(&self.prior.mean, &other.prior.mean, tolerance)
+            && close(&self.prior.m2, &other.prior.m2, tolerance)
+            && cached_close
+            && all_close(&self.neurons, &other.neurons, tolerance)
+            && all_close(&self.predictions, &other.predictions, tolerance)
+            && all_close(&self.errors, &other.errors, tolerance)
+            && all_close(&self.codebook, &other.codebook, tolerance)
+            && all_close_matrices(&self.weights, &other.weights, tolerance)
+            && all_close_matrices(&self.recognition, &other.recognition, tolerance)
     }
+}
+
+pub struct PCN<T = f64> {
+    weights: Vec<DMatrix<T>>,
+    neurons: Vec<Vec<T>>,
+    predictions: Vec<Vec<T>>,
+    errors: Vec<Vec<T>>,
+    layers: usize,
+    optimizers: Vec<Box<dyn Optimizer<T>>>,
+    learn_steps: usize,
+    prior: MemoryPrior<T>,
+    codebook: Vec<Vec<T>>,
+    recognition: Vec<DMatrix<T>>,
+    cached: Option<Vec<Vec<T>>>,
+}
 
+impl<T: Scalar> PCN<T> {
     pub fn new(layers: usize, n: usize, d: usize) -> Self {
         debug_assert!(layers > 1);
         debug_assert!(n > 0);
         debug_assert!(d > 1);
 
         let mut weights = Vec::new();
-        weights.push(DMatrix::new(d, n, 0.));
+        let mut recognition = Vec::new();
+        weights.push(DMatrix::new(d, n, T::ZERO));
+        recognition.push(DMatrix::new(n, d, T::ZERO));
         for _i in 0..(layers - 2) {
-            weights.push(DMatrix::new(n, n, 0.));
+            weights.push(DMatrix::new(n, n, T::ZERO));
+            recognition.push(DMatrix::new(n, n, T::ZERO));
         }
 
         let mut neurons = Vec::new();
         let mut predictions = Vec::new();
         let mut errors = Vec::new();
 
-        neurons.push(vec![0.; d]);
-        predictions.push(vec![0.; d]);
-        errors.push(vec![0.; d]);
+        neurons.push(vec![T::ZERO; d]);
+        predictions.push(vec![T::ZERO; d]);
+        errors.push(vec![T::ZERO; d]);
 
         for _i in 0..(layers - 1) {
-            neurons.push(vec![0.; n]);
-            predictions.push(vec![0.; n]);
-            errors.push(vec![0.; n]);
+            neurons.push(vec![T::ZERO; n]);
+            predictions.push(vec![T::ZERO; n]);
+            errors.push(vec![T::ZERO; n]);
+        }
+
+        let mut optimizers: Vec<Box<dyn Optimizer<T>>> = Vec::new();
+        for _i in 0..layers {
+            optimizers.push(Box::new(Sgd));
         }
 
         Self {
@@ -76,26 +254,172 @@ impl PCN {
             predictions,
             errors,
             layers,
+            optimizers,
+            learn_steps: 0,
+            prior: MemoryPrior::new(n),
+            codebook: Vec::new(),
+            recognition,
+            cached: None,
+        }
+    }
+
+    /// Set the generative weights, which `new` sets to zero, to values drawn
+    /// uniformly from `[-amount, amount)` to break the symmetry between the
+    /// neurons of a layer.
+    pub fn initialize_weights(&mut self, rng: &mut impl Rng, amount: T) {
+        let amount = amount.to_f64();
+
+        for w in &mut self.weights {
+            for v in w.as_mut_slice() {
+                *v = T::from_f64(rng.random_range(-amount..amount));
+            }
         }
     }
 
-    pub fn memory(&self) -> &[f64] {
+    /// Set all neuron values, predictions and errors to zero. Weights, the
+    /// memory prior and the codebook are kept.
+    pub fn reset(&mut self) {
+        for l in 0..self.layers {
+            self.neurons[l].fill(T::ZERO);
+            self.predictions[l].fill(T::ZERO);
+            self.errors[l].fill(T::ZERO);
+        }
+    }
+
+    /// Set the neuron values above the sensor layer from the state cached by
+    /// the last call to `infer`. Falls back to `reset` if nothing is cached.
+    pub fn restore_cached(&mut self) {
+        match self.cached.take() {
+            Some(cached) => {
+                for l in 1..self.layers {
+                    self.neurons[l].copy_from(&cached[l - 1]);
+                }
+
+                self.cached = Some(cached);
+            }
+            None => self.reset(),
+        }
+    }
+
+    /// Initialise the neuron values above the sensor layer with a bottom-up
+    /// pass of `sensor_pattern` through the recognition weights.
+    pub fn amortised_init(&mut self, sensor_pattern: &[T]) {
+        self.sensors_mut().copy_from(sensor_pattern);
+
+        for l in 0..(self.layers - 1) {
+            let (below, above) = self.neurons.split_at_mut(l + 1);
+            self.recognition[l].mul_vec(&below[l], &mut above[0]);
+        }
+    }
+
+    /// Train the recognition weights to map each layer to the layer above as
+    /// found by the last inference, using the delta rule.
+    pub fn learn_recognition(&mut self, alpha: T) {
+        for l in 0..(self.layers - 1) {
+            let mut guess = vec![T::ZERO; self.neurons[l + 1].len()];
+            self.recognition[l].mul_vec(&self.neurons[l], &mut guess);
+
+            for r in 0..guess.len() {
+                let delta = alpha * (self.neurons[l + 1][r] - guess[r]);
+
+                for c in 0..self.neurons[l].len() {
+                    self.recognition[l][(r, c)] += delta * self.neurons[l][c];
+                }
+            }
+        }
+    }
+
+    /// Inference with explicit initialisation of the neuron values, see
+    /// `InferenceStart`. The resulting state is cached for later warm starts.
+    pub fn infer(
+        &mut self,
+        start: InferenceStart,
+        memory_pattern: &[T],
+        sensor_pattern: &[T],
+        gamma: T,
+        steps: usize,
+    ) {
+        match start {
+            InferenceStart::Cold => self.reset(),
+            InferenceStart::Warm => self.restore_cached(),
+            InferenceStart::Amortised => self.amortised_init(sensor_pattern),
+        }
+
+        self.inference(memory_pattern, sensor_pattern, gamma, steps);
+        self.cached = Some(self.neurons[1..].to_vec());
+    }
+
+    /// Use `optimizer` for all subsequent calls to `learn`. Each weight matrix
+    /// and the memory layer get their own copy of the optimiser (and its state).
+    pub fn set_optimizer<O: Optimizer<T> + Clone + 'static>(&mut self, optimizer: O) {
+        self.optimizers.clear();
+
+        for _i in 0..self.layers {
+            self.optimizers.push(Box::new(optimizer.clone()));
+        }
+    }
+
+    /// The number of learning steps taken so far.
+    pub fn learn_steps(&self) -> usize {
+        self.learn_steps
+    }
+
+    /// Number of layers, including the sensor and memory layers.
+    pub fn layers(&self) -> usize {
+        self.layers
+    }
+
+    /// Neuron values of layer `l`. Layer 0 is the sensor layer.
+    pub fn neurons(&self, l: usize) -> &[T] {
+        &self.neurons[l]
+    }
+
+    /// Top-down predictions of layer `l`.
+    pub fn predictions(&self, l: usize) -> &[T] {
+        &self.predictions[l]
+    }
+
+    /// Prediction errors of layer `l`.
+    pub fn errors(&self, l: usize) -> &[T] {
+        &self.errors[l]
+    }
+
+    /// Weights predicting layer `l` from layer `l + 1`.
+    pub fn weights(&self, l: usize) -> &DMatrix<T> {
+        &self.weights[l]
+    }
+
+    pub fn snapshot(&self) -> PCNSnapshot<T> {
+        PCNSnapshot {
+            neurons: self.neurons.clone(),
+            predictions: self.predictions.clone(),
+            errors: self.errors.clone(),
+            weights: self.weights.clone(),
+            recognition: self.recognition.clone(),
+            prior: self.prior.clone(),
+            codebook: self.codebook.clone(),
+            cached: self.cached.clone(),
+            learn_steps: self.learn_steps,
+        }
+    }
+
+    pub fn memory(&self) -> &[T] {
         &self.neurons[self.layers - 1]
     }
 
-    pub fn memory_mut(&mut self) -> &mut [f64] {
+    pub fn memory_mut(&mut self) -> &mut [T] {
         &mut self.neurons[self.layers - 1]
     }
 
-    pub fn sensors(&self) -> &[f64] {
+    pub fn sensors(&self) -> &[T] {
         &self.neurons[0]
     }
 
-    pub fn sensors_mut(&mut self) -> &mut [f64] {
+    pub fn sensors_mut(&mut self) -> &mut [T] {
         &mut self.neurons[0]
     }
 
-    pub fn prediction(&mut self, memory_values: &[f64]) {
+    pub fn prediction(&mut self, memory_values: &[T]) {
         self.predictions[self.layers - 1].copy_from(memory_values);
 
         for i in (0..(self.layers - 1)).rev() {
@@ -105,9 +429,9 @@ impl PCN {
         }
     }
 
-    pub fn error(&mut self, sensor_values: &[f64]) {
+    pub fn error(&mut self, sensor_values: &[T]) {
         for j in 0..self.neurons[0].len() {
-            if sensor_values[j].abs() <= f64::EPSILON {
+            if sensor_values[j].abs() <= T::EPSILON {
                 self.errors[0][j] = self.neurons[0][j] - self.predictions[0][j];
             } else {
                 self.errors[0][j] = sensor_values[j] - self.predictions[0][j];
@@ -121,8 +445,8 @@ impl PCN {
         }
     }
 
-    pub fn global_error(&self) -> f64 {
-        let mut acc = 0.;
+    pub fn global_error(&self) -> T {
+        let mut acc = T::ZERO;
 
         for i in 0..self.layers {
             for j in 0..self.errors[i].len() {
@@ -131,36 +455,58 @@ impl PCN {
             }
         }
 
-        acc * 0.5
+        acc * T::from_f64(0.5)
     }
 
-    pub fn inference_sensor_step(&mut self, sensor_mask: &[f64], gamma: f64) {
+    pub fn inference_sensor_step(&mut self, sensor_mask: &[T], gamma: T) {
         for i in 0..self.neurons[0].len() {
-            if sensor_mask[i].abs() <= f64::EPSILON {
+            if sensor_mask[i].abs() <= T::EPSILON {
                 self.neurons[0][i] -= gamma * self.errors[0][i];
             }
         }
     }
 
-    pub fn inference_step(&mut self, gamma: f64) {
-        for i in 1..self.layers {
-            let mut ad = self.neurons[i].clone();
+    pub fn inference_step(&mut self, gamma: T) {
+        let errors = &self.errors;
+        let weights = &self.weights;
+
+        // Layer `i` only reads the errors of layers `i` and `i - 1`, so the
+        // layers can be updated independently.
+        let update = |(k, neurons): (usize, &mut Vec<T>)| {
+            let i = k + 1;
+
+            let mut ad = neurons.clone();
             activation_diff(&mut ad);
 
-            let mut et = vec![0.; self.neurons[i].len()];
-            self.weights[i - 1].trans_mul_vec(&self.errors[i - 1], &mut et);
+            let mut et = vec![T::ZERO; neurons.len()];
+            weights[i - 1].trans_mul_vec(&errors[i - 1], &mut et);
 
-            for j in 0..self.neurons[i].len() {
-                self.neurons[i][j] += gamma * (-self.errors[i][j] + ad[j] * et[j]);
+            for j in 0..neurons.len() {
+                neurons[j] += gamma * (-errors[i][j] + ad[j] * et[j]);
             }
+        };
+
+        #[cfg(feature = "parallel")]
+        if weights.iter().map(|w| w.as_slice().len()).sum::<usize>() >= crate::PARALLEL_THRESHOLD {
+            self.neurons[1..]
+                .par_iter_mut()
+                .enumerate()
+                .for_each(update);
+
+            return;
         }
+
+        self.neurons[1..].iter_mut().enumerate().for_each(update);
     }
 
+    /// Inference with the memory and sensor layers set to the given patterns.
+    /// The hidden layers continue from their current values; see `infer` for
+    /// inference with explicit initialisation.
     pub fn inference(
         &mut self,
-        memory_pattern: &[f64],
-        sensor_pattern: &[f64],
-        gamma: f64,
+        memory_pattern: &[T],
+        sensor_pattern: &[T],
+        gamma: T,
         steps: usize,
     ) {
         self.memory_mut().copy_from(memory_pattern);
@@ -175,9 +521,9 @@ impl PCN {
 
     pub fn inference_with_sensors(
         &mut self,
-        memory_pattern: &[f64],
-        sensor_pattern: &[f64],
-        gamma: f64,
+        memory_pattern: &[T],
+        sensor_pattern: &[T],
+        gamma: T,
         steps: usize,
     ) {
         self.memory_mut().copy_from(memory_pattern);
@@ -191,25 +537,663 @@ impl PCN {
         }
     }
 
-    pub fn learn(&mut self, alpha: f64) {
+    // Gradient of the energy with respect to the weights of layer `i`.
+    fn weight_gradients(&self, i: usize) -> DMatrix<T> {
+        let mut grads = DMatrix::new(self.weights[i].rows(), self.weights[i].cols(), T::ZERO);
+        let cols = grads.cols();
+        let errors = &self.errors[i];
+        let neurons = &self.neurons[i];
+
+        let fill_row = |(r, row): (usize, &mut [T])| {
+            for c in 0..row.len() {
+                row[c] = -errors[r] * neurons[c].tanh();
+            }
+        };
+
+        #[cfg(feature = "parallel")]
+        if grads.as_slice().len() >= crate::PARALLEL_THRESHOLD {
+            grads
+                .as_mut_slice()
+                .par_chunks_mut(cols)
+                .enumerate()
+                .for_each(fill_row);
+
+            return grads;
+        }
+
+        grads
+            .as_mut_slice()
+            .chunks_mut(cols)
+            .enumerate()
+            .for_each(fill_row);
+
+        grads
+    }
+
+    pub fn learn(&mut self, alpha: T) {
         for i in 0..(self.layers - 1) {
-            for r in 0..self.weights[i].rows() {
-                for c in 0..self.weights[i].cols() {
-                    self.weights[i][(r, c)] +=
-                        alpha * self.errors[i][r] * self.neurons[i][c].tanh();
+            let grads = self.weight_gradients(i);
+
+            self.optimizers[i].step(self.weights[i].as_mut_slice(), grads.as_slice(), alpha);
+        }
+
+        let top = self.layers - 1;
+        self.optimizers[top].step(&mut self.neurons[top], &self.errors[top], alpha);
+
+        self.prior.observe(&self.neurons[top]);
+        self.learn_steps += 1;
+    }
+
+    /// Like `learn` but with the learning rate taken from `schedule`.
+    pub fn learn_scheduled(&mut self, schedule: &impl Schedule) {
+        self.learn(T::from_f64(schedule.rate(self.learn_steps)));
+    }
+
+    pub fn memory_prior(&self) -> &MemoryPrior<T> {
+        &self.prior
+    }
+
+    /// Propagate `memory` down through the network, setting every layer to its
+    /// prediction. Returns the predicted sensor layer.
+    pub fn generate(&mut self, memory: &[T]) -> Vec<T> {
+        self.memory_mut().copy_from(memory);
+        self.predictions[self.layers - 1].copy_from(memory);
+
+        for i in (0..(self.layers - 1)).rev() {
+            let mut temp_vec = self.neurons[i + 1].clone();
+            activation(&mut temp_vec);
+            self.weights[i].mul_vec(&temp_vec, &mut self.predictions[i]);
+            self.neurons[i].copy_from(&self.predictions[i]);
+        }
+
+        for i in 0..self.layers {
+            self.errors[i].fill(T::ZERO);
+        }
+
+        self.neurons[0].clone()
+    }
+
+    /// Generate a sensor pattern from a memory drawn from the learned memory
+    /// prior. Before any learning the prior is a point mass at zero.
+    pub fn sample(&mut self, rng: &mut impl Rng) -> Vec<T> {
+        let variance = self.prior.variance();
+        let mut memory = self.prior.mean().to_vec();
+
+        for i in 0..memory.len() {
+            memory[i] += variance[i].sqrt() * T::from_f64(gaussian(rng));
+        }
+
+        self.generate(&memory)
+    }
+
+    /// Infer the hidden layers, the memory layer and the sensors where `mask`
+    /// is `false`, keeping the sensors where `mask` is `true` fixed at the
+    /// values in `partial_sensors`. The memory layer starts at, and is pulled
+    /// towards, the mean of the memory prior.
+    pub fn complete(
+        &mut self,
+        partial_sensors: &[T],
+        mask: &[bool],
+        gamma: T,
+        steps: usize,
+    ) -> Completion<T> {
+        let prior_mean = self.prior.mean().to_vec();
+
+        self.complete_from(&prior_mean, false, partial_sensors, mask, gamma, steps)
+    }
+
+    // Pattern completion starting with the memory layer at `start`. If
+    // `free_top` is set, the memory layer is not pulled back towards `start`.
+    fn complete_from(
+        &mut self,
+        start: &[T],
+        free_top: bool,
+        partial_sensors: &[T],
+        mask: &[bool],
+        gamma: T,
+        steps: usize,
+    ) -> Completion<T> {
+        debug_assert_eq!(partial_sensors.len(), self.neurons[0].len());
+        debug_assert_eq!(mask.len(), self.neurons[0].len());
+
+        let top = self.layers - 1;
+        let mut top_prior = start.to_vec();
+
+        self.generate(start);
+
+        for i in 0..mask.len() {
+            if mask[i] {
+                self.neurons[0][i] = partial_sensors[i];
+            }
+        }
+
+        for _i in 0..steps {
+            if free_top {
+                top_prior.copy_from(&self.neurons[top]);
+            }
+
+            self.prediction(&top_prior);
+            self.layer_errors();
+
+            for j in 0..mask.len() {
+                if !mask[j] {
+                    self.neurons[0][j] -= gamma * self.errors[0][j];
                 }
             }
+
+            self.inference_step(gamma);
+        }
+
+        if free_top {
+            top_prior.copy_from(&self.neurons[top]);
+        }
+
+        self.prediction(&top_prior);
+        self.layer_errors();
+
+        Completion {
+            sensors: self.neurons[0].clone(),
+            uncertainty: self.errors[0].iter().map(|e| e.abs()).collect(),
+        }
+    }
+
+    pub fn codebook(&self) -> &[Vec<T>] {
+        &self.codebook
+    }
+
+    /// Add a new memory code, drawn uniformly from `[-1, 1]`, to the codebook.
+    /// Returns the index of the new code.
+    pub fn add_code(&mut self, rng: &mut impl Rng) -> usize {
+        let code = (0..self.memory().len())
+            .map(|_| T::from_f64(rng.random_range(-1. ..1.)))
+            .collect();
+
+        self.codebook.push(code);
+        self.codebook.len() - 1
+    }
+
+    /// One round of inference and learning of `sensor_pattern` with the memory
+    /// layer initialised from code `index`. The code is updated to the learned
+    /// memory layer afterwards.
+    pub fn train_code(
+        &mut self,
+        index: usize,
+        sensor_pattern: &[T],
+        gamma: T,
+        steps: usize,
+        alpha: T,
+    ) {
+        let code = self.codebook[index].clone();
+
+        self.inference(&code, sensor_pattern, gamma, steps);
+        self.learn(alpha);
+
+        let top = self.layers - 1;
+        self.codebook[index].copy_from(&self.neurons[top]);
+    }
+
+    /// Store several sensor patterns in the network, each with its own
+    /// learned memory code. Returns the code index of each pattern.
+    pub fn memorize(
+        &mut self,
+        patterns: &[Vec<T>],
+        rng: &mut impl Rng,
+        gamma: T,
+        steps: usize,
+        alpha: T,
+        epochs: usize,
+    ) -> Vec<usize> {
+        let indices: Vec<usize> = patterns.iter().map(|_| self.add_code(rng)).collect();
+
+        for _e in 0..epochs {
+            for (k, pattern) in patterns.iter().enumerate() {
+                self.train_code(indices[k], pattern, gamma, steps, alpha);
+            }
+        }
+
+        indices
+    }
+
+    /// Recall a stored pattern from a partial cue. The memory layer is first
+    /// inferred freely from the cue, the closest code in the codebook is
+    /// selected and the sensors are then completed from that code.
+    pub fn recall(
+        &mut self,
+        partial_sensors: &[T],
+        mask: &[bool],
+        gamma: T,
+        steps: usize,
+    ) -> Recall<T> {
+        debug_assert!(!self.codebook.is_empty());
+
+        let start = self.prior.mean().to_vec();
+        self.complete_from(&start, true, partial_sensors, mask, gamma, steps);
+
+        let index = self.nearest_code(self.memory());
+        let code = self.codebook[index].clone();
+        let completion = self.complete_from(&code, false, partial_sensors, mask, gamma, steps);
+
+        Recall { index, completion }
+    }
+
+    fn nearest_code(&self, memory: &[T]) -> usize {
+        let mut best = 0;
+        let mut best_dist = T::MAX;
+
+        for (k, code) in self.codebook.iter().enumerate() {
+            let mut dist = T::ZERO;
+            for i in 0..code.len() {
+                let d = code[i] - memory[i];
+                dist += d * d;
+            }
+
+            if dist < best_dist {
+                best_dist = dist;
+                best = k;
+            }
+        }
+
+        best
+    }
+
+    fn layer_errors(&mut self) {
+        for i in 0..self.layers {
+            for j in 0..self.neurons[i].len() {
+                self.errors[i][j] = self.neurons[i][j] - self.predictions[i][j];
+            }
+        }
+    }
+}
+
+// Fallible variants of the operations above. They check the sizes of and
+// values in their arguments and the hyper-parameters before doing anything,
+// so the network is unchanged when they return an error.
+impl<T: Scalar> PCN<T> {
+    /// Like `new`, but returns an error unless there are at least two layers,
+    /// at least one neuron per hidden layer and at least two sensors.
+    pub fn try_new(layers: usize, n: usize, d: usize) -> Result<Self, HopfieldError> {
+        if layers < 2 {
+            return Err(HopfieldError::InvalidParameter {
+                name: "layers",
+                reason: "must be at least 2",
+            });
+        }
+        if n == 0 {
+            return Err(HopfieldError::InvalidParameter {
+                name: "n",
+                reason: "must be positive",
+            });
+        }
+        if d < 2 {
+            return Err(HopfieldError::InvalidParameter {
+                name: "d",
+                reason: "must be at least 2",
+            });
+        }
+
+        Ok(Self::new(layers, n, d))
+    }
+
+    fn check_sensors(&self, what: &'static str, values: &[T]) -> Result<(), HopfieldError> {
+        check_vec(what, self.neurons[0].len(), values)
+    }
+
+    fn check_memory(&self, what: &'static str, values: &[T]) -> Result<(), HopfieldError> {
+        check_vec(what, self.memory().len(), values)
+    }
+
+    pub fn try_initialize_weights(
+        &mut self,
+        rng: &mut impl Rng,
+        amount: T,
+    ) -> Result<(), HopfieldError> {
+        check_rate("amount", amount)?;
+
+        self.initialize_weights(rng, amount);
+        Ok(())
+    }
+
+    pub fn try_amortised_init(&mut self, sensor_pattern: &[T]) -> Result<(), HopfieldError> {
+        self.check_sensors("sensor pattern", sensor_pattern)?;
+
+        self.amortised_init(sensor_pattern);
+        Ok(())
+    }
+
+    pub fn try_learn_recognition(&mut self, alpha: T) -> Result<(), HopfieldError> {
+        check_rate("alpha", alpha)?;
+
+        self.learn_recognition(alpha);
+        Ok(())
+    }
+
+    pub fn try_infer(
+        &mut self,
+        start: InferenceStart,
+        memory_pattern: &[T],
+        sensor_pattern: &[T],
+        gamma: T,
+        steps: usize,
+    ) -> Result<(), HopfieldError> {
+        self.check_memory("memory pattern", memory_pattern)?;
+        self.check_sensors("sensor pattern", sensor_pattern)?;
+        check_rate("gamma", gamma)?;
+
+        self.infer(start, memory_pattern, sensor_pattern, gamma, steps);
+        Ok(())
+    }
+
+    pub fn try_prediction(&mut self, memory_values: &[T]) -> Result<(), HopfieldError> {
+        self.check_memory("memory values", memory_values)?;
+
+        self.prediction(memory_values);
+        Ok(())
+    }
+
+    pub fn try_error(&mut self, sensor_values: &[T]) -> Result<(), HopfieldError> {
+        self.check_sensors("sensor values", sensor_values)?;
+
+        self.error(sensor_values);
+        Ok(())
+    }
+
+    pub fn try_inference_sensor_step(
+        &mut self,
+        sensor_mask: &[T],
+        gamma: T,
+    ) -> Result<(), HopfieldError> {
+        self.check_sensors("sensor mask", sensor_mask)?;
+        check_rate("gamma", gamma)?;
+
+        self.inference_sensor_step(sensor_mask, gamma);
+        Ok(())
+    }
+
+    pub fn try_inference_step(&mut self, gamma: T) -> Result<(), HopfieldError> {
+        check_rate("gamma", gamma)?;
+
+        self.inference_step(gamma);
+        Ok(())
+    }
+
+    pub fn try_inference(
+        &mut self,
+        memory_pattern: &[T],
+        sensor_pattern: &[T],
+        gamma: T,
+        steps: usize,
+    ) -> Result<(), HopfieldError> {
+        self.check_memory("memory pattern", memory_pattern)?;
+        self.check_sensors("sensor pattern", sensor_pattern)?;
+        check_rate("gamma", gamma)?;
+
+        self.inference(memory_pattern, sensor_pattern, gamma, steps);
+        Ok(())
+    }
+
+    pub fn try_inference_with_sensors(
+        &mut self,
+        memory_pattern: &[T],
+        sensor_pattern: &[T],
+        gamma: T,
+        steps: usize,
+    ) -> Result<(), HopfieldError> {
+        self.check_memory("memory pattern", memory_pattern)?;
+        self.check_sensors("sensor pattern", sensor_pattern)?;
+        check_rate("gamma", gamma)?;
+
+        self.inference_with_sensors(memory_pattern, sensor_pattern, gamma, steps);
+        Ok(())
+    }
+
+    pub fn try_learn(&mut self, alpha: T) -> Result<(), HopfieldError> {
+        check_rate("alpha", alpha)?;
+
+        self.learn(alpha);
+        Ok(())
+    }
+
+    pub fn try_learn_scheduled(&mut self, schedule: &impl Schedule) -> Result<(), HopfieldError> {
+        self.try_learn(T::from_f64(schedule.rate(self.learn_steps)))
+    }
+
+    pub fn try_generate(&mut self, memory: &[T]) -> Result<Vec<T>, HopfieldError> {
+        self.check_memory("memory", memory)?;
+
+        Ok(self.generate(memory))
+    }
+
+    pub fn try_complete(
+        &mut self,
+        partial_sensors: &[T],
+        mask: &[bool],
+        gamma: T,
+        steps: usize,
+    ) -> Result<Completion<T>, HopfieldError> {
+        self.check_sensors("partial sensors", partial_sensors)?;
+        check_len("mask", self.neurons[0].len(), mask.len())?;
+        check_rate("gamma", gamma)?;
+
+        Ok(self.complete(partial_sensors, mask, gamma, steps))
+    }
+
+    pub fn try_train_code(
+        &mut self,
+        index: usize,
+        sensor_pattern: &[T],
+        gamma: T,
+        steps: usize,
+        alpha: T,
+    ) -> Result<(), HopfieldError> {
+        check_index("code index", index, self.codebook.len())?;
+        self.check_sensors("sensor pattern", sensor_pattern)?;
+        check_rate("gamma", gamma)?;
+        check_rate("alpha", alpha)?;
+
+        self.train_code(index, sensor_pattern, gamma, steps, alpha);
+        Ok(())
+    }
+
+    pub fn try_memorize(
+        &mut self,
+        patterns: &[Vec<T>],
+        rng: &mut impl Rng,
+        gamma: T,
+        steps: usize,
+        alpha: T,
+        epochs: usize,
+    ) -> Result<Vec<usize>, HopfieldError> {
+        for pattern in patterns {
+            self.check_sensors("pattern", pattern)?;
+        }
+        check_rate("gamma", gamma)?;
+        check_rate("alpha", alpha)?;
+
+        Ok(self.memorize(patterns, rng, gamma, steps, alpha, epochs))
+    }
+
+    /// Like `recall`, but also returns `EmptyMemory` if nothing has been
+    /// memorised yet.
+    pub fn try_recall(
+        &mut self,
+        partial_sensors: &[T],
+        mask: &[bool],
+        gamma: T,
+        steps: usize,
+    ) -> Result<Recall<T>, HopfieldError> {
+        if self.codebook.is_empty() {
+            return Err(HopfieldError::EmptyMemory);
+        }
+
+        self.check_sensors("partial sensors", partial_sensors)?;
+        check_len("mask", self.neurons[0].len(), mask.len())?;
+        check_rate("gamma", gamma)?;
+
+        Ok(self.recall(partial_sensors, mask, gamma, steps))
+    }
+}
+
+impl<T: Scalar> Debug for PCN<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PCN")
+            .field("layers", &self.layers)
+            .field("neurons", &self.neurons)
+            .field("predictions", &self.predictions)
+            .field("errors", &self.errors)
+            .field("weights", &self.weights)
+            .field("codebook", &self.codebook)
+            .field("learn_steps", &self.learn_steps)
+            .finish()
+    }
+}
+
+impl<T: Scalar> Display for PCN<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for l in 0..self.layers {
+            writeln!(f, "Layer {}", l)?;
+            write!(f, " - neurons: ")?;
+            fmt_values(f, &self.neurons[l])?;
+            write!(f, "\n - predictions: ")?;
+            fmt_values(f, &self.predictions[l])?;
+            write!(f, "\n - errors: ")?;
+            fmt_values(f, &self.errors[l])?;
+            writeln!(f)?;
+        }
+
+        for l in 0..(self.layers - 1) {
+            writeln!(f, "Layer {} weights", l)?;
+            Display::fmt(&self.weights[l], f)?;
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+// A PCN is saved (with serde or in the binary model format) as its learned
+// parameters: the weights, recognition weights, memory prior, codebook and
+// step count. Neuron activity is not saved, and the optimisers (which may be
+// arbitrary trait objects) are reset to `Sgd` on load.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub(crate) struct PCNParamsRef<'a, T> {
+    pub(crate) layers: usize,
+    pub(crate) weights: &'a [DMatrix<T>],
+    pub(crate) recognition: &'a [DMatrix<T>],
+    pub(crate) prior: &'a MemoryPrior<T>,
+    pub(crate) codebook: &'a [Vec<T>],
+    pub(crate) learn_steps: usize,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub(crate) struct PCNParams<T> {
+    pub(crate) layers: usize,
+    pub(crate) weights: Vec<DMatrix<T>>,
+    pub(crate) recognition: Vec<DMatrix<T>>,
+    pub(crate) prior: MemoryPrior<T>,
+    pub(crate) codebook: Vec<Vec<T>>,
+    pub(crate) learn_steps: usize,
+}
+
+impl<T: Scalar> PCN<T> {
+    pub(crate) fn params(&self) -> PCNParamsRef<'_, T> {
+        PCNParamsRef {
+            layers: self.layers,
+            weights: &self.weights,
+            recognition: &self.recognition,
+            prior: &self.prior,
+            codebook: &self.codebook,
+            learn_steps: self.learn_steps,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Scalar + serde::Serialize> serde::Serialize for PCN<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.params().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Scalar + serde::Deserialize<'de>> serde::Deserialize<'de> for PCN<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = PCNParams::deserialize(deserializer)?;
+
+        PCN::try_from(raw).map_err(serde::de::Error::custom)
+    }
+}
+
+impl<T: Scalar> TryFrom<PCNParams<T>> for PCN<T> {
+    type Error = String;
+
+    fn try_from(raw: PCNParams<T>) -> Result<Self, String> {
+        if raw.layers < 2 || raw.weights.len() != raw.layers - 1 {
+            return Err(format!(
+                "a PCN with {} layers needs {} weight matrices, found {}",
+                raw.layers,
+                raw.layers.saturating_sub(1),
+                raw.weights.len()
+            ));
+        }
+
+        let d = raw.weights[0].rows();
+        let n = raw.weights[0].cols();
+
+        if d < 2 || n == 0 {
+            return Err(format!("invalid sizes {} (sensors) and {} (neurons)", d, n));
+        }
+
+        for l in 0..(raw.layers - 1) {
+            let rows = if l == 0 { d } else { n };
+
+            if raw.weights[l].rows() != rows || raw.weights[l].cols() != n {
+                return Err(format!(
+                    "weights {} should be {}x{}, found {}x{}",
+                    l,
+                    rows,
+                    n,
+                    raw.weights[l].rows(),
+                    raw.weights[l].cols()
+                ));
+            }
         }
 
-        for i in 0..self.memory().len() {
-            self.neurons[self.layers - 1][i] -= alpha * self.errors[self.layers - 1][i];
+        let mut pcn = PCN::new(raw.layers, n, d);
+
+        if raw.recognition.len() != pcn.recognition.len()
+            || raw
+                .recognition
+                .iter()
+                .zip(&pcn.recognition)
+                .any(|(a, b)| a.rows() != b.rows() || a.cols() != b.cols())
+        {
+            return Err("recognition weights do not match the layer sizes".to_string());
         }
+        if raw.prior.mean.len() != n {
+            return Err(format!(
+                "prior has {} values, expected {}",
+                raw.prior.mean.len(),
+                n
+            ));
+        }
+        if let Some(code) = raw.codebook.iter().find(|c| c.len() != n) {
+            return Err(format!("code has {} values, expected {}", code.len(), n));
+        }
+
+        pcn.weights = raw.weights;
+        pcn.recognition = raw.recognition;
+        pcn.prior = raw.prior;
+        pcn.codebook = raw.codebook;
+        pcn.learn_steps = raw.learn_steps;
+
+        Ok(pcn)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::seed::Seed;
     use crate::state::State;
 
     #[test]
@@ -224,85 +1208,373 @@ mod test {
         }
     }
 
+    fn alternating<T: Scalar>(n: usize) -> Vec<T> {
+        (0..n)
+            .map(|i| T::from_f64(if i % 2 == 0 { 1. } else { -1. }))
+            .collect()
+    }
+
+    // Train `pcn` on the alternating pattern for `rounds` rounds of `steps`
+    // inference steps, then check it completes the pattern from its first half.
+    fn assert_learns_alternating(pcn: &mut PCN, rounds: usize, steps: usize) {
+        let s = alternating(16);
+        let m = alternating(8);
+
+        for _i in 0..rounds {
+            pcn.inference(&m, &s, 0.1, steps);
+            pcn.learn(0.1);
+        }
+
+        let pattern: Vec<f64> = (0..16).map(|i| if i < 8 { s[i] } else { 0. }).collect();
+
+        pcn.memory_mut().copy_from(&m);
+        pcn.sensors_mut().copy_from(&pattern);
+
+        pcn.inference_with_sensors(&m, &pattern, 0.1, 1000);
+
+        for i in 0..16 {
+            assert!((pcn.sensors()[i] - s[i]).abs() < 0.1);
+        }
+
+        assert!(pcn.global_error() < 0.1);
+    }
+
     #[test]
     fn can_learn_a_pattern() {
-        const SENSORS: usize = 16;
-        const MEMORY: usize = 8;
+        let mut pcn = PCN::new(3, 8, 16);
+
+        assert_learns_alternating(&mut pcn, 10, 1000);
+    }
+
+    #[test]
+    fn momentum_learns_a_pattern_in_fewer_iterations() {
+        use crate::optim::Momentum;
+
+        let mut pcn = PCN::new(3, 8, 16);
+        pcn.set_optimizer(Momentum::new(0.5));
+
+        assert_learns_alternating(&mut pcn, 3, 100);
+        assert_eq!(pcn.learn_steps(), 3);
+    }
 
-        let mut pcn = PCN::new(3, MEMORY, SENSORS);
-        let mut s = vec![0.; SENSORS];
-        let mut m = vec![0.; MEMORY];
+    fn trained_pcn(s: &[f64], m: &[f64]) -> PCN {
+        let mut pcn = PCN::new(3, m.len(), s.len());
 
-        for i in 0..SENSORS {
-            s[i] = if i % 2 == 0 { 1. } else { -1. };
+        for _i in 0..10 {
+            pcn.inference(m, s, 0.1, 1000);
+            pcn.learn(0.1);
         }
-        for i in 0..MEMORY {
-            m[i] = if i % 2 == 0 { 1. } else { -1. };
+
+        pcn
+    }
+
+    #[test]
+    fn generates_learned_pattern() {
+        let s: Vec<f64> = alternating(16);
+        let m: Vec<f64> = alternating(8);
+        let mut pcn = trained_pcn(&s, &m);
+
+        let memory = pcn.memory().to_vec();
+        let generated = pcn.generate(&memory);
+
+        for i in 0..16 {
+            assert!((generated[i] - s[i]).abs() < 0.1);
         }
+    }
+
+    #[test]
+    fn completes_missing_sensors() {
+        let s: Vec<f64> = alternating(16);
+        let m: Vec<f64> = alternating(8);
+        let mut pcn = trained_pcn(&s, &m);
+
+        let mask: Vec<bool> = (0..16).map(|i| i < 8).collect();
+        let partial: Vec<f64> = (0..16).map(|i| if i < 8 { s[i] } else { 0. }).collect();
 
-        const T: usize = 1000;
-        const U: usize = 10;
-        const GAMMA: f64 = 0.1;
-        const ALPHA: f64 = 0.1;
+        let completion = pcn.complete(&partial, &mask, 0.1, 1000);
 
-        for _i in 0..U {
-            pcn.inference(&m, &s, GAMMA, T);
-            pcn.learn(ALPHA);
+        for i in 0..16 {
+            assert!((completion.sensors[i] - s[i]).abs() < 0.1);
+            assert!(completion.uncertainty[i] < 0.1);
         }
+    }
+
+    #[test]
+    fn samples_from_memory_prior() {
+        let s1: Vec<f64> = alternating(16);
+        let s2: Vec<f64> = (0..16).map(|i| if i < 8 { 1. } else { -1. }).collect();
+        let m1: Vec<f64> = alternating(8);
+        let m2: Vec<f64> = m1.iter().map(|v| -v).collect();
+        let mut pcn = PCN::new(3, 8, 16);
+        let mut rng = Seed(1).rng();
 
-        let mut pattern = vec![0.; SENSORS];
-        for i in 0..(SENSORS / 2) {
-            pattern[i] = if i % 2 == 0 { 1. } else { -1. };
+        for _i in 0..10 {
+            for (m, s) in [(&m1, &s1), (&m2, &s2)] {
+                pcn.inference(m, s, 0.1, 1000);
+                pcn.learn(0.1);
+            }
         }
 
-        pcn.memory_mut().copy_from(&m);
-        pcn.sensors_mut().copy_from(&pattern);
+        assert_eq!(pcn.memory_prior().count(), 20);
+        assert!(pcn.memory_prior().variance().iter().all(|v| *v > 0.5));
 
-        pcn.inference_with_sensors(&m, &pattern, GAMMA, T);
+        let first = pcn.sample(&mut rng);
+        let second = pcn.sample(&mut rng);
 
-        for i in 0..SENSORS {
-            assert!((pcn.sensors()[i] - s[i]).abs() < 0.1);
+        assert_eq!(first.len(), 16);
+        assert!(first != second);
+    }
+
+    #[test]
+    fn recalls_pattern_from_codebook() {
+        const SENSORS: usize = 16;
+
+        let patterns: Vec<Vec<f64>> = vec![
+            alternating(SENSORS),
+            (0..SENSORS)
+                .map(|i| if i < SENSORS / 2 { 1. } else { -1. })
+                .collect(),
+            (0..SENSORS)
+                .map(|i| if i % 4 < 2 { -1. } else { 1. })
+                .collect(),
+        ];
+
+        let mut pcn = PCN::new(3, 8, SENSORS);
+        let mut rng = Seed(1).rng();
+        let indices = pcn.memorize(&patterns, &mut rng, 0.1, 200, 0.1, 20);
+
+        assert_eq!(pcn.codebook().len(), 3);
+        assert_eq!(pcn.memory_prior().count(), 3 * 20);
+
+        let mask: Vec<bool> = (0..SENSORS).map(|i| i < SENSORS / 2).collect();
+
+        for (k, p) in patterns.iter().enumerate() {
+            let cue: Vec<f64> = (0..SENSORS)
+                .map(|i| if mask[i] { p[i] } else { 0. })
+                .collect();
+            let recall = pcn.recall(&cue, &mask, 0.1, 500);
+
+            assert_eq!(recall.index, indices[k]);
+            for i in 0..SENSORS {
+                assert_eq!(recall.completion.sensors[i].signum(), p[i]);
+            }
         }
+    }
 
-        assert!(pcn.global_error() < 0.1);
+    #[test]
+    fn snapshots_track_learning() {
+        let s: Vec<f64> = alternating(16);
+        let m: Vec<f64> = alternating(8);
+
+        let p1 = trained_pcn(&s, &m);
+        let mut p2 = trained_pcn(&s, &m);
+
+        assert_eq!(p1.snapshot(), p2.snapshot());
+        assert_eq!(p1.layers(), 3);
+        assert_eq!(p1.neurons(0), p1.sensors());
+        assert_eq!(p1.weights(1).rows(), 8);
+
+        let before = p2.snapshot();
+        p2.inference(&m, &s, 0.1, 10);
+        p2.learn(0.1);
+
+        assert!(before != p2.snapshot());
+
+        let learned = p2.snapshot();
+        p2.learn_recognition(0.1);
+        assert!(learned.recognition != p2.snapshot().recognition);
+        assert!(!learned.approx_eq(&p2.snapshot(), 0.));
+        assert!(before.approx_eq(&p1.snapshot(), 0.));
+        assert!(!before.approx_eq(&p2.snapshot(), 0.1));
     }
 
     #[test]
-    fn will_not_recall_pattern_it_hasnt_learned() {
-        const SENSORS: usize = 16;
-        const MEMORY: usize = 8;
+    fn display_is_formatted_with_precision() {
+        let pcn: PCN = PCN::new(2, 1, 2);
+        let text = format!("{:.1}", pcn);
+
+        assert!(text.starts_with("Layer 0\n - neurons: 0.0 0.0\n"));
+        assert!(text.contains("Layer 0 weights\n0.0\n0.0"));
+    }
+
+    #[test]
+    fn cold_start_does_not_depend_on_history() {
+        let s: Vec<f64> = alternating(16);
+        let m: Vec<f64> = alternating(8);
+
+        let mut p1 = trained_pcn(&s, &m);
+        let mut p2 = trained_pcn(&s, &m);
+
+        p2.generate(&[0.5; 8]);
+
+        p1.infer(InferenceStart::Cold, &m, &s, 0.1, 10);
+        p2.infer(InferenceStart::Cold, &m, &s, 0.1, 10);
 
-        let mut pcn = PCN::new(2, MEMORY, SENSORS);
-        let mut s = vec![0.; SENSORS];
-        let mut m = vec![0.; MEMORY];
+        assert_eq!(p1.snapshot(), p2.snapshot());
+    }
+
+    #[test]
+    fn warm_start_continues_from_cache() {
+        let s: Vec<f64> = alternating(16);
+        let m: Vec<f64> = alternating(8);
+
+        let mut p1 = trained_pcn(&s, &m);
+        let mut p2 = trained_pcn(&s, &m);
+
+        p1.infer(InferenceStart::Cold, &m, &s, 0.1, 10);
+        p1.inference(&m, &s, 0.1, 10);
+
+        p2.infer(InferenceStart::Cold, &m, &s, 0.1, 10);
+        p2.generate(&[0.5; 8]);
+        p2.infer(InferenceStart::Warm, &m, &s, 0.1, 10);
+
+        // Only p2 cached the state after the second run.
+        let (a, b) = (p1.snapshot(), p2.snapshot());
+        assert_eq!(a.neurons, b.neurons);
+        assert_eq!(a.errors, b.errors);
+        assert_eq!(b.cached.as_deref(), Some(&b.neurons[1..]));
+    }
+
+    #[test]
+    fn amortised_start_needs_few_steps() {
+        const STEPS: usize = 3;
+
+        let frames: Vec<Vec<f64>> = (0..20)
+            .map(|t| {
+                (0..16)
+                    .map(|i| ((i as f64) * 0.4 + (t as f64) * 0.1).sin())
+                    .collect()
+            })
+            .collect();
+        let m = vec![0.; 8];
 
-        for i in 0..SENSORS {
-            s[i] = if i < (SENSORS / 2) { 1. } else { -1. };
+        let mut pcn = PCN::new(3, 8, 16);
+
+        for _e in 0..20 {
+            for frame in &frames {
+                pcn.infer(InferenceStart::Cold, &m, frame, 0.1, 200);
+                pcn.learn(0.05);
+                pcn.learn_recognition(0.05);
+            }
         }
-        for i in 0..MEMORY {
-            m[i] = if i % 2 == 0 { 1. } else { -1. };
+
+        let mut cold = 0.;
+        let mut amortised = 0.;
+
+        for frame in &frames {
+            pcn.infer(InferenceStart::Cold, &m, frame, 0.1, STEPS);
+            pcn.error(frame);
+            cold += pcn.global_error();
+
+            pcn.infer(InferenceStart::Amortised, &m, frame, 0.1, STEPS);
+            pcn.error(frame);
+            amortised += pcn.global_error();
+        }
+
+        assert!(amortised < cold);
+    }
+
+    #[test]
+    fn can_learn_a_pattern_f32() {
+        let s: Vec<f32> = alternating(16);
+        let m: Vec<f32> = alternating(8);
+
+        let mut pcn: PCN<f32> = PCN::new(3, 8, 16);
+
+        for _i in 0..10 {
+            pcn.inference(&m, &s, 0.1, 1000);
+            pcn.learn(0.1);
         }
 
-        const T: usize = 1000;
-        const U: usize = 10;
-        const GAMMA: f64 = 0.1;
-        const ALPHA: f64 = 0.1;
+        let memory = pcn.memory().to_vec();
+        let generated = pcn.generate(&memory);
 
-        for _i in 0..U {
-            pcn.inference(&m, &s, GAMMA, T);
-            pcn.learn(ALPHA);
+        for i in 0..16 {
+            assert!((generated[i] - s[i]).abs() < 0.1);
         }
+    }
 
-        let mut pattern = vec![0.; SENSORS];
-        for i in 0..(SENSORS / 2) {
-            pattern[i] = if i % 2 == 0 { 1. } else { -1. };
+    #[test]
+    fn will_not_recall_pattern_it_hasnt_learned() {
+        let mut pcn = PCN::new(2, 8, 16);
+        let s: Vec<f64> = (0..16).map(|i| if i < 8 { 1. } else { -1. }).collect();
+        let m = alternating(8);
+
+        for _i in 0..10 {
+            pcn.inference(&m, &s, 0.1, 1000);
+            pcn.learn(0.1);
         }
 
-        pcn.inference_with_sensors(&m, &pattern, GAMMA, T);
+        let pattern: Vec<f64> = alternating(16)
+            .iter()
+            .enumerate()
+            .map(|(i, v)| if i < 8 { *v } else { 0. })
+            .collect();
+
+        pcn.inference_with_sensors(&m, &pattern, 0.1, 1000);
 
         pcn.memory_mut().copy_from(&m);
         pcn.error(&s);
 
         assert!(pcn.global_error() >= 5.0);
     }
+
+    #[test]
+    fn try_variants_reject_malformed_input() {
+        assert!(matches!(
+            PCN::<f64>::try_new(1, 4, 4),
+            Err(HopfieldError::InvalidParameter { name: "layers", .. })
+        ));
+        assert!(PCN::<f64>::try_new(3, 0, 4).is_err());
+
+        let mut pcn: PCN = PCN::try_new(3, 2, 4).unwrap();
+        let before = pcn.snapshot();
+
+        assert_eq!(
+            pcn.try_inference(&[0.; 2], &[1.; 3], 0.1, 10),
+            Err(HopfieldError::DimensionMismatch {
+                what: "sensor pattern",
+                expected: 4,
+                found: 3
+            })
+        );
+        assert!(pcn
+            .try_inference(&[0.; 2], &[1., 1., f64::NAN, 1.], 0.1, 10)
+            .is_err());
+        assert!(pcn.try_inference(&[0.; 2], &[1.; 4], -0.1, 10).is_err());
+        assert!(pcn.try_learn(f64::INFINITY).is_err());
+        assert!(pcn.try_complete(&[1.; 4], &[true; 3], 0.1, 10).is_err());
+        assert_eq!(
+            pcn.try_recall(&[1.; 4], &[true; 4], 0.1, 10)
+                .map(|r| r.index),
+            Err(HopfieldError::EmptyMemory)
+        );
+        assert!(before.approx_eq(&pcn.snapshot(), 0.));
+
+        pcn.try_inference(&[0.; 2], &[1.; 4], 0.1, 10).unwrap();
+        pcn.try_learn(0.01).unwrap();
+        assert_eq!(pcn.learn_steps(), 1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let s: Vec<f64> = alternating(16);
+        let m: Vec<f64> = alternating(8);
+        let mut pcn = trained_pcn(&s, &m);
+
+        let json = serde_json::to_string(&pcn).unwrap();
+        let mut loaded: PCN = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(loaded.layers(), 3);
+        assert_eq!(loaded.learn_steps(), pcn.learn_steps());
+        assert_eq!(loaded.memory_prior().mean(), pcn.memory_prior().mean());
+        for l in 0..2 {
+            assert_eq!(loaded.weights(l), pcn.weights(l));
+        }
+        assert_eq!(loaded.generate(&m), pcn.generate(&m));
+
+        let broken = json.replacen("\"rows\":8", "\"rows\":7", 1);
+        assert!(serde_json::from_str::<PCN>(&broken).is_err());
+    }
 }
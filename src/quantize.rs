@@ -0,0 +1,409 @@
+//! Low precision synapses for classic Hopfield networks. Trained `SMatrix`
+//! weights can be quantised after training to a few bits per synapse, stored
+//! as `SMatrix<i8>` so recall can use `bitstate`, and networks can be trained
+//! directly with clipped Hebbian learning. `capacity_report` measures how
+//! many random patterns a network can still retrieve at each bit width.
+
+use crate::bitstate::{self, BitState, BitWeights};
+use crate::classic;
+use crate::error::{check_finite, check_len, HopfieldError};
+use crate::scalar::Scalar;
+use crate::smatrix::SMatrix;
+use rand::Rng;
+
+/// Weights quantised to integers: the original weight `w` is approximately
+/// `scale * q`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quantized {
+    pub weights: SMatrix<i8>,
+    pub scale: f64,
+    pub bits: u32,
+}
+
+/// The largest integer level used for `bits` bits per synapse. One bit
+/// stores only the sign; otherwise the levels are symmetric around zero, eg.
+/// `{-1, 0, 1}` for two bits. Panics unless `bits` is between 1 and 8.
+pub fn levels(bits: u32) -> i8 {
+    assert!((1..=8).contains(&bits), "bits must be between 1 and 8");
+
+    if bits == 1 {
+        1
+    } else {
+        ((1_i32 << (bits - 1)) - 1) as i8
+    }
+}
+
+/// Quantise `weights` uniformly to `bits` bits (1 to 8), scaled so the largest
+/// weight maps to the largest level. Self-couplings are set to zero. Panics
+/// unless `bits` is between 1 and 8.
+pub fn quantize<T: Scalar>(weights: &SMatrix<T>, bits: u32) -> Quantized {
+    let n = weights.rows();
+    let top = levels(bits);
+
+    let mut max = 0.;
+    for r in 0..n {
+        for c in (r + 1)..n {
+            max = f64::max(max, weights[(r, c)].to_f64().abs());
+        }
+    }
+
+    let scale = if max > 0. { max / top as f64 } else { 1. };
+
+    let q = SMatrix::from_fn2(n, &mut |r, c| {
+        let w = weights[(r, c)].to_f64();
+
+        if r == c {
+            0
+        } else if bits == 1 {
+            if w >= 0. {
+                1
+            } else {
+                -1
+            }
+        } else {
+            (w / scale).round().clamp(-top as f64, top as f64) as i8
+        }
+    });
+
+    Quantized {
+        weights: q,
+        scale,
+        bits,
+    }
+}
+
+fn check_bits(bits: u32) -> Result<(), HopfieldError> {
+    if !(1..=8).contains(&bits) {
+        return Err(HopfieldError::InvalidParameter {
+            name: "bits",
+            reason: "must be between 1 and 8",
+        });
+    }
+
+    Ok(())
+}
+
+/// Like `quantize`, but returns an error instead of panicking for a bit width
+/// outside 1 to 8.
+pub fn try_quantize<T: Scalar>(
+    weights: &SMatrix<T>,
+    bits: u32,
+) -> Result<Quantized, HopfieldError> {
+    check_bits(bits)?;
+
+    Ok(quantize(weights, bits))
+}
+
+impl Quantized {
+    /// The weights converted back to `T`.
+    pub fn dequantize<T: Scalar>(&self) -> SMatrix<T> {
+        let scale = self.scale;
+
+        SMatrix::from_fn2(self.weights.rows(), &mut |r, c| {
+            T::from_f64(self.weights[(r, c)] as f64 * scale)
+        })
+    }
+}
+
+/// Clipped Hebbian learning: add `p_i * p_j` to each weight and clip the
+/// result to `[-bound, bound]`. With `bound = 1` the synapses are ternary;
+/// `levels(bits)` gives the bound for a given number of bits. Old patterns
+/// are gradually overwritten by new ones instead of causing a catastrophic
+/// loss of all memories when the network is overloaded. Panics if `bound` is
+/// not positive or `pattern` does not have one value per neuron.
+pub fn clipped_hebb_learn<T: Scalar>(weights: &mut SMatrix<i8>, pattern: &[T], bound: i8) {
+    assert_eq!(weights.rows(), pattern.len(), "pattern size mismatch");
+    assert!(bound > 0, "bound must be positive");
+
+    let sign: Vec<i8> = pattern
+        .iter()
+        .map(|v| if *v >= T::ZERO { 1 } else { -1 })
+        .collect();
+
+    weights.for_each_upper_mut(|r, c, w| {
+        if r != c {
+            let v = *w as i16 + (sign[r] * sign[c]) as i16;
+            *w = v.clamp(-bound as i16, bound as i16) as i8;
+        }
+    });
+}
+
+/// Like `clipped_hebb_learn`, but returns an error instead of panicking for a
+/// bound that is not positive or a pattern of the wrong size.
+pub fn try_clipped_hebb_learn<T: Scalar>(
+    weights: &mut SMatrix<i8>,
+    pattern: &[T],
+    bound: i8,
+) -> Result<(), HopfieldError> {
+    check_len("pattern", weights.rows(), pattern.len())?;
+
+    if bound <= 0 {
+        return Err(HopfieldError::InvalidParameter {
+            name: "bound",
+            reason: "must be positive",
+        });
+    }
+
+    clipped_hebb_learn(weights, pattern, bound);
+    Ok(())
+}
+
+/// Mean overlap between each pattern and the state reached by running up to
+/// `steps` synchronous updates (with zero bias) starting from it.
+pub fn retrieval_overlap<W: BitWeights>(weights: &W, patterns: &[BitState], steps: usize) -> f64 {
+    let bias = vec![0; weights.rows()];
+
+    mean_retrieval(patterns, steps, |input, output| {
+        bitstate::update_state_sync(weights, &bias, input, output)
+    })
+}
+
+fn mean_retrieval<F: FnMut(&BitState, &mut BitState)>(
+    patterns: &[BitState],
+    steps: usize,
+    mut update: F,
+) -> f64 {
+    if patterns.is_empty() {
+        return 1.;
+    }
+
+    let mut acc = 0.;
+
+    for p in patterns {
+        let mut state = p.clone();
+        let mut next = p.clone();
+
+        for _i in 0..steps {
+            update(&state, &mut next);
+
+            if next == state {
+                break;
+            }
+
+            std::mem::swap(&mut state, &mut next);
+        }
+
+        acc += state.overlap(p);
+    }
+
+    acc / patterns.len() as f64
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CapacityReport {
+    /// Bits per synapse, or `None` for the unquantised `f64` weights.
+    pub bits: Option<u32>,
+    /// The largest number of patterns retrieved with a mean overlap of at
+    /// least the requested minimum.
+    pub capacity: usize,
+    /// `capacity` relative to the unquantised network.
+    pub relative: f64,
+}
+
+/// Measure the capacity of a network of `neurons` neurons trained with Hebbian
+/// learning on random patterns, both at full precision and after quantising
+/// the weights to each of the given bit widths. Patterns are added one at a
+/// time until the mean retrieval overlap of every network falls below
+/// `min_overlap`.
+pub fn capacity_report<R: Rng>(
+    neurons: usize,
+    bits: &[u32],
+    min_overlap: f64,
+    rng: &mut R,
+) -> Vec<CapacityReport> {
+    const STEPS: usize = 10;
+
+    let mut weights = SMatrix::new(neurons, 0.);
+    let mut patterns = Vec::new();
+    let mut capacity = vec![0; bits.len() + 1];
+    let mut failed = vec![false; bits.len() + 1];
+    let bias = vec![0.; neurons];
+
+    while failed.iter().any(|f| !f) && patterns.len() < neurons {
+        let p: Vec<f64> = (0..neurons)
+            .map(|_i| if rng.random_bool(0.5) { 1. } else { -1. })
+            .collect();
+
+        classic::hebb_learn(&mut weights, &p);
+        patterns.push(BitState::from_slice(&p));
+
+        for k in 0..=bits.len() {
+            if failed[k] {
+                continue;
+            }
+
+            let overlap = if k == 0 {
+                let mut input = vec![0.; neurons];
+                let mut output = vec![0.; neurons];
+
+                mean_retrieval(&patterns, STEPS, |s, next| {
+                    s.copy_to(&mut input[..]);
+                    classic::update_state_sync(&weights, &bias, &input, &mut output);
+                    *next = BitState::from_slice(&output);
+                })
+            } else {
+                let q = quantize(&weights, bits[k - 1]);
+
+                retrieval_overlap(&q.weights, &patterns, STEPS)
+            };
+
+            if overlap >= min_overlap {
+                capacity[k] = patterns.len();
+            } else {
+                failed[k] = true;
+            }
+        }
+    }
+
+    let full = capacity[0].max(1) as f64;
+
+    (0..=bits.len())
+        .map(|k| CapacityReport {
+            bits: if k == 0 { None } else { Some(bits[k - 1]) },
+            capacity: capacity[k],
+            relative: capacity[k] as f64 / full,
+        })
+        .collect()
+}
+
+/// Like `capacity_report`, but checks that every bit width is between 1 and
+/// 8 and that `min_overlap` is finite.
+pub fn try_capacity_report<R: Rng>(
+    neurons: usize,
+    bits: &[u32],
+    min_overlap: f64,
+    rng: &mut R,
+) -> Result<Vec<CapacityReport>, HopfieldError> {
+    for b in bits {
+        check_bits(*b)?;
+    }
+    check_finite("min_overlap", &[min_overlap])?;
+
+    Ok(capacity_report(neurons, bits, min_overlap, rng))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seed::Seed;
+
+    #[test]
+    fn quantize_round_trip() {
+        let w = SMatrix::from_fn2(4, &mut |r, c| {
+            if r == c {
+                0.5
+            } else {
+                (c as f64 - 2. * r as f64) * 0.1
+            }
+        });
+
+        assert_eq!(levels(1), 1);
+        assert_eq!(levels(2), 1);
+        assert_eq!(levels(4), 7);
+        assert_eq!(levels(8), 127);
+
+        let q8 = quantize(&w, 8);
+        let back: SMatrix<f64> = q8.dequantize();
+
+        for r in 0..4 {
+            for c in 0..4 {
+                if r == c {
+                    assert_eq!(back[(r, c)], 0.);
+                } else {
+                    assert!((back[(r, c)] - w[(r, c)]).abs() <= q8.scale / 2.);
+                }
+            }
+        }
+
+        let q1 = quantize(&w, 1);
+        assert_eq!(q1.weights[(0, 1)], 1);
+        assert_eq!(q1.weights[(3, 2)], -1);
+        assert_eq!(q1.weights[(2, 2)], 0);
+    }
+
+    #[test]
+    fn clipped_learning_stays_in_bounds() {
+        const N: usize = 50;
+
+        let p: Vec<f64> = (0..N).map(|i| if i % 3 == 0 { 1. } else { -1. }).collect();
+        let mut weights = SMatrix::new(N, 0_i8);
+
+        for _i in 0..5 {
+            clipped_hebb_learn(&mut weights, &p, 2);
+        }
+
+        assert_eq!(weights[(0, 3)], 2);
+        assert_eq!(weights[(0, 1)], -2);
+        assert_eq!(weights[(4, 4)], 0);
+        assert_eq!(
+            retrieval_overlap(&weights, &[BitState::from_slice(&p)], 5),
+            1.
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_bits_and_bounds() {
+        let w = SMatrix::from_fn2(3, &mut |r, c| (r + c) as f64);
+        let mut weights = SMatrix::new(3, 0_i8);
+        let bits = HopfieldError::InvalidParameter {
+            name: "bits",
+            reason: "must be between 1 and 8",
+        };
+        let bound = HopfieldError::InvalidParameter {
+            name: "bound",
+            reason: "must be positive",
+        };
+
+        assert_eq!(try_quantize(&w, 0), Err(bits.clone()));
+        assert_eq!(try_quantize(&w, 9), Err(bits));
+        assert_eq!(try_quantize(&w, 2).unwrap(), quantize(&w, 2));
+
+        assert_eq!(
+            try_clipped_hebb_learn(&mut weights, &[1., -1., 1.], 0),
+            Err(bound.clone())
+        );
+        assert_eq!(
+            try_clipped_hebb_learn(&mut weights, &[1., -1., 1.], -1),
+            Err(bound)
+        );
+        assert!(try_clipped_hebb_learn(&mut weights, &[1., -1.], 1).is_err());
+        assert_eq!(weights, SMatrix::new(3, 0));
+
+        try_clipped_hebb_learn(&mut weights, &[1., -1., 1.], 1).unwrap();
+        assert_eq!(weights[(0, 2)], 1);
+
+        let mut rng = Seed(1).rng();
+        assert!(try_capacity_report(8, &[4, 0], 0.9, &mut rng).is_err());
+        assert!(try_capacity_report(8, &[4], f64::NAN, &mut rng).is_err());
+        assert_eq!(
+            try_capacity_report(8, &[4], 0.9, &mut rng).unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "bits must be between 1 and 8")]
+    fn quantize_panics_for_zero_bits() {
+        quantize(&SMatrix::new(2, 1.), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "bits must be between 1 and 8")]
+    fn quantize_panics_for_nine_bits() {
+        quantize(&SMatrix::new(2, 1.), 9);
+    }
+
+    #[test]
+    fn fewer_bits_store_fewer_patterns() {
+        let mut rng = Seed(11).rng();
+        let report = capacity_report(64, &[1, 8], 0.98, &mut rng);
+
+        assert_eq!(report[0].bits, None);
+        assert_eq!(report[0].relative, 1.);
+        assert!(report[0].capacity >= 3);
+        assert!(report[1].capacity <= report[2].capacity);
+        assert!(report[2].relative > 0.7);
+    }
+}
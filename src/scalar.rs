@@ -0,0 +1,180 @@
+//! The numeric type used for states and weights. The models in this crate are
+//! generic over `Scalar`, which is implemented for `f32` and `f64` (the
+//! default everywhere) and, with the `half` feature, for the half precision
+//! `half::f16` storage type.
+
+use crate::simd::Kernel;
+use crate::{MaybeSend, MaybeSync};
+use std::fmt::{Debug, Display};
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+pub trait Scalar:
+    Kernel
+    + Default
+    + PartialOrd
+    + Debug
+    + Display
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+    + DivAssign
+    + MaybeSend
+    + MaybeSync
+    + 'static
+{
+    const ZERO: Self;
+    const ONE: Self;
+    const EPSILON: Self;
+    const MAX: Self;
+
+    fn from_f64(v: f64) -> Self;
+    fn to_f64(self) -> f64;
+
+    fn abs(self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn tanh(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn cos(self) -> Self;
+    fn powi(self, n: i32) -> Self;
+
+    fn from_usize(v: usize) -> Self {
+        Self::from_f64(v as f64)
+    }
+}
+
+macro_rules! impl_scalar {
+    ($t:ident) => {
+        impl Scalar for $t {
+            const ZERO: Self = 0.;
+            const ONE: Self = 1.;
+            const EPSILON: Self = $t::EPSILON;
+            const MAX: Self = $t::MAX;
+
+            fn from_f64(v: f64) -> Self {
+                v as $t
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn abs(self) -> Self {
+                $t::abs(self)
+            }
+
+            fn exp(self) -> Self {
+                $t::exp(self)
+            }
+
+            fn ln(self) -> Self {
+                $t::ln(self)
+            }
+
+            fn tanh(self) -> Self {
+                $t::tanh(self)
+            }
+
+            fn sqrt(self) -> Self {
+                $t::sqrt(self)
+            }
+
+            fn cos(self) -> Self {
+                $t::cos(self)
+            }
+
+            fn powi(self, n: i32) -> Self {
+                $t::powi(self, n)
+            }
+        }
+    };
+}
+
+impl_scalar!(f32);
+impl_scalar!(f64);
+
+// Half precision is a storage format: arithmetic goes through `f32`.
+#[cfg(feature = "half")]
+impl Scalar for half::f16 {
+    const ZERO: Self = half::f16::ZERO;
+    const ONE: Self = half::f16::ONE;
+    const EPSILON: Self = half::f16::EPSILON;
+    const MAX: Self = half::f16::MAX;
+
+    fn from_f64(v: f64) -> Self {
+        half::f16::from_f64(v)
+    }
+
+    fn to_f64(self) -> f64 {
+        half::f16::to_f64(self)
+    }
+
+    fn abs(self) -> Self {
+        half::f16::from_f32(self.to_f32().abs())
+    }
+
+    fn exp(self) -> Self {
+        half::f16::from_f32(self.to_f32().exp())
+    }
+
+    fn ln(self) -> Self {
+        half::f16::from_f32(self.to_f32().ln())
+    }
+
+    fn tanh(self) -> Self {
+        half::f16::from_f32(self.to_f32().tanh())
+    }
+
+    fn sqrt(self) -> Self {
+        half::f16::from_f32(self.to_f32().sqrt())
+    }
+
+    fn cos(self) -> Self {
+        half::f16::from_f32(self.to_f32().cos())
+    }
+
+    fn powi(self, n: i32) -> Self {
+        half::f16::from_f32(self.to_f32().powi(n))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conversions_round_trip() {
+        assert_eq!(f32::from_f64(0.5).to_f64(), 0.5);
+        assert_eq!(f64::from_usize(3), 3.);
+        assert_eq!(<f32 as Scalar>::tanh(0.), 0.);
+    }
+
+    #[cfg(feature = "half")]
+    #[test]
+    fn half_precision_modern_recall() {
+        use crate::dmatrix::DMatrix;
+        use crate::modern;
+        use half::f16;
+
+        let p: Vec<f16> = [1., 1., -1., -1., 1., 1., -1., -1.]
+            .iter()
+            .map(|v| f16::from_f64(*v))
+            .collect();
+        let a: Vec<f16> = [1., 0., -1., 0., 0., 1., 0., 0.]
+            .iter()
+            .map(|v| f16::from_f64(*v))
+            .collect();
+        let mut m = DMatrix::new(1, 8, f16::ZERO);
+        let mut output = vec![f16::ZERO; 8];
+
+        modern::learn(&mut m, &p);
+        modern::update_state_sync(&m, &a, &mut output);
+
+        assert_eq!(output, p);
+    }
+}
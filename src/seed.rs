@@ -0,0 +1,97 @@
+//! Reproducible random numbers. Every stochastic function in the crate takes
+//! the generator it draws from as `&mut impl Rng` (weight initialisation,
+//! noise and corruption, asynchronous update order, annealing, PCN codes and
+//! weights) and never creates one of its own, so a run is determined by the
+//! generators passed in. `Seed` makes such generators from a number.
+//!
+//! The generators are ChaCha8, whose output for a given seed is fixed across
+//! platforms and versions of `rand`, unlike `StdRng`. Calls that draw the same
+//! values in the same order therefore give identical results on every run:
+//!
+//! ```
+//! use hopfield::classic;
+//! use hopfield::pcn::PCN;
+//! use hopfield::seed::Seed;
+//! use hopfield::smatrix::SMatrix;
+//! use hopfield::state::State;
+//!
+//! fn run(seed: Seed) -> (SMatrix<f64>, Vec<f64>, Vec<f64>) {
+//!     let mut rng = seed.rng();
+//!     let mut weights = SMatrix::new(16, 0.);
+//!     let mut state = vec![0.; 16];
+//!     let mut pcn: PCN = PCN::new(2, 4, 16);
+//!
+//!     classic::initialize_weights(&mut weights, &mut rng, 0.5);
+//!     state.add_noise(&mut rng, 1.);
+//!     classic::anneal(&weights, &[0.; 16], &mut state, [2., 1., 0.5, 0.], &mut rng);
+//!     pcn.initialize_weights(&mut rng, 0.1);
+//!
+//!     (weights, state, pcn.generate(&[1., -1., 1., -1.]))
+//! }
+//!
+//! assert_eq!(run(Seed(7)), run(Seed(7)));
+//! assert_ne!(run(Seed(7)).0, run(Seed(8)).0);
+//! ```
+//!
+//! Work that is split into independent parts, possibly run in parallel, should
+//! give each part its own generator from `Seed::child`, so the result does not
+//! depend on the order in which the parts run.
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+
+/// The generator made by `Seed::rng`.
+pub type SeedRng = ChaCha8Rng;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Seed(pub u64);
+
+// A SplitMix64 step, used to derive independent seeds.
+fn mix(seed: u64, v: u64) -> u64 {
+    let mut z = seed ^ v.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl Seed {
+    /// A new generator, which always produces the same values for the same
+    /// seed.
+    pub fn rng(self) -> SeedRng {
+        SeedRng::seed_from_u64(self.0)
+    }
+
+    /// The seed for independent part `index` of a computation seeded with
+    /// `self`. Children of different indices, and of different seeds, give
+    /// unrelated generators.
+    ///
+    /// ```
+    /// use hopfield::seed::Seed;
+    /// use rand::Rng;
+    ///
+    /// let seed = Seed(1);
+    /// let a: Vec<u32> = (0..4).map(|t| seed.child(t).rng().random()).collect();
+    /// let b: Vec<u32> = (0..4).rev().map(|t| seed.child(t).rng().random()).collect();
+    ///
+    /// assert_eq!(a, b.into_iter().rev().collect::<Vec<u32>>());
+    /// assert_ne!(seed.child(0), seed.child(1));
+    /// ```
+    pub fn child(self, index: u64) -> Seed {
+        Seed(mix(self.0, index))
+    }
+}
+
+impl From<u64> for Seed {
+    fn from(seed: u64) -> Self {
+        Seed(seed)
+    }
+}
+
+impl Display for Seed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -0,0 +1,253 @@
+//! Sequence memory with asymmetric weights (temporal association). Next to the
+//! symmetric Hebbian weights `J = 1/N sum_m x^m x^m^T`, which make each stored
+//! pattern an attractor, the network has asymmetric transition weights
+//! `J1 = 1/N sum_m x^(m+1) x^m^T` that push the state from a pattern to the
+//! next one. The local field of neuron `i` is
+//!
+//! `h_i(t) = sum_j J_ij s_j(t) + strength * sum_j J1_ij s_j(t - delay)`
+//!
+//! and all neurons are updated synchronously. Without a delay the state moves
+//! to the next pattern at every step. With a delay (the delayed synapses of
+//! Kleinfeld and of Sompolinsky and Kanter) the symmetric term holds each
+//! pattern until the delayed state has caught up with it, so every pattern
+//! lasts `delay + 1` steps. Either way `strength` must be larger than 1 for
+//! the transition term to overcome the symmetric one. Without a delay the
+//! symmetric term only competes with the transition term, so a larger
+//! strength makes the replay more robust to noise.
+//!
+//! See `modern::update_sequence_sync` for the modern network counterpart.
+
+use crate::classic;
+use crate::dmatrix::DMatrix;
+use crate::error::{check_rate, check_vec, HopfieldError};
+use crate::scalar::Scalar;
+use crate::smatrix::SMatrix;
+use std::collections::VecDeque;
+
+pub struct SequenceNetwork<T = f64> {
+    weights: SMatrix<T>,
+    transition: DMatrix<T>,
+    strength: T,
+    delay: usize,
+    // The last `delay + 1` states, oldest first.
+    history: VecDeque<Vec<T>>,
+}
+
+impl<T: Scalar> SequenceNetwork<T> {
+    /// A network of `n` neurons without stored sequences, in the all zeros
+    /// state.
+    pub fn new(n: usize, strength: T, delay: usize) -> Self {
+        Self {
+            weights: SMatrix::new(n, T::ZERO),
+            transition: DMatrix::new(n, n, T::ZERO),
+            strength,
+            delay,
+            history: (0..=delay).map(|_| vec![T::ZERO; n]).collect(),
+        }
+    }
+
+    pub fn neurons(&self) -> usize {
+        self.weights.rows()
+    }
+
+    pub fn delay(&self) -> usize {
+        self.delay
+    }
+
+    /// The symmetric weights.
+    pub fn weights(&self) -> &SMatrix<T> {
+        &self.weights
+    }
+
+    /// The asymmetric transition weights.
+    pub fn transition(&self) -> &DMatrix<T> {
+        &self.transition
+    }
+
+    pub fn state(&self) -> &[T] {
+        self.history.back().unwrap()
+    }
+
+    /// Store `sequence`. If `cyclic` is set the last pattern is followed by
+    /// the first, otherwise the last pattern is a fixed point. Several
+    /// sequences can be stored as long as they share no patterns.
+    pub fn learn<P: AsRef<[T]>>(&mut self, sequence: &[P], cyclic: bool) {
+        let n_inv = T::ONE / T::from_usize(self.neurons());
+
+        for p in sequence {
+            classic::hebb_learn(&mut self.weights, p.as_ref());
+        }
+
+        let pairs = if cyclic && sequence.len() > 1 {
+            sequence.len()
+        } else {
+            sequence.len().saturating_sub(1)
+        };
+
+        for k in 0..pairs {
+            let from = sequence[k].as_ref();
+            let to = sequence[(k + 1) % sequence.len()].as_ref();
+
+            for r in 0..to.len() {
+                let v = n_inv * to[r];
+
+                for c in 0..from.len() {
+                    self.transition[(r, c)] += v * from[c];
+                }
+            }
+        }
+    }
+
+    /// Set the state, and all the delayed states, to `cue`.
+    pub fn reset(&mut self, cue: &[T]) {
+        debug_assert_eq!(cue.len(), self.neurons());
+
+        for s in &mut self.history {
+            s.copy_from_slice(cue);
+        }
+    }
+
+    /// One synchronous update of all neurons. Returns the new state.
+    pub fn step(&mut self) -> &[T] {
+        let n = self.neurons();
+        let mut field = vec![T::ZERO; n];
+        let mut push = vec![T::ZERO; n];
+
+        self.weights.mul_vec(self.state(), &mut field);
+        self.transition.mul_vec(&self.history[0], &mut push);
+
+        // Reuse the oldest state for the new one.
+        let mut next = self.history.pop_front().unwrap();
+
+        for i in 0..n {
+            next[i] = if field[i] + self.strength * push[i] >= T::ZERO {
+                T::ONE
+            } else {
+                -T::ONE
+            };
+        }
+
+        self.history.push_back(next);
+        self.state()
+    }
+
+    /// Start from `cue` and return the states after each of `steps` updates.
+    pub fn replay(&mut self, cue: &[T], steps: usize) -> Vec<Vec<T>> {
+        self.reset(cue);
+
+        (0..steps).map(|_| self.step().to_vec()).collect()
+    }
+}
+
+// Fallible variants, which leave the network unchanged on error.
+impl<T: Scalar> SequenceNetwork<T> {
+    /// Like `new`, but checks that `strength` is finite and positive.
+    pub fn try_new(n: usize, strength: T, delay: usize) -> Result<Self, HopfieldError> {
+        check_rate("strength", strength)?;
+
+        Ok(Self::new(n, strength, delay))
+    }
+
+    /// Like `learn`, but checks the sizes of and values in the patterns.
+    pub fn try_learn<P: AsRef<[T]>>(
+        &mut self,
+        sequence: &[P],
+        cyclic: bool,
+    ) -> Result<(), HopfieldError> {
+        for p in sequence {
+            check_vec("pattern", self.neurons(), p.as_ref())?;
+        }
+
+        self.learn(sequence, cyclic);
+        Ok(())
+    }
+
+    pub fn try_reset(&mut self, cue: &[T]) -> Result<(), HopfieldError> {
+        check_vec("cue", self.neurons(), cue)?;
+
+        self.reset(cue);
+        Ok(())
+    }
+
+    pub fn try_replay(&mut self, cue: &[T], steps: usize) -> Result<Vec<Vec<T>>, HopfieldError> {
+        check_vec("cue", self.neurons(), cue)?;
+
+        Ok(self.replay(cue, steps))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::metrics;
+    use crate::seed::Seed;
+    use crate::state::State;
+    use rand::Rng;
+
+    const N: usize = 100;
+
+    fn random_sequence(len: usize, seed: u64) -> Vec<Vec<f64>> {
+        let mut rng = Seed(seed).rng();
+
+        (0..len)
+            .map(|_| {
+                (0..N)
+                    .map(|_| if rng.random_bool(0.5) { 1. } else { -1. })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // The pattern closest to each state.
+    fn decode(sequence: &[Vec<f64>], states: &[Vec<f64>]) -> Vec<usize> {
+        states
+            .iter()
+            .map(|s| metrics::best_match(sequence, s).unwrap().0)
+            .collect()
+    }
+
+    #[test]
+    fn replays_sequence_from_noisy_cue() {
+        let sequence = random_sequence(5, 1);
+        let mut network = SequenceNetwork::new(N, 4., 0);
+        network.learn(&sequence, false);
+
+        let mut cue = sequence[0].clone();
+        cue.flip_random(&mut Seed(2).rng(), 10);
+        let states = network.replay(&cue, 6);
+
+        assert_eq!(decode(&sequence, &states), vec![1, 2, 3, 4, 4, 4]);
+        assert_eq!(states[3], sequence[4]);
+    }
+
+    #[test]
+    fn delayed_synapses_hold_each_pattern() {
+        let sequence = random_sequence(4, 3);
+        let mut network = SequenceNetwork::new(N, 2., 2);
+        network.learn(&sequence, true);
+
+        let states = network.replay(&sequence[0], 12);
+
+        assert_eq!(
+            decode(&sequence, &states),
+            vec![1, 1, 1, 2, 2, 2, 3, 3, 3, 0, 0, 0]
+        );
+        for s in &states {
+            assert_eq!(metrics::best_match(&sequence, s).unwrap().1, 1.);
+        }
+    }
+
+    #[test]
+    fn try_variants_check_patterns() {
+        let sequence = random_sequence(2, 4);
+        let mut network = SequenceNetwork::new(N, 2., 1);
+
+        assert!(SequenceNetwork::try_new(N, -1., 0).is_err());
+        assert!(network.try_learn(&[&sequence[0][1..]], false).is_err());
+        assert_eq!(network.transition().as_slice(), &[0.; N * N][..]);
+        assert!(network.try_replay(&[0.; 3], 1).is_err());
+
+        network.try_learn(&sequence, false).unwrap();
+        assert_eq!(network.try_replay(&sequence[0], 3).unwrap()[2], sequence[1]);
+    }
+}
@@ -0,0 +1,274 @@
+//! Vector kernels used by the dense matrix operations. For `f32` and `f64` on
+//! x86-64 the kernels use AVX when the CPU supports it (detected at runtime),
+//! otherwise they fall back to scalar loops with several independent
+//! accumulators. The vectorised kernels sum in a different order than a plain
+//! loop, so results may differ in the last bits.
+
+use std::ops::{AddAssign, Mul};
+
+/// Slice kernels for a numeric type. The defaults are portable scalar loops;
+/// `f32` and `f64` override them with vectorised versions.
+pub trait Kernel: Mul<Output = Self> + AddAssign + Default + Copy {
+    /// The dot product of `a` and `b`, which must have the same length.
+    fn dot(a: &[Self], b: &[Self]) -> Self {
+        dot_scalar(a, b)
+    }
+
+    /// `y += alpha * x`, where `x` and `y` must have the same length.
+    fn axpy(alpha: Self, x: &[Self], y: &mut [Self]) {
+        axpy_scalar(alpha, x, y)
+    }
+}
+
+fn dot_scalar<T: Mul<Output = T> + AddAssign + Default + Copy>(a: &[T], b: &[T]) -> T {
+    debug_assert_eq!(a.len(), b.len());
+
+    let mut acc = [T::default(); 4];
+    let chunks = a.len() / 4;
+
+    for i in 0..chunks {
+        for j in 0..4 {
+            acc[j] += a[4 * i + j] * b[4 * i + j];
+        }
+    }
+
+    for i in (4 * chunks)..a.len() {
+        acc[0] += a[i] * b[i];
+    }
+
+    acc[0] += acc[1];
+    acc[2] += acc[3];
+    acc[0] += acc[2];
+    acc[0]
+}
+
+fn axpy_scalar<T: Mul<Output = T> + AddAssign + Copy>(alpha: T, x: &[T], y: &mut [T]) {
+    debug_assert_eq!(x.len(), y.len());
+
+    for i in 0..y.len() {
+        y[i] += alpha * x[i];
+    }
+}
+
+macro_rules! impl_scalar_kernel {
+    ($($t:ty),*) => {
+        $(impl Kernel for $t {})*
+    };
+}
+
+impl_scalar_kernel!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+#[cfg(feature = "half")]
+impl Kernel for half::f16 {}
+
+#[cfg(target_arch = "x86_64")]
+mod avx {
+    use std::arch::x86_64::*;
+
+    // The functions below must only be called when AVX is available, which
+    // is checked by the callers with `is_x86_feature_detected!`.
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn dot_f64(a: &[f64], b: &[f64]) -> f64 {
+        let n = a.len().min(b.len());
+        let chunks = n / 8;
+        let pa = a.as_ptr();
+        let pb = b.as_ptr();
+
+        let mut acc0 = _mm256_setzero_pd();
+        let mut acc1 = _mm256_setzero_pd();
+
+        for i in 0..chunks {
+            let k = 8 * i;
+            acc0 = _mm256_add_pd(
+                acc0,
+                _mm256_mul_pd(_mm256_loadu_pd(pa.add(k)), _mm256_loadu_pd(pb.add(k))),
+            );
+            acc1 = _mm256_add_pd(
+                acc1,
+                _mm256_mul_pd(
+                    _mm256_loadu_pd(pa.add(k + 4)),
+                    _mm256_loadu_pd(pb.add(k + 4)),
+                ),
+            );
+        }
+
+        let mut lanes = [0.; 4];
+        _mm256_storeu_pd(lanes.as_mut_ptr(), _mm256_add_pd(acc0, acc1));
+
+        let mut acc = (lanes[0] + lanes[1]) + (lanes[2] + lanes[3]);
+        for i in (8 * chunks)..n {
+            acc += a[i] * b[i];
+        }
+
+        acc
+    }
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn dot_f32(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len().min(b.len());
+        let chunks = n / 16;
+        let pa = a.as_ptr();
+        let pb = b.as_ptr();
+
+        let mut acc0 = _mm256_setzero_ps();
+        let mut acc1 = _mm256_setzero_ps();
+
+        for i in 0..chunks {
+            let k = 16 * i;
+            acc0 = _mm256_add_ps(
+                acc0,
+                _mm256_mul_ps(_mm256_loadu_ps(pa.add(k)), _mm256_loadu_ps(pb.add(k))),
+            );
+            acc1 = _mm256_add_ps(
+                acc1,
+                _mm256_mul_ps(
+                    _mm256_loadu_ps(pa.add(k + 8)),
+                    _mm256_loadu_ps(pb.add(k + 8)),
+                ),
+            );
+        }
+
+        let mut lanes = [0.; 8];
+        _mm256_storeu_ps(lanes.as_mut_ptr(), _mm256_add_ps(acc0, acc1));
+
+        let mut acc = ((lanes[0] + lanes[1]) + (lanes[2] + lanes[3]))
+            + ((lanes[4] + lanes[5]) + (lanes[6] + lanes[7]));
+        for i in (16 * chunks)..n {
+            acc += a[i] * b[i];
+        }
+
+        acc
+    }
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn axpy_f64(alpha: f64, x: &[f64], y: &mut [f64]) {
+        let n = x.len().min(y.len());
+        let chunks = n / 4;
+        let px = x.as_ptr();
+        let py = y.as_mut_ptr();
+        let va = _mm256_set1_pd(alpha);
+
+        for i in 0..chunks {
+            let k = 4 * i;
+            let v = _mm256_add_pd(
+                _mm256_loadu_pd(py.add(k)),
+                _mm256_mul_pd(va, _mm256_loadu_pd(px.add(k))),
+            );
+            _mm256_storeu_pd(py.add(k), v);
+        }
+
+        for i in (4 * chunks)..n {
+            y[i] += alpha * x[i];
+        }
+    }
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn axpy_f32(alpha: f32, x: &[f32], y: &mut [f32]) {
+        let n = x.len().min(y.len());
+        let chunks = n / 8;
+        let px = x.as_ptr();
+        let py = y.as_mut_ptr();
+        let va = _mm256_set1_ps(alpha);
+
+        for i in 0..chunks {
+            let k = 8 * i;
+            let v = _mm256_add_ps(
+                _mm256_loadu_ps(py.add(k)),
+                _mm256_mul_ps(va, _mm256_loadu_ps(px.add(k))),
+            );
+            _mm256_storeu_ps(py.add(k), v);
+        }
+
+        for i in (8 * chunks)..n {
+            y[i] += alpha * x[i];
+        }
+    }
+}
+
+macro_rules! impl_float_kernel {
+    ($t:ty, $dot:ident, $axpy:ident) => {
+        impl Kernel for $t {
+            fn dot(a: &[$t], b: &[$t]) -> $t {
+                debug_assert_eq!(a.len(), b.len());
+
+                #[cfg(target_arch = "x86_64")]
+                if is_x86_feature_detected!("avx") {
+                    // SAFETY: AVX support has just been checked.
+                    return unsafe { avx::$dot(a, b) };
+                }
+
+                dot_scalar(a, b)
+            }
+
+            fn axpy(alpha: $t, x: &[$t], y: &mut [$t]) {
+                debug_assert_eq!(x.len(), y.len());
+
+                #[cfg(target_arch = "x86_64")]
+                if is_x86_feature_detected!("avx") {
+                    // SAFETY: AVX support has just been checked.
+                    unsafe { avx::$axpy(alpha, x, y) };
+                    return;
+                }
+
+                axpy_scalar(alpha, x, y)
+            }
+        }
+    };
+}
+
+impl_float_kernel!(f64, dot_f64, axpy_f64);
+impl_float_kernel!(f32, dot_f32, axpy_f32);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn vectors(n: usize) -> (Vec<f64>, Vec<f64>) {
+        let a = (0..n).map(|i| ((i * 13) as f64 * 0.1).sin()).collect();
+        let b = (0..n).map(|i| ((i * 7) as f64 * 0.2).cos()).collect();
+
+        (a, b)
+    }
+
+    #[test]
+    fn dot_matches_naive_loop() {
+        for n in 0..70 {
+            let (a, b) = vectors(n);
+            let expected: f64 = a.iter().zip(&b).map(|(x, y)| x * y).sum();
+
+            assert!((f64::dot(&a, &b) - expected).abs() < 1e-10);
+
+            let a32: Vec<f32> = a.iter().map(|v| *v as f32).collect();
+            let b32: Vec<f32> = b.iter().map(|v| *v as f32).collect();
+
+            assert!((f32::dot(&a32, &b32) as f64 - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn axpy_matches_naive_loop() {
+        for n in 0..70 {
+            let (x, mut y) = vectors(n);
+            let expected: Vec<f64> = x.iter().zip(&y).map(|(a, b)| b + 0.5 * a).collect();
+
+            f64::axpy(0.5, &x, &mut y);
+
+            for i in 0..n {
+                assert!((y[i] - expected[i]).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn integer_kernels() {
+        let a: Vec<i32> = (0..11).collect();
+        let mut y = vec![1; 11];
+
+        assert_eq!(i32::dot(&a, &a), 385);
+
+        i32::axpy(2, &a, &mut y);
+
+        assert_eq!(y[10], 21);
+    }
+}
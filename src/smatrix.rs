@@ -1,23 +1,72 @@
 //! Symmetric matrix representation. Used for matrices that are symmetric along the
-//! diagonal. This is needed for classic Hopfield networks that have un-directed 
+//! diagonal. This is needed for classic Hopfield networks that have un-directed
 //! edges.
 
-use std::fmt::Debug;
+use crate::dmatrix::fmt_values;
+use crate::error::{check_len, HopfieldError};
+use crate::{MaybeSend, MaybeSync};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::fmt::{self, Display, Formatter};
 use std::ops::{AddAssign, Index, IndexMut, Mul};
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "RawSMatrix<T>")
+)]
 pub struct SMatrix<T> {
     rows: usize,
     data: Vec<T>,
 }
 
-impl<T: Debug> SMatrix<T> {
-    pub fn pp(&self) {
+// The serialised form of `SMatrix`, checked for a consistent shape on load.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawSMatrix<T> {
+    rows: usize,
+    data: Vec<T>,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<RawSMatrix<T>> for SMatrix<T> {
+    type Error = String;
+
+    fn try_from(raw: RawSMatrix<T>) -> Result<Self, String> {
+        let size = raw
+            .rows
+            .checked_add(1)
+            .and_then(|r| r.checked_mul(raw.rows))
+            .map(|s| s / 2);
+
+        if size != Some(raw.data.len()) {
+            return Err(format!(
+                "a symmetric matrix with {} rows needs {} elements, found {}",
+                raw.rows,
+                size.unwrap_or(usize::MAX),
+                raw.data.len()
+            ));
+        }
+
+        Ok(Self {
+            rows: raw.rows,
+            data: raw.data,
+        })
+    }
+}
+
+impl<T: Display> Display for SMatrix<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         for r in 0..self.rows {
-            for c in 0..self.rows {
-                print!("{:?} ", self[(r, c)]);
+            if r > 0 {
+                writeln!(f)?;
             }
-            println!("");
+
+            fmt_values(f, (0..self.rows).map(|c| &self[(r, c)]))?;
         }
+
+        Ok(())
     }
 }
 
@@ -29,6 +78,17 @@ impl<T> SMatrix<T> {
     pub fn cols(&self) -> usize {
         self.rows
     }
+
+    // The upper triangle row by row, row `r` holding columns `r..rows`.
+    pub(crate) fn packed(&self) -> &[T] {
+        &self.data
+    }
+
+    pub(crate) fn from_packed(rows: usize, data: Vec<T>) -> Self {
+        debug_assert_eq!(data.len(), rows * (rows + 1) / 2);
+
+        Self { rows, data }
+    }
 }
 
 impl<T: Default + Clone> SMatrix<T> {
@@ -94,6 +154,14 @@ impl<T: AddAssign + Clone> SMatrix<T> {
             self.data[i] += mat.data[i].clone()
         }
     }
+
+    /// Like `add_matrix`, but checks that both matrices have the same size.
+    pub fn try_add_matrix(&mut self, mat: &SMatrix<T>) -> Result<(), HopfieldError> {
+        check_len("rows", self.rows, mat.rows)?;
+
+        self.add_matrix(mat);
+        Ok(())
+    }
 }
 
 impl<T: Mul<Output = T> + AddAssign + Copy> SMatrix<T> {
@@ -108,6 +176,173 @@ impl<T: Mul<Output = T> + AddAssign + Copy> SMatrix<T> {
     }
 }
 
+impl<T: MaybeSend> SMatrix<T> {
+    // The packed storage split into rows, row `r` holding columns `r..rows`.
+    fn packed_rows(&self) -> Vec<&[T]> {
+        let mut rows = Vec::with_capacity(self.rows);
+        let mut rest = self.data.as_slice();
+
+        for r in 0..self.rows {
+            let (row, tail) = rest.split_at(self.rows - r);
+            rows.push(row);
+            rest = tail;
+        }
+
+        rows
+    }
+
+    // Like `packed_rows`, but mutable.
+    fn packed_rows_mut(&mut self) -> Vec<&mut [T]> {
+        let mut rows = Vec::with_capacity(self.rows);
+        let mut rest = self.data.as_mut_slice();
+
+        for r in 0..self.rows {
+            let (row, tail) = std::mem::take(&mut rest).split_at_mut(self.rows - r);
+            rows.push(row);
+            rest = tail;
+        }
+
+        rows
+    }
+
+    /// Call `f(r, c, &mut a_rc)` for every element on or above the diagonal
+    /// (`r <= c`). Rows are visited in parallel with the `parallel` feature.
+    pub fn for_each_upper_mut<F: Fn(usize, usize, &mut T) + MaybeSync>(&mut self, f: F) {
+        let update_row = |(r, row): (usize, &mut [T])| {
+            for (i, v) in row.iter_mut().enumerate() {
+                f(r, r + i, v);
+            }
+        };
+
+        #[cfg(feature = "parallel")]
+        if self.data.len() >= crate::PARALLEL_THRESHOLD {
+            self.packed_rows_mut()
+                .into_par_iter()
+                .enumerate()
+                .for_each(update_row);
+
+            return;
+        }
+
+        self.packed_rows_mut()
+            .into_iter()
+            .enumerate()
+            .for_each(update_row);
+    }
+}
+
+impl<T: Mul<Output = T> + AddAssign + Copy + MaybeSend + MaybeSync> SMatrix<T> {
+    /// Symmetric rank-1 update `A += alpha * x * x^T`, walking the packed
+    /// storage once.
+    pub fn syr(&mut self, alpha: T, x: &[T]) {
+        debug_assert_eq!(self.rows, x.len());
+
+        self.for_each_upper_mut(|r, c, a| *a += alpha * x[r] * x[c]);
+    }
+
+    /// Like `syr`, but checks the length of `x`.
+    pub fn try_syr(&mut self, alpha: T, x: &[T]) -> Result<(), HopfieldError> {
+        check_len("x", self.rows, x.len())?;
+
+        self.syr(alpha, x);
+        Ok(())
+    }
+
+    /// Like `syr` but leaves the diagonal unchanged, as needed for the zero
+    /// self-coupling of Hopfield networks.
+    pub fn syr_hollow(&mut self, alpha: T, x: &[T]) {
+        debug_assert_eq!(self.rows, x.len());
+
+        self.for_each_upper_mut(|r, c, a| {
+            if r != c {
+                *a += alpha * x[r] * x[c];
+            }
+        });
+    }
+
+    /// Like `syr_hollow`, but checks the length of `x`.
+    pub fn try_syr_hollow(&mut self, alpha: T, x: &[T]) -> Result<(), HopfieldError> {
+        check_len("x", self.rows, x.len())?;
+
+        self.syr_hollow(alpha, x);
+        Ok(())
+    }
+}
+
+impl<T: Mul<Output = T> + AddAssign + Default + Copy + MaybeSend + MaybeSync> SMatrix<T> {
+    /// Full matrix-vector product `output = A * input`. The packed upper
+    /// triangle is read once, each element contributing to both `output[r]`
+    /// and `output[c]`. With the `parallel` feature the rows of large
+    /// matrices are split between threads, each summing into its own output
+    /// vector, and the partial outputs are added at the end, which may round
+    /// differently.
+    pub fn mul_vec(&self, input: &[T], output: &mut [T]) {
+        debug_assert_eq!(self.rows, input.len());
+        debug_assert_eq!(self.rows, output.len());
+
+        #[cfg(feature = "parallel")]
+        if self.data.len() >= crate::PARALLEL_THRESHOLD {
+            let zeros = || vec![T::default(); self.rows];
+            let sum = self
+                .packed_rows()
+                .into_par_iter()
+                .enumerate()
+                .fold(zeros, |mut acc, (r, row)| {
+                    add_packed_row(r, row, input, &mut acc);
+                    acc
+                })
+                .reduce(zeros, |mut a, b| {
+                    for i in 0..a.len() {
+                        a[i] += b[i];
+                    }
+                    a
+                });
+
+            output.copy_from_slice(&sum);
+            return;
+        }
+
+        self.mul_vec_packed(input, output);
+    }
+
+    /// Like `mul_vec`, but checks the sizes of `input` and `output`.
+    pub fn try_mul_vec(&self, input: &[T], output: &mut [T]) -> Result<(), HopfieldError> {
+        check_len("input", self.rows, input.len())?;
+        check_len("output", self.rows, output.len())?;
+
+        self.mul_vec(input, output);
+        Ok(())
+    }
+
+    fn mul_vec_packed(&self, input: &[T], output: &mut [T]) {
+        output.fill(T::default());
+
+        for (r, row) in self.packed_rows().into_iter().enumerate() {
+            add_packed_row(r, row, input, output);
+        }
+    }
+}
+
+// Add the products of packed row `r` (columns `r..rows`) with `input` to
+// `output`, using each element for both `output[r]` and its mirror `output[c]`.
+fn add_packed_row<T: Mul<Output = T> + AddAssign + Copy>(
+    r: usize,
+    row: &[T],
+    input: &[T],
+    output: &mut [T],
+) {
+    let xr = input[r];
+    let mut acc = row[0] * xr;
+
+    for i in 1..row.len() {
+        let c = r + i;
+        acc += row[i] * input[c];
+        output[c] += row[i] * xr;
+    }
+
+    output[r] += acc;
+}
+
 impl<T> Index<(usize, usize)> for SMatrix<T> {
     type Output = T;
 
@@ -127,6 +362,7 @@ impl<T> IndexMut<(usize, usize)> for SMatrix<T> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::seed::Seed;
 
     #[test]
     fn get_the_right_row() {
@@ -157,7 +393,7 @@ mod test {
     fn random_matrix_is_symmetric() {
         use rand::Rng;
 
-        let mut rng = rand::rng();
+        let mut rng = Seed(1).rng();
         let mat: SMatrix<f64> = SMatrix::from_fn0(8, &mut || rng.random_range(-1. ..1.));
 
         for r in 0..8 {
@@ -178,6 +414,70 @@ mod test {
         }
     }
 
+    #[test]
+    fn display_full_matrix() {
+        let mat: SMatrix<f64> = SMatrix::from_fn2(3, &mut |r, c| (r + c) as f64);
+
+        assert_eq!(
+            format!("{:.1}", mat),
+            "0.0 1.0 2.0\n1.0 2.0 3.0\n2.0 3.0 4.0"
+        );
+    }
+
+    #[test]
+    fn mul_vec_matches_row_mul() {
+        let mat: SMatrix<i32> = SMatrix::from_fn2(8, &mut |r, c| (r * 3 + c) as i32);
+        let v: Vec<i32> = vec![3, -1, 4, 1, -5, 9, 2, -6];
+        let mut out = vec![0; 8];
+
+        mat.mul_vec(&v, &mut out);
+
+        for r in 0..8 {
+            assert_eq!(out[r], mat.row_mul(r, &v, 0));
+        }
+    }
+
+    #[test]
+    fn mul_vec_matches_dense_product() {
+        // Large enough to take the parallel path with the `parallel` feature.
+        const N: usize = 200;
+
+        let mat: SMatrix<f64> = SMatrix::from_fn2(N, &mut |r, c| ((r * 7 + c) as f64).cos());
+        let v: Vec<f64> = (0..N).map(|i| (i as f64 * 0.3).sin()).collect();
+        let mut out = vec![0.; N];
+
+        mat.mul_vec(&v, &mut out);
+
+        for r in 0..N {
+            let expected: f64 = (0..N).map(|c| mat[(r, c)] * v[c]).sum();
+
+            assert!((out[r] - expected).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn syr_adds_outer_product() {
+        let x: Vec<i32> = vec![1, -2, 3, 4];
+        let mut m1: SMatrix<i32> = SMatrix::from_fn2(4, &mut |r, c| (r + c) as i32);
+        let mut m2 = m1.clone();
+
+        m1.syr(2, &x);
+        m2.syr_hollow(2, &x);
+
+        for r in 0..4 {
+            for c in 0..4 {
+                let expected = (r + c) as i32 + 2 * x[r] * x[c];
+                assert_eq!(m1[(r, c)], expected);
+
+                if r == c {
+                    assert_eq!(m2[(r, c)], (r + c) as i32);
+                } else {
+                    assert_eq!(m2[(r, c)], expected);
+                }
+            }
+        }
+    }
+
     #[test]
     fn add_zero_matrix() {
         let mut m1: SMatrix<i32> = SMatrix::from_fn2(8, &mut |r, c| (r + c) as i32);
@@ -206,4 +506,15 @@ mod test {
             }
         }
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let m: SMatrix<i32> = SMatrix::from_fn2(4, &mut |r, c| (r * 4 + c) as i32);
+
+        let json = serde_json::to_string(&m).unwrap();
+
+        assert_eq!(serde_json::from_str::<SMatrix<i32>>(&json).unwrap(), m);
+        assert!(serde_json::from_str::<SMatrix<i32>>(r#"{"rows":3,"data":[1,2,3]}"#).is_err());
+    }
 }
@@ -0,0 +1,752 @@
+//! Sparse symmetric matrix representation for diluted networks, where each
+//! neuron is only connected to a small fraction of the others. The matrix is
+//! stored in compressed sparse row (CSR) form with both `(r, c)` and `(c, r)`
+//! present, so a row holds all connections of a neuron. Insertion and updates
+//! keep the two halves equal.
+
+use crate::dmatrix::fmt_values;
+use crate::error::{check_index, check_len, check_probability, HopfieldError};
+use crate::{MaybeSend, MaybeSync};
+use rand::seq::SliceRandom;
+use rand::Rng;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::fmt::{self, Display, Formatter};
+use std::ops::{AddAssign, Index, Mul};
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        try_from = "RawSpMatrix<T>",
+        bound(deserialize = "T: serde::Deserialize<'de> + Default + PartialEq")
+    )
+)]
+pub struct SpMatrix<T> {
+    rows: usize,
+    // Row `r` is stored in `indices[offsets[r]..offsets[r + 1]]` (sorted
+    // columns) and the same range of `data`.
+    offsets: Vec<usize>,
+    indices: Vec<usize>,
+    data: Vec<T>,
+    // Returned by indexing for entries that are not stored.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    zero: T,
+}
+
+// The serialised form of `SpMatrix`. On load the rows are checked to be
+// sorted and in range and every stored `(r, c)` to have a stored `(c, r)` with
+// the same value.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawSpMatrix<T> {
+    rows: usize,
+    offsets: Vec<usize>,
+    indices: Vec<usize>,
+    data: Vec<T>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: Default + PartialEq> TryFrom<RawSpMatrix<T>> for SpMatrix<T> {
+    type Error = String;
+
+    fn try_from(raw: RawSpMatrix<T>) -> Result<Self, String> {
+        let n = raw.rows;
+
+        if n.checked_add(1) != Some(raw.offsets.len())
+            || raw.offsets.first() != Some(&0)
+            || raw.offsets.get(n) != Some(&raw.indices.len())
+            || raw.indices.len() != raw.data.len()
+            || raw.offsets.windows(2).any(|w| w[0] > w[1])
+        {
+            return Err(format!(
+                "inconsistent offsets for a sparse matrix with {} rows and {} entries",
+                n,
+                raw.data.len()
+            ));
+        }
+
+        let m = Self {
+            rows: n,
+            offsets: raw.offsets,
+            indices: raw.indices,
+            data: raw.data,
+            zero: T::default(),
+        };
+
+        for r in 0..n {
+            let row = m.row_indices(r);
+
+            if row.windows(2).any(|w| w[0] >= w[1]) || row.last().is_some_and(|c| *c >= n) {
+                return Err(format!("row {} is not sorted or out of range", r));
+            }
+            if let Some(c) = row.iter().find(|c| !m.contains(**c, r)) {
+                return Err(format!("entry ({}, {}) has no symmetric entry", r, c));
+            }
+            if let Some(c) = row.iter().find(|c| m[(r, **c)] != m[(**c, r)]) {
+                return Err(format!("entry ({}, {}) differs from ({}, {})", r, c, c, r));
+            }
+        }
+
+        Ok(m)
+    }
+}
+
+impl<T: Display> Display for SpMatrix<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for r in 0..self.rows {
+            if r > 0 {
+                writeln!(f)?;
+            }
+
+            fmt_values(f, (0..self.rows).map(|c| &self[(r, c)]))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> SpMatrix<T> {
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.rows
+    }
+
+    /// The number of stored entries, counting `(r, c)` and `(c, r)` separately.
+    pub fn nnz(&self) -> usize {
+        self.data.len()
+    }
+
+    /// The number of neurons connected to neuron `r`.
+    pub fn degree(&self, r: usize) -> usize {
+        self.offsets[r + 1] - self.offsets[r]
+    }
+
+    /// The columns of the entries stored in row `r`, in increasing order.
+    pub fn row_indices(&self, r: usize) -> &[usize] {
+        &self.indices[self.offsets[r]..self.offsets[r + 1]]
+    }
+
+    /// Whether the entry at `(r, c)` is stored.
+    pub fn contains(&self, r: usize, c: usize) -> bool {
+        self.position(r, c).is_some()
+    }
+
+    fn position(&self, r: usize, c: usize) -> Option<usize> {
+        self.row_indices(r)
+            .binary_search(&c)
+            .ok()
+            .map(|i| self.offsets[r] + i)
+    }
+}
+
+impl<T: Clone + Default> SpMatrix<T> {
+    /// Build a matrix from a list of `(r, c, value)` entries. Each entry is
+    /// stored at both `(r, c)` and `(c, r)`; if a position is given more than
+    /// once the last value wins. Entries not stored read as `T::default()`.
+    pub fn from_triplets<I>(rows: usize, triplets: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, usize, T)>,
+    {
+        let mut entries: Vec<(usize, usize, T)> = Vec::new();
+
+        for (r, c, v) in triplets {
+            debug_assert!(r < rows && c < rows);
+
+            if r != c {
+                entries.push((c, r, v.clone()));
+            }
+            entries.push((r, c, v));
+        }
+
+        // A stable sort keeps the insertion order of duplicates, so keeping
+        // the last one of each run gives "last value wins".
+        entries.sort_by_key(|e| (e.0, e.1));
+
+        let mut offsets = vec![0; rows + 1];
+        let mut indices = Vec::with_capacity(entries.len());
+        let mut data: Vec<T> = Vec::with_capacity(entries.len());
+        let mut previous = None;
+
+        for (r, c, v) in entries {
+            if previous == Some((r, c)) {
+                *data.last_mut().unwrap() = v;
+            } else {
+                indices.push(c);
+                data.push(v);
+                offsets[r + 1] += 1;
+                previous = Some((r, c));
+            }
+        }
+
+        for r in 0..rows {
+            offsets[r + 1] += offsets[r];
+        }
+
+        Self {
+            rows,
+            offsets,
+            indices,
+            data,
+            zero: T::default(),
+        }
+    }
+
+    /// A matrix with the given connections (each given once, in either
+    /// direction), all set to `value`.
+    pub fn from_edges<I>(rows: usize, edges: I, value: T) -> Self
+    where
+        I: IntoIterator<Item = (usize, usize)>,
+    {
+        Self::from_triplets(rows, edges.into_iter().map(|(r, c)| (r, c, value.clone())))
+    }
+
+    /// Set the entry at `(r, c)` and `(c, r)`, inserting it if it is not
+    /// stored yet. Inserting moves all later entries, so build matrices with
+    /// `from_triplets` rather than by repeated insertion.
+    pub fn insert(&mut self, r: usize, c: usize, value: T) {
+        debug_assert!(r < self.rows && c < self.rows);
+
+        self.insert_half(r, c, value.clone());
+        if r != c {
+            self.insert_half(c, r, value);
+        }
+    }
+
+    /// Like `from_triplets`, but checks that every position is in the matrix.
+    pub fn try_from_triplets<I>(rows: usize, triplets: I) -> Result<Self, HopfieldError>
+    where
+        I: IntoIterator<Item = (usize, usize, T)>,
+    {
+        let triplets: Vec<(usize, usize, T)> = triplets.into_iter().collect();
+
+        for (r, c, _v) in &triplets {
+            check_index("row", *r, rows)?;
+            check_index("column", *c, rows)?;
+        }
+
+        Ok(Self::from_triplets(rows, triplets))
+    }
+
+    /// Like `from_edges`, but checks that every position is in the matrix.
+    pub fn try_from_edges<I>(rows: usize, edges: I, value: T) -> Result<Self, HopfieldError>
+    where
+        I: IntoIterator<Item = (usize, usize)>,
+    {
+        Self::try_from_triplets(rows, edges.into_iter().map(|(r, c)| (r, c, value.clone())))
+    }
+
+    /// Like `insert`, but checks that `(r, c)` is in the matrix.
+    pub fn try_insert(&mut self, r: usize, c: usize, value: T) -> Result<(), HopfieldError> {
+        check_index("row", r, self.rows)?;
+        check_index("column", c, self.rows)?;
+
+        self.insert(r, c, value);
+        Ok(())
+    }
+
+    fn insert_half(&mut self, r: usize, c: usize, value: T) {
+        match self.row_indices(r).binary_search(&c) {
+            Ok(i) => self.data[self.offsets[r] + i] = value,
+            Err(i) => {
+                let k = self.offsets[r] + i;
+
+                self.indices.insert(k, c);
+                self.data.insert(k, value);
+
+                for o in &mut self.offsets[(r + 1)..] {
+                    *o += 1;
+                }
+            }
+        }
+    }
+}
+
+impl<T: Clone + Default> SpMatrix<T> {
+    /// Erdős–Rényi dilution: each pair of distinct neurons is connected with
+    /// probability `p`.
+    pub fn erdos_renyi<R: Rng>(rows: usize, p: f64, value: T, rng: &mut R) -> Self {
+        debug_assert!((0. ..=1.).contains(&p));
+
+        let mut edges = Vec::new();
+
+        for r in 0..rows {
+            for c in (r + 1)..rows {
+                if rng.random_bool(p) {
+                    edges.push((r, c));
+                }
+            }
+        }
+
+        Self::from_edges(rows, edges, value)
+    }
+
+    /// Random dilution where every neuron has `degree` connections. Built
+    /// by randomly pairing `degree` stubs per neuron; pairs that would form a
+    /// self-connection or repeat a connection are put back and paired again.
+    /// The few stubs left over after that are dropped, leaving their neurons
+    /// just below `degree`.
+    pub fn fixed_degree<R: Rng>(rows: usize, degree: usize, value: T, rng: &mut R) -> Self {
+        const ROUNDS: usize = 16;
+
+        debug_assert!(degree < rows);
+
+        let mut stubs: Vec<usize> = (0..rows)
+            .flat_map(|r| std::iter::repeat_n(r, degree))
+            .collect();
+        let mut edges = std::collections::BTreeSet::new();
+
+        for _i in 0..ROUNDS {
+            stubs.shuffle(rng);
+
+            let mut rejected = Vec::new();
+
+            for p in stubs.chunks_exact(2) {
+                let edge = (p[0].min(p[1]), p[0].max(p[1]));
+
+                if p[0] == p[1] || !edges.insert(edge) {
+                    rejected.extend_from_slice(p);
+                }
+            }
+
+            if rejected.is_empty() {
+                break;
+            }
+
+            stubs = rejected;
+        }
+
+        Self::from_edges(rows, edges, value)
+    }
+
+    /// Like `erdos_renyi`, but checks that `p` is in `[0, 1]`.
+    pub fn try_erdos_renyi<R: Rng>(
+        rows: usize,
+        p: f64,
+        value: T,
+        rng: &mut R,
+    ) -> Result<Self, HopfieldError> {
+        check_probability("p", p)?;
+
+        Ok(Self::erdos_renyi(rows, p, value, rng))
+    }
+
+    /// Like `fixed_degree`, but checks that `degree` is less than `rows`.
+    pub fn try_fixed_degree<R: Rng>(
+        rows: usize,
+        degree: usize,
+        value: T,
+        rng: &mut R,
+    ) -> Result<Self, HopfieldError> {
+        check_degree(rows, degree)?;
+
+        Ok(Self::fixed_degree(rows, degree, value, rng))
+    }
+
+    /// Watts–Strogatz small-world dilution: a ring where each neuron is
+    /// connected to its `degree / 2` nearest neighbours on either side, after
+    /// which each connection is rewired to a random neuron with probability
+    /// `beta`.
+    pub fn small_world<R: Rng>(
+        rows: usize,
+        degree: usize,
+        beta: f64,
+        value: T,
+        rng: &mut R,
+    ) -> Self {
+        debug_assert!(degree < rows);
+        debug_assert!((0. ..=1.).contains(&beta));
+
+        let half = degree / 2;
+        let mut adjacent = vec![std::collections::BTreeSet::new(); rows];
+
+        for r in 0..rows {
+            for k in 1..=half {
+                let c = (r + k) % rows;
+                adjacent[r].insert(c);
+                adjacent[c].insert(r);
+            }
+        }
+
+        for k in 1..=half {
+            for r in 0..rows {
+                let c = (r + k) % rows;
+
+                if !adjacent[r].contains(&c) || !rng.random_bool(beta) {
+                    continue;
+                }
+                if adjacent[r].len() + 1 >= rows {
+                    continue;
+                }
+
+                let mut n = rng.random_range(0..rows);
+                while n == r || adjacent[r].contains(&n) {
+                    n = rng.random_range(0..rows);
+                }
+
+                adjacent[r].remove(&c);
+                adjacent[c].remove(&r);
+                adjacent[r].insert(n);
+                adjacent[n].insert(r);
+            }
+        }
+
+        let edges = adjacent
+            .iter()
+            .enumerate()
+            .flat_map(|(r, a)| a.range((r + 1)..).map(move |c| (r, *c)));
+
+        Self::from_edges(rows, edges, value)
+    }
+
+    /// Like `small_world`, but checks that `degree` is less than `rows` and
+    /// that `beta` is in `[0, 1]`.
+    pub fn try_small_world<R: Rng>(
+        rows: usize,
+        degree: usize,
+        beta: f64,
+        value: T,
+        rng: &mut R,
+    ) -> Result<Self, HopfieldError> {
+        check_degree(rows, degree)?;
+        check_probability("beta", beta)?;
+
+        Ok(Self::small_world(rows, degree, beta, value, rng))
+    }
+}
+
+fn check_degree(rows: usize, degree: usize) -> Result<(), HopfieldError> {
+    if degree >= rows {
+        return Err(HopfieldError::InvalidParameter {
+            name: "degree",
+            reason: "must be less than the number of rows",
+        });
+    }
+
+    Ok(())
+}
+
+impl<T: AddAssign + Clone> SpMatrix<T> {
+    /// Add `mat` to this matrix. Entries stored in `mat` but not here are
+    /// inserted.
+    pub fn add_matrix(&mut self, mat: &SpMatrix<T>) {
+        debug_assert_eq!(self.rows, mat.rows);
+
+        if self.offsets == mat.offsets && self.indices == mat.indices {
+            for i in 0..self.data.len() {
+                self.data[i] += mat.data[i].clone();
+            }
+
+            return;
+        }
+
+        let mut offsets = vec![0; self.rows + 1];
+        let mut indices = Vec::with_capacity(self.nnz().max(mat.nnz()));
+        let mut data = Vec::with_capacity(self.nnz().max(mat.nnz()));
+
+        for r in 0..self.rows {
+            let (mut i, mut j) = (self.offsets[r], mat.offsets[r]);
+            let (i_end, j_end) = (self.offsets[r + 1], mat.offsets[r + 1]);
+
+            while i < i_end || j < j_end {
+                if j == j_end || (i < i_end && self.indices[i] < mat.indices[j]) {
+                    indices.push(self.indices[i]);
+                    data.push(self.data[i].clone());
+                    i += 1;
+                } else if i == i_end || mat.indices[j] < self.indices[i] {
+                    indices.push(mat.indices[j]);
+                    data.push(mat.data[j].clone());
+                    j += 1;
+                } else {
+                    let mut v = self.data[i].clone();
+                    v += mat.data[j].clone();
+                    indices.push(self.indices[i]);
+                    data.push(v);
+                    i += 1;
+                    j += 1;
+                }
+            }
+
+            offsets[r + 1] = indices.len();
+        }
+
+        self.offsets = offsets;
+        self.indices = indices;
+        self.data = data;
+    }
+
+    /// Like `add_matrix`, but checks that both matrices have the same size.
+    pub fn try_add_matrix(&mut self, mat: &SpMatrix<T>) -> Result<(), HopfieldError> {
+        check_len("rows", self.rows, mat.rows)?;
+
+        self.add_matrix(mat);
+        Ok(())
+    }
+}
+
+impl<T: Mul<Output = T> + AddAssign + Copy> SpMatrix<T> {
+    pub fn row_mul(&self, row: usize, vec: &[T], init: T) -> T {
+        let mut acc = init;
+
+        for k in self.offsets[row]..self.offsets[row + 1] {
+            let c = self.indices[k];
+
+            if c < vec.len() {
+                acc += self.data[k] * vec[c];
+            }
+        }
+
+        acc
+    }
+}
+
+impl<T: Mul<Output = T> + AddAssign + Default + Copy + MaybeSend + MaybeSync> SpMatrix<T> {
+    /// Matrix-vector product `output = A * input`, parallel over the rows for
+    /// large matrices with the `parallel` feature.
+    pub fn mul_vec(&self, input: &[T], output: &mut [T]) {
+        debug_assert_eq!(self.rows, input.len());
+        debug_assert_eq!(self.rows, output.len());
+
+        #[cfg(feature = "parallel")]
+        if self.data.len() >= crate::PARALLEL_THRESHOLD {
+            output
+                .par_iter_mut()
+                .enumerate()
+                .for_each(|(r, o)| *o = self.row_mul(r, input, T::default()));
+
+            return;
+        }
+
+        for r in 0..self.rows {
+            output[r] = self.row_mul(r, input, T::default());
+        }
+    }
+
+    /// Like `mul_vec`, but checks the sizes of `input` and `output`.
+    pub fn try_mul_vec(&self, input: &[T], output: &mut [T]) -> Result<(), HopfieldError> {
+        check_len("input", self.rows, input.len())?;
+        check_len("output", self.rows, output.len())?;
+
+        self.mul_vec(input, output);
+        Ok(())
+    }
+}
+
+impl<T: Copy> SpMatrix<T> {
+    /// Call `f(r, c, &mut a_rc)` for every stored element on or above the
+    /// diagonal (`r <= c`), then copy the results to the elements below it.
+    pub fn for_each_upper_mut<F: Fn(usize, usize, &mut T)>(&mut self, f: F) {
+        for r in 0..self.rows {
+            for k in self.offsets[r]..self.offsets[r + 1] {
+                let c = self.indices[k];
+
+                if r <= c {
+                    f(r, c, &mut self.data[k]);
+                }
+            }
+        }
+
+        for r in 0..self.rows {
+            for k in self.offsets[r]..self.offsets[r + 1] {
+                let c = self.indices[k];
+
+                if c < r {
+                    self.data[k] = self.data[self.position(c, r).unwrap()];
+                }
+            }
+        }
+    }
+}
+
+impl<T: Mul<Output = T> + AddAssign + Copy> SpMatrix<T> {
+    /// Symmetric rank-1 update `A += alpha * x * x^T` restricted to the
+    /// stored entries and leaving the diagonal unchanged.
+    pub fn syr_hollow(&mut self, alpha: T, x: &[T]) {
+        debug_assert_eq!(self.rows, x.len());
+
+        self.for_each_upper_mut(|r, c, a| {
+            if r != c {
+                *a += alpha * x[r] * x[c];
+            }
+        });
+    }
+
+    /// Like `syr_hollow`, but checks the length of `x`.
+    pub fn try_syr_hollow(&mut self, alpha: T, x: &[T]) -> Result<(), HopfieldError> {
+        check_len("x", self.rows, x.len())?;
+
+        self.syr_hollow(alpha, x);
+        Ok(())
+    }
+}
+
+impl<T> Index<(usize, usize)> for SpMatrix<T> {
+    type Output = T;
+
+    fn index(&self, index: (usize, usize)) -> &T {
+        match self.position(index.0, index.1) {
+            Some(k) => &self.data[k],
+            None => &self.zero,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seed::Seed;
+
+    fn is_symmetric(m: &SpMatrix<f64>) -> bool {
+        (0..m.rows()).all(|r| (0..m.rows()).all(|c| m[(r, c)] == m[(c, r)]))
+    }
+
+    #[test]
+    fn symmetric_insertion() {
+        let mut m = SpMatrix::from_triplets(4, vec![(0, 2, 1.), (3, 1, 2.), (0, 2, 5.)]);
+
+        assert_eq!(m.nnz(), 4);
+        assert_eq!(m[(2, 0)], 5.);
+        assert_eq!(m[(1, 3)], 2.);
+        assert_eq!(m[(1, 2)], 0.);
+
+        m.insert(2, 1, 3.);
+        m.insert(0, 0, 4.);
+
+        assert_eq!(m.nnz(), 7);
+        assert_eq!(m.row_indices(2), &[0, 1]);
+        assert!(is_symmetric(&m));
+        assert_eq!(format!("{}", m), "4 0 5 0\n0 0 3 2\n5 3 0 0\n0 2 0 0");
+    }
+
+    #[test]
+    fn row_mul_matches_dense() {
+        let m = SpMatrix::from_triplets(
+            5,
+            vec![(0, 1, 1), (0, 4, -2), (2, 3, 3), (1, 1, 4), (4, 2, 5)],
+        );
+        let v = vec![1, 2, 3, 4, 5];
+        let mut out = vec![0; 5];
+
+        m.mul_vec(&v, &mut out);
+
+        for r in 0..5 {
+            let expected: i32 = (0..5).map(|c| m[(r, c)] * v[c]).sum();
+
+            assert_eq!(m.row_mul(r, &v, 0), expected);
+            assert_eq!(out[r], expected);
+        }
+    }
+
+    #[test]
+    fn add_matrix_merges_structure() {
+        let mut m1 = SpMatrix::from_triplets(3, vec![(0, 1, 1), (1, 2, 2)]);
+        let m2 = SpMatrix::from_triplets(3, vec![(0, 1, 10), (0, 2, 20)]);
+
+        m1.add_matrix(&m2);
+
+        assert_eq!(m1[(1, 0)], 11);
+        assert_eq!(m1[(2, 1)], 2);
+        assert_eq!(m1[(2, 0)], 20);
+        assert_eq!(m1.nnz(), 6);
+
+        m1.add_matrix(&m1.clone());
+
+        assert_eq!(m1[(0, 1)], 22);
+    }
+
+    #[test]
+    fn syr_hollow_updates_both_halves() {
+        let x = vec![1., -1., 1., 1.];
+        let mut m = SpMatrix::from_edges(4, vec![(0, 1), (2, 3), (1, 1)], 0.);
+
+        m.syr_hollow(0.5, &x);
+
+        assert_eq!(m[(1, 0)], -0.5);
+        assert_eq!(m[(3, 2)], 0.5);
+        assert_eq!(m[(1, 1)], 0.);
+        assert_eq!(m[(0, 2)], 0.);
+        assert!(is_symmetric(&m));
+    }
+
+    #[test]
+    fn dilution_constructors() {
+        const N: usize = 400;
+
+        let mut rng = Seed(7).rng();
+
+        let er = SpMatrix::erdos_renyi(N, 0.05, 1., &mut rng);
+        let mean = er.nnz() as f64 / N as f64;
+        assert!((mean - 0.05 * (N - 1) as f64).abs() < 2.);
+
+        let fixed = SpMatrix::fixed_degree(N, 10, 1., &mut rng);
+        assert!((0..N).all(|r| fixed.degree(r) <= 10));
+        assert!((0..N).filter(|r| fixed.degree(*r) == 10).count() >= N - 4);
+
+        let ring = SpMatrix::small_world(N, 6, 0., 1., &mut rng);
+        assert!((0..N).all(|r| ring.row_indices(r).len() == 6));
+        assert!(ring.contains(0, N - 3) && !ring.contains(0, 4));
+
+        let sw = SpMatrix::small_world(N, 6, 0.2, 1., &mut rng);
+        assert_eq!(sw.nnz(), ring.nnz());
+
+        for m in [&er, &fixed, &sw] {
+            assert!((0..N).all(|r| !m.contains(r, r)));
+            assert!(is_symmetric(m));
+        }
+    }
+
+    #[test]
+    fn try_constructors_check_parameters() {
+        let mut rng = Seed(8).rng();
+
+        assert!(SpMatrix::try_erdos_renyi(10, 1.5, 1., &mut rng).is_err());
+        assert!(SpMatrix::try_fixed_degree(10, 10, 1., &mut rng).is_err());
+        assert!(SpMatrix::try_small_world(10, 4, -0.5, 1., &mut rng).is_err());
+        assert!(SpMatrix::try_small_world(10, 12, 0.5, 1., &mut rng).is_err());
+        assert!(SpMatrix::try_from_edges(4, [(0, 1), (2, 4)], 1.).is_err());
+        assert_eq!(
+            SpMatrix::try_from_triplets(4, [(0, 1, 2.), (3, 2, 1.)]),
+            Ok(SpMatrix::from_triplets(4, [(0, 1, 2.), (3, 2, 1.)]))
+        );
+
+        let mut m = SpMatrix::from_edges(4, [(0, 1)], 1.);
+        assert_eq!(
+            m.try_insert(1, 4, 1.),
+            Err(HopfieldError::InvalidParameter {
+                name: "column",
+                reason: "index out of range",
+            })
+        );
+        assert_eq!(m.nnz(), 2);
+        m.try_insert(1, 3, 1.).unwrap();
+        assert!(m.contains(3, 1));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let m = SpMatrix::from_triplets(4, vec![(0, 2, 1.), (3, 1, 2.)]);
+
+        let json = serde_json::to_string(&m).unwrap();
+
+        assert_eq!(serde_json::from_str::<SpMatrix<f64>>(&json).unwrap(), m);
+
+        // (0, 2) without (2, 0).
+        let asymmetric = r#"{"rows":3,"offsets":[0,1,1,1],"indices":[2],"data":[1.0]}"#;
+        assert!(serde_json::from_str::<SpMatrix<f64>>(asymmetric).is_err());
+
+        // (0, 1) and (1, 0) with different values.
+        let unequal = r#"{"rows":2,"offsets":[0,1,2],"indices":[1,0],"data":[1.0,2.0]}"#;
+        assert!(serde_json::from_str::<SpMatrix<f64>>(unequal).is_err());
+
+        let huge = format!(
+            r#"{{"rows":{},"offsets":[],"indices":[],"data":[]}}"#,
+            usize::MAX
+        );
+        assert!(serde_json::from_str::<SpMatrix<f64>>(&huge).is_err());
+    }
+}
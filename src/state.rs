@@ -1,22 +1,35 @@
 //! Some utility methods that are useful for manipulating the state of various Hopfield
 //! models. The state is represented with a slice.
-
+//!
+//! The corruption methods (`flip_random`, `erase_block`, `occlude`, ...) make
+//! noisy recall cues from stored patterns. Each returns a mask that is `true`
+//! at the positions selected by the noise model, so intact and corrupted
+//! positions can be told apart when measuring recall. The methods taking a
+//! probability, a standard deviation or an image width also have `try_`
+//! variants that check them; `flip_random`, `erase_random` and `erase_block`
+//! clip their counts to the state and cannot fail.
+
+use crate::error::{check_finite, check_non_negative, check_probability, HopfieldError};
+use crate::scalar::Scalar;
+use rand::seq::index;
 use rand::Rng;
 use std::ops::Deref;
 use std::ops::DerefMut;
 use std::ops::{BitAnd, Shr};
 
-pub trait State {
-    fn error_norm(&self, pattern: &[f64]) -> f64;
-    fn copy_from(&mut self, pattern: &[f64]);
-    fn decay(&mut self, d: f64);
-    fn add_pattern(&mut self, pattern: &[f64], amount: f64);
-    fn add_noise(&mut self, rng: &mut impl Rng, amount: f64);
+pub trait State<T: Scalar = f64> {
+    fn error_norm(&self, pattern: &[T]) -> T;
+    fn copy_from(&mut self, pattern: &[T]);
+    fn decay(&mut self, d: T);
+    fn add_pattern(&mut self, pattern: &[T], amount: T);
+    fn add_noise(&mut self, rng: &mut impl Rng, amount: T);
+    #[allow(clippy::wrong_self_convention)]
     fn from_bits<S: BitAnd<Output = S> + Shr<Output = S> + From<u8> + PartialEq + Copy>(
         &mut self,
         count: usize,
         bits: S,
     );
+    #[allow(clippy::wrong_self_convention)]
     fn from_bits_with_mask<S: BitAnd<Output = S> + Shr<Output = S> + From<u8> + PartialEq + Copy>(
         &mut self,
         count: usize,
@@ -24,46 +37,129 @@ pub trait State {
         mask: S,
     );
     fn softmax(&mut self);
+
+    /// Flip the sign of exactly `count` values (all of them if `count` is
+    /// larger than the state), chosen uniformly at random.
+    fn flip_random(&mut self, rng: &mut impl Rng, count: usize) -> Vec<bool>;
+    /// Flip the sign of each value independently with probability `p`.
+    fn flip_with_probability(&mut self, rng: &mut impl Rng, p: f64) -> Vec<bool>;
+    /// Set exactly `count` values, chosen uniformly at random, to zero.
+    fn erase_random(&mut self, rng: &mut impl Rng, count: usize) -> Vec<bool>;
+    /// Set a contiguous block of `len` values at a random position to zero.
+    fn erase_block(&mut self, rng: &mut impl Rng, len: usize) -> Vec<bool>;
+    /// Treat the state as an image of the given `width` and set the
+    /// `w x h` rectangle with top left corner `(x, y)`, clipped to the image,
+    /// to `value`.
+    fn occlude(
+        &mut self,
+        width: usize,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        value: T,
+    ) -> Vec<bool>;
+    /// Set each value with probability `p` to 1 or -1 with equal chance.
+    fn salt_and_pepper(&mut self, rng: &mut impl Rng, p: f64) -> Vec<bool>;
+    /// Add zero mean Gaussian noise with standard deviation `sigma` to every
+    /// value.
+    fn add_gaussian_noise(&mut self, rng: &mut impl Rng, sigma: T) -> Vec<bool>;
+
+    /// Like `flip_with_probability`, but checks that `p` is in `[0, 1]`.
+    fn try_flip_with_probability(
+        &mut self,
+        rng: &mut impl Rng,
+        p: f64,
+    ) -> Result<Vec<bool>, HopfieldError> {
+        check_probability("p", p)?;
+
+        Ok(self.flip_with_probability(rng, p))
+    }
+
+    /// Like `occlude`, but checks that `width` is positive and divides the
+    /// size of the state, and that `value` is finite.
+    fn try_occlude(
+        &mut self,
+        width: usize,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        value: T,
+    ) -> Result<Vec<bool>, HopfieldError>;
+
+    /// Like `salt_and_pepper`, but checks that `p` is in `[0, 1]`.
+    fn try_salt_and_pepper(
+        &mut self,
+        rng: &mut impl Rng,
+        p: f64,
+    ) -> Result<Vec<bool>, HopfieldError> {
+        check_probability("p", p)?;
+
+        Ok(self.salt_and_pepper(rng, p))
+    }
+
+    /// Like `add_gaussian_noise`, but checks that `sigma` is finite and
+    /// non-negative.
+    fn try_add_gaussian_noise(
+        &mut self,
+        rng: &mut impl Rng,
+        sigma: T,
+    ) -> Result<Vec<bool>, HopfieldError> {
+        check_non_negative("sigma", sigma)?;
+
+        Ok(self.add_gaussian_noise(rng, sigma))
+    }
+}
+
+// Standard normal sample using the Box-Muller transform.
+pub(crate) fn gaussian(rng: &mut impl Rng) -> f64 {
+    let u1: f64 = 1. - rng.random::<f64>();
+    let u2: f64 = rng.random();
+
+    (-2. * u1.ln()).sqrt() * (2. * std::f64::consts::PI * u2).cos()
 }
 
-impl<T: Deref<Target = [f64]> + DerefMut<Target = [f64]>> State for T {
-    fn error_norm(&self, pattern: &[f64]) -> f64 {
-        let mut acc = 0.;
-        let mut pat_acc = 0.;
+impl<T: Scalar, D: Deref<Target = [T]> + DerefMut<Target = [T]>> State<T> for D {
+    fn error_norm(&self, pattern: &[T]) -> T {
+        let mut acc = T::ZERO;
+        let mut pat_acc = T::ZERO;
 
         for i in 0..self.len().min(pattern.len()) {
             acc += self[i] * pattern[i];
             pat_acc += pattern[i].abs();
         }
 
-        if pat_acc < f64::EPSILON {
-            0.
+        if pat_acc < T::EPSILON {
+            T::ZERO
         } else {
-            1. - acc / pat_acc
+            T::ONE - acc / pat_acc
         }
     }
 
-    fn copy_from(&mut self, pattern: &[f64]) {
+    fn copy_from(&mut self, pattern: &[T]) {
         for i in 0..self.len().min(pattern.len()) {
             self[i] = pattern[i];
         }
     }
 
-    fn decay(&mut self, d: f64) {
+    fn decay(&mut self, d: T) {
         for i in 0..self.len() {
             self[i] *= d;
         }
     }
 
-    fn add_pattern(&mut self, pattern: &[f64], amount: f64) {
+    fn add_pattern(&mut self, pattern: &[T], amount: T) {
         for i in 0..self.len().min(pattern.len()) {
             self[i] += pattern[i] * amount;
         }
     }
 
-    fn add_noise(&mut self, rng: &mut impl Rng, amount: f64) {
+    fn add_noise(&mut self, rng: &mut impl Rng, amount: T) {
+        let amount = amount.to_f64();
+
         for i in 0..self.len() {
-            self[i] += rng.random_range(-amount..amount);
+            self[i] += T::from_f64(rng.random_range(-amount..amount));
         }
     }
 
@@ -74,9 +170,9 @@ impl<T: Deref<Target = [f64]> + DerefMut<Target = [f64]>> State for T {
     ) {
         for i in 0..count.min(self.len()) {
             if bits & 1.into() == 1.into() {
-                self[i] = 1.;
+                self[i] = T::ONE;
             } else {
-                self[i] = -1.;
+                self[i] = -T::ONE;
             }
 
             bits = bits >> 1.into();
@@ -94,12 +190,12 @@ impl<T: Deref<Target = [f64]> + DerefMut<Target = [f64]>> State for T {
         for i in 0..count.min(self.len()) {
             if mask & 1.into() == 1.into() {
                 if bits & 1.into() == 1.into() {
-                    self[i] = 1.;
+                    self[i] = T::ONE;
                 } else {
-                    self[i] = -1.;
+                    self[i] = -T::ONE;
                 }
             } else {
-                self[i] = 0.;
+                self[i] = T::ZERO;
             }
 
             bits = bits >> 1.into();
@@ -109,7 +205,7 @@ impl<T: Deref<Target = [f64]> + DerefMut<Target = [f64]>> State for T {
 
     fn softmax(&mut self) {
         if self.len() > 0 {
-            let mut acc = 0.;
+            let mut acc = T::ZERO;
 
             for i in 0..self.len() {
                 let v = self[i].exp();
@@ -117,18 +213,134 @@ impl<T: Deref<Target = [f64]> + DerefMut<Target = [f64]>> State for T {
                 self[i] = v;
             }
 
-            if acc > f64::EPSILON {
+            if acc > T::EPSILON {
                 for i in 0..self.len() {
                     self[i] /= acc;
                 }
             }
         }
     }
+
+    fn flip_random(&mut self, rng: &mut impl Rng, count: usize) -> Vec<bool> {
+        let mut mask = vec![false; self.len()];
+
+        for i in index::sample(rng, self.len(), count.min(self.len())) {
+            self[i] = -self[i];
+            mask[i] = true;
+        }
+
+        mask
+    }
+
+    fn flip_with_probability(&mut self, rng: &mut impl Rng, p: f64) -> Vec<bool> {
+        let mut mask = vec![false; self.len()];
+
+        for i in 0..self.len() {
+            if rng.random_bool(p) {
+                self[i] = -self[i];
+                mask[i] = true;
+            }
+        }
+
+        mask
+    }
+
+    fn erase_random(&mut self, rng: &mut impl Rng, count: usize) -> Vec<bool> {
+        let mut mask = vec![false; self.len()];
+
+        for i in index::sample(rng, self.len(), count.min(self.len())) {
+            self[i] = T::ZERO;
+            mask[i] = true;
+        }
+
+        mask
+    }
+
+    fn erase_block(&mut self, rng: &mut impl Rng, len: usize) -> Vec<bool> {
+        let mut mask = vec![false; self.len()];
+        let len = len.min(self.len());
+        let start = rng.random_range(0..=(self.len() - len));
+
+        for i in start..(start + len) {
+            self[i] = T::ZERO;
+            mask[i] = true;
+        }
+
+        mask
+    }
+
+    fn occlude(
+        &mut self,
+        width: usize,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        value: T,
+    ) -> Vec<bool> {
+        let mut mask = vec![false; self.len()];
+        let height = self.len() / width.max(1);
+
+        for r in y.min(height)..y.saturating_add(h).min(height) {
+            for c in x.min(width)..x.saturating_add(w).min(width) {
+                self[r * width + c] = value;
+                mask[r * width + c] = true;
+            }
+        }
+
+        mask
+    }
+
+    fn salt_and_pepper(&mut self, rng: &mut impl Rng, p: f64) -> Vec<bool> {
+        let mut mask = vec![false; self.len()];
+
+        for i in 0..self.len() {
+            if rng.random_bool(p) {
+                self[i] = if rng.random_bool(0.5) {
+                    T::ONE
+                } else {
+                    -T::ONE
+                };
+                mask[i] = true;
+            }
+        }
+
+        mask
+    }
+
+    fn add_gaussian_noise(&mut self, rng: &mut impl Rng, sigma: T) -> Vec<bool> {
+        for i in 0..self.len() {
+            self[i] += sigma * T::from_f64(gaussian(rng));
+        }
+
+        vec![sigma != T::ZERO; self.len()]
+    }
+
+    fn try_occlude(
+        &mut self,
+        width: usize,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        value: T,
+    ) -> Result<Vec<bool>, HopfieldError> {
+        if width == 0 || !self.len().is_multiple_of(width) {
+            return Err(HopfieldError::InvalidParameter {
+                name: "width",
+                reason: "must be positive and divide the state size",
+            });
+        }
+        check_finite("value", &[value])?;
+
+        Ok(self.occlude(width, x, y, w, h, value))
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::seed::Seed;
 
     #[test]
     fn from_bits_works() {
@@ -180,10 +392,21 @@ mod test {
         }
     }
 
+    #[test]
+    fn works_with_f32() {
+        let mut v = vec![0.0_f32; 4];
+
+        v.from_bits(4, 0b0101_u8);
+        v.decay(0.5);
+
+        assert_eq!(v, vec![0.5, -0.5, 0.5, -0.5]);
+        assert_eq!(v.error_norm(&[1., -1., 1., -1.]), 0.5);
+    }
+
     #[test]
     fn add_noise_is_bounded() {
         let mut v = vec![1.0_f64; 10000];
-        let mut rng = rand::rng();
+        let mut rng = Seed(1).rng();
 
         v.add_noise(&mut rng, 1.0);
 
@@ -192,4 +415,85 @@ mod test {
             assert!(v[i] >= 0.0);
         }
     }
+
+    #[test]
+    fn corruptions_report_their_masks() {
+        let p: Vec<f64> = (0..100)
+            .map(|i| if i % 3 == 0 { 1. } else { -1. })
+            .collect();
+        let mut rng = Seed(5).rng();
+
+        let mut v = p.clone();
+        let mask = v.flip_random(&mut rng, 17);
+        assert_eq!(mask.iter().filter(|m| **m).count(), 17);
+        for i in 0..100 {
+            assert_eq!(v[i] != p[i], mask[i]);
+        }
+
+        let mut v = p.clone();
+        let mask = v.erase_block(&mut rng, 10);
+        let start = mask.iter().position(|m| *m).unwrap();
+        assert!(mask[start..start + 10].iter().all(|m| *m));
+        assert_eq!(mask.iter().filter(|m| **m).count(), 10);
+        assert_eq!(v[start + 9], 0.);
+
+        let mut v = p.clone();
+        let mask = v.erase_random(&mut rng, 1000);
+        assert!(mask.iter().all(|m| *m) && v.iter().all(|x| *x == 0.));
+
+        // A 10x10 image with a 3x2 rectangle hanging over the right edge.
+        let mut v = p.clone();
+        let mask = v.occlude(10, 8, 4, 3, 2, 0.5);
+        assert_eq!(mask.iter().filter(|m| **m).count(), 4);
+        assert!(mask[48] && mask[49] && mask[58] && mask[59]);
+        assert_eq!(v[59], 0.5);
+
+        let mut v = p.clone();
+        let mask = v.salt_and_pepper(&mut rng, 0.3);
+        for i in 0..100 {
+            assert!(mask[i] || v[i] == p[i]);
+            assert_eq!(v[i].abs(), 1.);
+        }
+
+        let mut v = vec![0.; 10000];
+        assert!(v.add_gaussian_noise(&mut rng, 2.).iter().all(|m| *m));
+        let var = v.iter().map(|x| x * x).sum::<f64>() / 10000.;
+        assert!((var - 4.).abs() < 0.3);
+
+        let mut v = p.clone();
+        let mask = v.flip_with_probability(&mut rng, 0.);
+        assert!(!mask.iter().any(|m| *m) && v == p);
+    }
+
+    #[test]
+    fn try_corruptions_check_parameters() {
+        let p: Vec<f64> = (0..100)
+            .map(|i| if i % 3 == 0 { 1. } else { -1. })
+            .collect();
+        let mut rng = Seed(6).rng();
+        let mut v = p.clone();
+
+        assert!(v.try_flip_with_probability(&mut rng, 1.5).is_err());
+        assert!(v.try_flip_with_probability(&mut rng, f64::NAN).is_err());
+        assert!(v.try_salt_and_pepper(&mut rng, -0.1).is_err());
+        assert!(v.try_add_gaussian_noise(&mut rng, -1.).is_err());
+        assert!(v.try_occlude(0, 0, 0, 1, 1, 0.).is_err());
+        assert!(v.try_occlude(7, 0, 0, 1, 1, 0.).is_err());
+        assert!(v.try_occlude(10, 0, 0, 1, 1, f64::NAN).is_err());
+        assert_eq!(v, p);
+
+        assert_eq!(
+            v.try_flip_with_probability(&mut rng, 1.).unwrap(),
+            vec![true; 100]
+        );
+        assert_eq!(
+            v.try_salt_and_pepper(&mut rng, 0.).unwrap(),
+            vec![false; 100]
+        );
+
+        // The rectangle is clipped instead of overflowing.
+        let mask = v.try_occlude(10, 9, 9, usize::MAX, usize::MAX, 0.).unwrap();
+        assert_eq!(mask.iter().filter(|m| **m).count(), 1);
+        assert_eq!(v[99], 0.);
+    }
 }